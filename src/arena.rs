@@ -1,4 +1,6 @@
-use std::{fmt, hash, marker::PhantomData, num::NonZeroU32};
+use crate::FastHashMap;
+
+use std::{convert::TryFrom, fmt, hash, marker::PhantomData, num::NonZeroU32};
 
 /// An unique index in the arena array that a handle points to.
 ///
@@ -11,7 +13,7 @@ type Index = NonZeroU32;
 /// A strongly typed reference to a SPIR-V element.
 #[repr(transparent)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize), serde(into = "SerHandle"))]
-#[cfg_attr(feature = "deserialize", derive(serde::Deserialize), serde(from = "SerHandle"))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize), serde(try_from = "SerHandle"))]
 pub struct Handle<T> {
     index: Index,
     marker: PhantomData<T>,
@@ -24,31 +26,33 @@ pub struct Handle<T> {
 enum SerHandle {
     // The single-variant enum makes the serialized RON representation look like `Handle(42)`.
     // Otherwise it would just look like `42`.
-    Handle(Index)
+    // The index is a plain integer, so that a zero is reported as an error
+    // when converting to a handle instead of failing to deserialize `NonZeroU32`.
+    Handle(u32)
 }
 
 #[cfg(feature = "serialize")]
 impl<T> From<Handle<T>> for SerHandle {
     fn from(handle: Handle<T>) -> Self {
-        SerHandle::Handle(handle.index)
+        SerHandle::Handle(handle.index.get())
     }
 }
 
 #[cfg(feature = "deserialize")]
-impl<T> From<SerHandle> for Handle<T> {
-    fn from(handle: SerHandle) -> Self {
+impl<T> TryFrom<SerHandle> for Handle<T> {
+    type Error = &'static str;
+    fn try_from(handle: SerHandle) -> Result<Self, Self::Error> {
         match handle {
-            SerHandle::Handle(index) => Handle { index, marker: PhantomData },
+            SerHandle::Handle(index) => Index::new(index)
+                .map(Handle::new)
+                .ok_or("handle index must be non-zero"),
         }
     }
 }
 
 impl<T> Clone for Handle<T> {
     fn clone(&self) -> Self {
-        Handle {
-            index: self.index,
-            marker: self.marker,
-        }
+        *self
     }
 }
 impl<T> Copy for Handle<T> {}
@@ -83,6 +87,14 @@ impl<T> Handle<T> {
         }
     }
 
+    /// Creates a handle from a zero-based index into an arena.
+    ///
+    /// Returns `None` if the index does not fit into a handle.
+    pub fn from_usize(index: usize) -> Option<Self> {
+        let position = u32::try_from(index).ok()?.checked_add(1)?;
+        Index::new(position).map(Handle::new)
+    }
+
     /// Returns the zero-based index of this handle.
     pub fn index(self) -> usize {
         let index = self.index.get() - 1;
@@ -92,7 +104,7 @@ impl<T> Handle<T> {
 
 /// An arena holding some kind of component (e.g., type, constant,
 /// instruction, etc.) that can be referenced.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub struct Arena<T> {
@@ -111,11 +123,32 @@ impl<T> Arena<T> {
         Arena { data: Vec::new() }
     }
 
+    pub fn with_capacity(capacity: usize) -> Self {
+        Arena { data: Vec::with_capacity(capacity) }
+    }
+
+    /// Reserves capacity for at least `additional` more elements.
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+    }
+
     pub fn len(&self) -> usize {
         self.data.len()
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (Handle<T>, &T)> {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Removes all the elements, keeping the allocated capacity.
+    ///
+    /// All the handles previously returned by this arena become invalid,
+    /// and new elements will reuse them.
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (Handle<T>, &T)> + ExactSizeIterator {
         self.data.iter().enumerate().map(|(i, v)| {
             let position = i + 1;
             let index = unsafe { Index::new_unchecked(position as u32) };
@@ -123,6 +156,33 @@ impl<T> Arena<T> {
         })
     }
 
+    /// Returns the handles of all the elements, without borrowing them.
+    pub fn handles(&self) -> impl Iterator<Item = Handle<T>> {
+        (1 ..= self.data.len()).map(|position| {
+            let index = unsafe { Index::new_unchecked(position as u32) };
+            Handle::new(index)
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = (Handle<T>, &mut T)> + ExactSizeIterator {
+        self.data.iter_mut().enumerate().map(|(i, v)| {
+            let position = i + 1;
+            let index = unsafe { Index::new_unchecked(position as u32) };
+            (Handle::new(index), v)
+        })
+    }
+
+    /// Removes all the elements, yielding them by value with their handles.
+    ///
+    /// The arena stays usable afterwards, and new elements will reuse the handles.
+    pub fn drain(&mut self) -> impl Iterator<Item = (Handle<T>, T)> + '_ {
+        self.data.drain(..).enumerate().map(|(i, v)| {
+            let position = i + 1;
+            let index = unsafe { Index::new_unchecked(position as u32) };
+            (Handle::new(index), v)
+        })
+    }
+
     /// Adds a new value to the arena, returning a typed handle.
     ///
     /// The value is not linked to any SPIR-V module.
@@ -147,6 +207,91 @@ impl<T> Arena<T> {
             self.append(value)
         }
     }
+
+    /// Adds a value with a custom check for uniqueness: returns a handle
+    /// pointing to the first existing element for which `eq` returns true,
+    /// or adds a new element otherwise.
+    pub fn fetch_if<F>(&mut self, value: T, eq: F) -> Handle<T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        match self.position(eq) {
+            Some(handle) => handle,
+            None => self.append(value),
+        }
+    }
+
+    /// Returns the handle of the first element for which `pred` returns true.
+    pub fn position<F>(&self, pred: F) -> Option<Handle<T>>
+    where
+        F: Fn(&T) -> bool,
+    {
+        self.data.iter().position(pred).map(|index| {
+            let index = unsafe { Index::new_unchecked((index + 1) as u32) };
+            Handle::new(index)
+        })
+    }
+
+    /// Moves all the elements of `other` to the end of this arena.
+    ///
+    /// Returns a function mapping the handles of `other` to the handles
+    /// of the same elements in this arena, so that references between
+    /// the moved elements can be rewritten.
+    pub fn append_arena(&mut self, mut other: Arena<T>) -> impl Fn(Handle<T>) -> Handle<T> {
+        let offset = self.data.len() as u32;
+        self.data.append(&mut other.data);
+        move |handle| {
+            let index = handle.index.get()
+                .checked_add(offset)
+                .and_then(Index::new)
+                .expect("Appended arena handle overflows the index");
+            Handle::new(index)
+        }
+    }
+
+    /// Removes the elements for which `keep` returns false.
+    ///
+    /// Since the remaining elements are shifted down, the handles to them
+    /// change. Returns a map from the old handles of the retained elements
+    /// to their new handles. Handles of removed elements are not in the map.
+    pub fn retain<F>(&mut self, mut keep: F) -> FastHashMap<Handle<T>, Handle<T>>
+    where
+        F: FnMut(Handle<T>, &T) -> bool,
+    {
+        let mut remap = FastHashMap::default();
+        let old_data = std::mem::take(&mut self.data);
+        for (i, value) in old_data.into_iter().enumerate() {
+            let index = unsafe { Index::new_unchecked((i + 1) as u32) };
+            let old_handle = Handle::new(index);
+            if keep(old_handle, &value) {
+                let new_handle = self.append(value);
+                remap.insert(old_handle, new_handle);
+            }
+        }
+        remap
+    }
+
+    /// Returns true if the handle is within the bounds of this arena.
+    ///
+    /// Handles only carry an index, so a handle from a different arena
+    /// of the same type is accepted as long as its index is in range.
+    pub fn contains(&self, handle: Handle<T>) -> bool {
+        handle.index() < self.data.len()
+    }
+
+    /// Returns a reference to the element pointed to by the handle,
+    /// or `None` if the handle is out of bounds of this arena.
+    pub fn try_get(&self, handle: Handle<T>) -> Option<&T> {
+        let index = handle.index.get() - 1;
+        self.data.get(index as usize)
+    }
+
+    /// Returns a mutable reference to the element pointed to by the handle,
+    /// or `None` if the handle is out of bounds of this arena.
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        let index = handle.index.get() - 1;
+        self.data.get_mut(index as usize)
+    }
 }
 
 impl<T> std::ops::Index<Handle<T>> for Arena<T> {
@@ -157,10 +302,54 @@ impl<T> std::ops::Index<Handle<T>> for Arena<T> {
     }
 }
 
+impl<T> std::ops::IndexMut<Handle<T>> for Arena<T> {
+    fn index_mut(&mut self, handle: Handle<T>) -> &mut T {
+        let index = handle.index.get() - 1;
+        &mut self.data[index as usize]
+    }
+}
+
+impl<T> IntoIterator for Arena<T> {
+    type Item = (Handle<T>, T);
+    type IntoIter = std::iter::Map<
+        std::iter::Enumerate<std::vec::IntoIter<T>>,
+        fn((usize, T)) -> (Handle<T>, T),
+    >;
+
+    /// Consumes the arena, yielding the values with their handles,
+    /// numbered the same way as by `iter`.
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.into_iter().enumerate().map(|(i, v)| {
+            let position = i + 1;
+            let index = unsafe { Index::new_unchecked(position as u32) };
+            (Handle::new(index), v)
+        })
+    }
+}
+
+impl<T> std::iter::FromIterator<T> for Arena<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Arena { data: iter.into_iter().collect() }
+    }
+}
+
+impl<T> Extend<T> for Arena<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.data.extend(iter);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn handle_from_usize() {
+        let handle = Handle::<u8>::from_usize(5).unwrap();
+        assert_eq!(handle.index(), 5);
+        assert!(Handle::<u8>::from_usize(std::u32::MAX as usize).is_none());
+    }
+
     #[test]
     fn append_non_unique() {
         let mut arena: Arena<u8> = Arena::new();
@@ -197,6 +386,227 @@ mod tests {
         assert!(arena[t1] != arena[t2]);
     }
 
+    #[test]
+    fn fetch_if() {
+        let mut arena: Arena<u8> = Arena::new();
+        let even = arena.fetch_if(0, |&v| v % 2 == 0);
+        let odd = arena.fetch_if(1, |&v| v % 2 == 1);
+        assert!(even != odd);
+        assert_eq!(arena.fetch_if(4, |&v| v % 2 == 0), even);
+        assert_eq!(arena.fetch_if(3, |&v| v % 2 == 1), odd);
+        assert_eq!(arena.len(), 2);
+        assert_eq!(arena[even], 0);
+        assert_eq!(arena[odd], 1);
+    }
+
+    #[test]
+    fn index_mut() {
+        let mut arena: Arena<u8> = Arena::new();
+        let t1 = arena.append(0);
+        arena[t1] = 5;
+        assert_eq!(arena[t1], 5);
+    }
+
+    #[test]
+    fn handles() {
+        let mut arena: Arena<u8> = Arena::new();
+        for i in 0 .. 3 {
+            arena.append(i);
+        }
+        let handles = arena.handles().collect::<Vec<_>>();
+        for &handle in handles.iter() {
+            arena[handle] += 1;
+        }
+        let indices = handles.iter().map(|h| h.index()).collect::<Vec<_>>();
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert_eq!(arena[handles[2]], 3);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut arena: Arena<u8> = Arena::new();
+        let t1 = arena.append(1);
+        let t2 = arena.append(2);
+        for (_, value) in arena.iter_mut() {
+            *value *= 2;
+        }
+        assert_eq!(arena[t1], 2);
+        assert_eq!(arena[t2], 4);
+        let handles = arena.iter_mut().map(|(h, _)| h).collect::<Vec<_>>();
+        assert_eq!(handles, vec![t1, t2]);
+    }
+
+    #[test]
+    fn iter_rev() {
+        let mut arena: Arena<u8> = Arena::new();
+        arena.append(10);
+        arena.append(20);
+        arena.append(30);
+        assert_eq!(arena.iter().len(), 3);
+        let values = arena.iter()
+            .rev()
+            .map(|(h, &v)| (h.index.get(), v))
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![(3, 30), (2, 20), (1, 10)]);
+        for (h, v) in arena.iter_mut().rev().take(1) {
+            assert_eq!(h.index.get(), 3);
+            *v += 1;
+        }
+        assert_eq!(arena.iter().last().map(|(_, &v)| v), Some(31));
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut arena: Arena<u8> = Arena::new();
+        let t1 = arena.append(10);
+        let t2 = arena.append(20);
+        let t3 = arena.append(30);
+        let values = arena.into_iter().collect::<Vec<(Handle<u8>, u8)>>();
+        assert_eq!(values, vec![(t1, 10), (t2, 20), (t3, 30)]);
+        let indices = values.iter().map(|&(h, _)| h.index.get()).collect::<Vec<_>>();
+        assert_eq!(indices, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn position() {
+        let mut arena: Arena<u8> = Arena::new();
+        arena.append(1);
+        let t2 = arena.append(4);
+        arena.append(6);
+        assert_eq!(arena.position(|&v| v % 2 == 0), Some(t2));
+        assert_eq!(arena.position(|&v| v > 10), None);
+    }
+
+    #[test]
+    fn drain() {
+        let mut arena: Arena<u8> = Arena::new();
+        let t1 = arena.append(10);
+        let t2 = arena.append(20);
+        let values = arena.drain().collect::<Vec<_>>();
+        assert_eq!(values, vec![(t1, 10), (t2, 20)]);
+        assert!(arena.is_empty());
+        assert_eq!(arena.append(30), t1);
+    }
+
+    #[test]
+    fn from_iter() {
+        let mut arena = (0u8 .. 3).collect::<Arena<u8>>();
+        let handles = arena.iter().map(|(h, _)| h).collect::<Vec<_>>();
+        assert_eq!(handles.len(), 3);
+        for (i, &handle) in handles.iter().enumerate() {
+            assert_eq!(handle.index(), i);
+            assert_eq!(arena[handle], i as u8);
+        }
+        arena.extend(vec![3, 4]);
+        assert_eq!(arena.len(), 5);
+        let t5 = arena.append(5);
+        assert_eq!(t5.index(), 5);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut arena: Arena<u8> = Arena::with_capacity(10);
+        assert!(arena.data.capacity() >= 10);
+        arena.reserve(20);
+        assert!(arena.data.capacity() >= 20);
+    }
+
+    #[test]
+    fn is_empty() {
+        let mut arena: Arena<u8> = Arena::new();
+        assert!(arena.is_empty());
+        arena.append(0);
+        assert!(!arena.is_empty());
+    }
+
+    #[test]
+    fn clear() {
+        let mut arena: Arena<u8> = Arena::new();
+        arena.append(0);
+        arena.append(1);
+        let capacity = arena.data.capacity();
+        arena.clear();
+        assert_eq!(arena.len(), 0);
+        assert_eq!(arena.data.capacity(), capacity);
+        let t1 = arena.append(2);
+        assert_eq!(t1.index.get(), 1);
+        assert_eq!(arena[t1], 2);
+    }
+
+    #[test]
+    fn retain() {
+        let mut arena: Arena<u8> = Arena::new();
+        let t1 = arena.append(0);
+        let t2 = arena.append(1);
+        let t3 = arena.append(2);
+        let remap = arena.retain(|handle, _| handle != t2);
+        assert_eq!(arena.len(), 2);
+        assert_eq!(remap.len(), 2);
+        assert_eq!(remap[&t1], t1);
+        assert!(!remap.contains_key(&t2));
+        let new_t3 = remap[&t3];
+        assert_eq!(t3.index(), 2);
+        assert_eq!(new_t3.index(), 1);
+        assert_eq!(arena[new_t3], 2);
+    }
+
+    #[test]
+    fn append_arena() {
+        let mut arena: Arena<u8> = Arena::new();
+        let mut other: Arena<u8> = Arena::new();
+        for i in 0 .. 3 {
+            arena.append(i);
+        }
+        let handles = (3 .. 6).map(|i| other.append(i)).collect::<Vec<_>>();
+        let remap = arena.append_arena(other);
+        assert_eq!(arena.len(), 6);
+        for (i, &handle) in handles.iter().enumerate() {
+            let new_handle = remap(handle);
+            assert_eq!(new_handle.index(), handle.index() + 3);
+            assert_eq!(arena[new_handle], i as u8 + 3);
+        }
+    }
+
+    #[test]
+    fn try_get() {
+        let mut arena: Arena<u8> = Arena::new();
+        let t1 = arena.append(0);
+        let t2 = arena.append(1);
+        assert_eq!(arena.try_get(t1), Some(&0));
+        assert_eq!(arena.try_get(t2), Some(&1));
+        assert_eq!(arena.try_get(Handle::DUMMY), None);
+    }
+
+    #[test]
+    fn contains() {
+        let mut arena: Arena<u8> = Arena::new();
+        let t1 = arena.append(0);
+        assert!(arena.contains(t1));
+        assert!(!arena.contains(Handle::from_usize(1).unwrap()));
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut arena: Arena<u8> = Arena::new();
+        let t1 = arena.append(0);
+        if let Some(value) = arena.get_mut(t1) {
+            *value = 3;
+        }
+        assert_eq!(arena[t1], 3);
+        assert_eq!(arena.get_mut(Handle::DUMMY), None);
+    }
+
+    #[test]
+    #[cfg(feature = "deserialize")]
+    fn try_get_deserialized() {
+        let mut arena: Arena<u8> = Arena::new();
+        let _ = arena.append(0);
+        let valid: Handle<u8> = ron::de::from_str("Handle(1)").unwrap();
+        let dangling: Handle<u8> = ron::de::from_str("Handle(2)").unwrap();
+        assert_eq!(arena.try_get(valid), Some(&0));
+        assert_eq!(arena.try_get(dangling), None);
+    }
+
     #[test]
     #[cfg(feature = "serialize")]
     fn handle_ser() {
@@ -212,6 +622,13 @@ mod tests {
         assert_eq!(handle_de.index, TestHandle::DUMMY.index);
     }
 
+    #[test]
+    #[cfg(feature = "deserialize")]
+    fn handle_de_zero() {
+        let result = ron::de::from_str::<Handle<()>>("Handle(0)");
+        assert!(result.is_err());
+    }
+
     #[test]
     #[cfg(all(feature = "serialize", feature = "deserialize"))]
     fn handle_ser_de() {
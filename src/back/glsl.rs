@@ -0,0 +1,759 @@
+/*! OpenGL Shading Language (GLSL) backend
+
+## Entry points
+
+A GLSL shader has a single `void main()` function, so only modules with
+at most one entry point can be written. The entry point function is
+renamed to `main` and can't take parameters or return a value.
+
+## Built-ins
+
+Variables bound to built-ins are not declared. Instead, expressions refer
+to the corresponding `gl_` variable directly, like `gl_Position`.
+
+## Functions
+
+All functions other than the entry point are declared by a prototype
+before any of them is defined, so that they can be called in any order.
+
+## Resource bindings
+
+GLSL has no descriptor sets, so only the binding index of a resource is
+written, with `layout(binding = N)`. That needs GLSL 420 or GLSL ES 310;
+older versions can't declare resources.
+
+## Loops
+
+GLSL has no equivalent of a `continuing` block, so only loops without
+one can be written, as `while (true)`.
+!*/
+
+use std::fmt::{Error as FmtError, Write};
+
+use super::{binary_operator_string, name_or_index, FunctionTypes, COMPONENTS, INDENT};
+use crate::{arena::Handle, proc::ResolveError};
+
+/// GLSL version to emit in the `#version` header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Version {
+    /// Desktop GLSL, like `450` for `#version 450 core`.
+    /// Versions before 150 have no profiles, like `#version 110`.
+    Desktop(u16),
+    /// GLSL ES, like `310` for `#version 310 es`.
+    Embedded(u16),
+}
+
+impl Version {
+    /// Whether `layout(binding = N)` is available.
+    fn supports_binding_layout(self) -> bool {
+        match self {
+            Version::Desktop(number) => number >= 420,
+            Version::Embedded(number) => number >= 310,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Format(FmtError),
+    Resolve(ResolveError),
+    MultipleEntryPoints,
+    InvalidEntryPoint(Handle<crate::Function>),
+    UnsupportedType(Handle<crate::Type>),
+    UnsupportedStorageClass(crate::StorageClass),
+    UnsupportedBuiltIn(spirv::BuiltIn),
+    UnsupportedExpression(Handle<crate::Expression>),
+    /// A statement of the given function can't be written.
+    UnsupportedStatement(Handle<crate::Function>, crate::Statement),
+}
+
+impl From<FmtError> for Error {
+    fn from(e: FmtError) -> Self {
+        Error::Format(e)
+    }
+}
+
+fn scalar_string(kind: crate::ScalarKind, width: crate::Bytes) -> Option<&'static str> {
+    match (kind, width) {
+        (crate::ScalarKind::Float, 32) => Some("float"),
+        (crate::ScalarKind::Sint, 32) => Some("int"),
+        (crate::ScalarKind::Uint, 32) => Some("uint"),
+        (crate::ScalarKind::Bool, 1) => Some("bool"),
+        _ => None,
+    }
+}
+
+fn vector_prefix(kind: crate::ScalarKind, width: crate::Bytes) -> Option<&'static str> {
+    match (kind, width) {
+        (crate::ScalarKind::Float, 32) => Some(""),
+        (crate::ScalarKind::Sint, 32) => Some("i"),
+        (crate::ScalarKind::Uint, 32) => Some("u"),
+        (crate::ScalarKind::Bool, 1) => Some("b"),
+        _ => None,
+    }
+}
+
+/// Return the storage qualifier, or `None` for plain globals.
+fn storage_class_string(class: crate::StorageClass) -> Result<Option<&'static str>, Error> {
+    match class {
+        crate::StorageClass::Input => Ok(Some("in")),
+        crate::StorageClass::Output => Ok(Some("out")),
+        crate::StorageClass::Uniform => Ok(Some("uniform")),
+        crate::StorageClass::WorkGroup => Ok(Some("shared")),
+        crate::StorageClass::Private => Ok(None),
+        other => Err(Error::UnsupportedStorageClass(other)),
+    }
+}
+
+fn built_in_string(built_in: spirv::BuiltIn) -> Result<&'static str, Error> {
+    match built_in {
+        spirv::BuiltIn::Position => Ok("gl_Position"),
+        spirv::BuiltIn::VertexId => Ok("gl_VertexID"),
+        spirv::BuiltIn::InstanceId => Ok("gl_InstanceID"),
+        spirv::BuiltIn::FragCoord => Ok("gl_FragCoord"),
+        spirv::BuiltIn::FragDepth => Ok("gl_FragDepth"),
+        spirv::BuiltIn::GlobalInvocationId => Ok("gl_GlobalInvocationID"),
+        spirv::BuiltIn::LocalInvocationId => Ok("gl_LocalInvocationID"),
+        other => Err(Error::UnsupportedBuiltIn(other)),
+    }
+}
+
+struct Writer<'a> {
+    module: &'a crate::Module,
+    version: Version,
+    out: String,
+    entry_point: Option<Handle<crate::Function>>,
+    types: FunctionTypes,
+}
+
+impl Writer<'_> {
+    fn type_string(&self, handle: Handle<crate::Type>) -> Result<String, Error> {
+        let ty = &self.module.types[handle];
+        let unsupported = || Error::UnsupportedType(handle);
+        Ok(match ty.inner {
+            crate::TypeInner::Scalar { kind, width } => {
+                scalar_string(kind, width).ok_or_else(unsupported)?.to_owned()
+            }
+            crate::TypeInner::Vector { size, kind, width } => {
+                let prefix = vector_prefix(kind, width).ok_or_else(unsupported)?;
+                format!("{}vec{}", prefix, size as u8)
+            }
+            crate::TypeInner::Matrix { columns, rows, kind: crate::ScalarKind::Float, width: 32 } => {
+                if columns == rows {
+                    format!("mat{}", columns as u8)
+                } else {
+                    format!("mat{}x{}", columns as u8, rows as u8)
+                }
+            }
+            crate::TypeInner::Array { base, size: crate::ArraySize::Static(length) } => {
+                format!("{}[{}]", self.type_string(base)?, length)
+            }
+            crate::TypeInner::Array { base, size: crate::ArraySize::Dynamic } => {
+                format!("{}[]", self.type_string(base)?)
+            }
+            crate::TypeInner::Struct { .. } => name_or_index(&ty.name, "Struct", handle),
+            _ => return Err(unsupported()),
+        })
+    }
+
+    fn write_constant(&mut self, handle: Handle<crate::Constant>) -> Result<(), Error> {
+        let constant = &self.module.constants[handle];
+        match constant.inner {
+            crate::ConstantInner::Sint(value) => write!(self.out, "{}", value)?,
+            crate::ConstantInner::Uint(value) => write!(self.out, "{}u", value)?,
+            crate::ConstantInner::Float(value) => {
+                write!(self.out, "{}", value)?;
+                if value.fract() == 0.0 {
+                    self.out.write_str(".0")?;
+                }
+            }
+            crate::ConstantInner::Bool(value) => write!(self.out, "{}", value)?,
+            crate::ConstantInner::Composite(ref constituents) => {
+                let ty_name = self.type_string(constant.ty)?;
+                write!(self.out, "{}(", ty_name)?;
+                for (i, &constituent) in constituents.iter().enumerate() {
+                    if i != 0 {
+                        self.out.write_str(", ")?;
+                    }
+                    self.write_constant(constituent)?;
+                }
+                self.out.write_str(")")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_expression(
+        &mut self,
+        expr_handle: Handle<crate::Expression>,
+        fun: &crate::Function,
+    ) -> Result<(), Error> {
+        let module = self.module;
+        match fun.expressions[expr_handle] {
+            crate::Expression::Access { base, index } => {
+                self.write_expression(base, fun)?;
+                self.out.write_str("[")?;
+                self.write_expression(index, fun)?;
+                self.out.write_str("]")?;
+            }
+            crate::Expression::AccessIndex { base, index } => {
+                let accessor = self.types
+                    .access_index_string(base, index, fun, module)
+                    .map_err(Error::Resolve)?
+                    .ok_or(Error::UnsupportedExpression(expr_handle))?;
+                self.write_expression(base, fun)?;
+                self.out.write_str(&accessor)?;
+            }
+            crate::Expression::Swizzle { size, vector, ref pattern } => {
+                self.write_expression(vector, fun)?;
+                self.out.write_str(".")?;
+                for &component in &pattern[.. size as usize] {
+                    self.out.write_char(COMPONENTS[component as usize])?;
+                }
+            }
+            crate::Expression::Constant(handle) => self.write_constant(handle)?,
+            crate::Expression::Compose { ty, ref components } => {
+                let ty_name = self.type_string(ty)?;
+                write!(self.out, "{}(", ty_name)?;
+                for (i, &component) in components.iter().enumerate() {
+                    if i != 0 {
+                        self.out.write_str(", ")?;
+                    }
+                    self.write_expression(component, fun)?;
+                }
+                self.out.write_str(")")?;
+            }
+            crate::Expression::FunctionParameter(index) => write!(self.out, "param{}", index)?,
+            crate::Expression::GlobalVariable(handle) => {
+                let var = &module.global_variables[handle];
+                match var.binding {
+                    Some(crate::Binding::BuiltIn(built_in)) => {
+                        self.out.write_str(built_in_string(built_in)?)?;
+                    }
+                    _ => self.out.write_str(&name_or_index(&var.name, "global", handle))?,
+                }
+            }
+            crate::Expression::LocalVariable(handle) => {
+                let var = &fun.local_variables[handle];
+                self.out.write_str(&name_or_index(&var.name, "local", handle))?;
+            }
+            crate::Expression::Load { pointer } => self.write_expression(pointer, fun)?,
+            crate::Expression::Unary { op, expr } => {
+                self.out.write_str(match op {
+                    crate::UnaryOperator::Negate => "-",
+                    crate::UnaryOperator::Not => "!",
+                })?;
+                self.write_expression(expr, fun)?;
+            }
+            crate::Expression::Binary { op, left, right } => {
+                self.out.write_str("(")?;
+                self.write_expression(left, fun)?;
+                write!(self.out, " {} ", binary_operator_string(op))?;
+                self.write_expression(right, fun)?;
+                self.out.write_str(")")?;
+            }
+            _ => return Err(Error::UnsupportedExpression(expr_handle)),
+        }
+        Ok(())
+    }
+
+    fn write_block(
+        &mut self,
+        level: usize,
+        block: &[crate::Statement],
+        fun_handle: Handle<crate::Function>,
+        fun: &crate::Function,
+    ) -> Result<(), Error> {
+        for statement in block {
+            self.write_statement(level, statement, fun_handle, fun)?;
+        }
+        Ok(())
+    }
+
+    fn write_statement(
+        &mut self,
+        level: usize,
+        statement: &crate::Statement,
+        fun_handle: Handle<crate::Function>,
+        fun: &crate::Function,
+    ) -> Result<(), Error> {
+        let indent = INDENT.repeat(level);
+        match *statement {
+            crate::Statement::Empty |
+            crate::Statement::Line(None) => {}
+            // the directive sets the number of the line that follows it
+            crate::Statement::Line(Some(ref span)) => writeln!(self.out, "#line {}", span.line)?,
+            crate::Statement::Block(ref block) => {
+                writeln!(self.out, "{}{{", indent)?;
+                self.write_block(level + 1, block, fun_handle, fun)?;
+                writeln!(self.out, "{}}}", indent)?;
+            }
+            crate::Statement::If { condition, ref accept, ref reject } => {
+                // an empty `accept` is written by negating the condition instead
+                let (prefix, accept, reject) = if accept.is_empty() && !reject.is_empty() {
+                    ("!", reject, accept)
+                } else {
+                    ("", accept, reject)
+                };
+                write!(self.out, "{}if ({}", indent, prefix)?;
+                self.write_expression(condition, fun)?;
+                writeln!(self.out, ") {{")?;
+                self.write_block(level + 1, accept, fun_handle, fun)?;
+                if !reject.is_empty() {
+                    writeln!(self.out, "{}}} else {{", indent)?;
+                    self.write_block(level + 1, reject, fun_handle, fun)?;
+                }
+                writeln!(self.out, "{}}}", indent)?;
+            }
+            crate::Statement::Switch { selector, ref cases, ref default } => {
+                write!(self.out, "{}switch (", indent)?;
+                self.write_expression(selector, fun)?;
+                writeln!(self.out, ") {{")?;
+                let mut values = cases.keys().cloned().collect::<Vec<_>>();
+                values.sort();
+                for value in values {
+                    writeln!(self.out, "{}{}case {}:", indent, INDENT, value)?;
+                    self.write_block(level + 2, &cases[&value].0, fun_handle, fun)?;
+                }
+                writeln!(self.out, "{}{}default:", indent, INDENT)?;
+                self.write_block(level + 2, default, fun_handle, fun)?;
+                writeln!(self.out, "{}}}", indent)?;
+            }
+            // GLSL has no equivalent of a continuing block
+            crate::Statement::Loop { ref continuing, .. } if !continuing.is_empty() => {
+                return Err(Error::UnsupportedStatement(fun_handle, statement.clone()));
+            }
+            crate::Statement::Loop { ref body, .. } => {
+                writeln!(self.out, "{}while (true) {{", indent)?;
+                self.write_block(level + 1, body, fun_handle, fun)?;
+                writeln!(self.out, "{}}}", indent)?;
+            }
+            crate::Statement::Break => writeln!(self.out, "{}break;", indent)?,
+            crate::Statement::Continue => writeln!(self.out, "{}continue;", indent)?,
+            crate::Statement::Return { value: None } => writeln!(self.out, "{}return;", indent)?,
+            crate::Statement::Return { value: Some(value) } => {
+                write!(self.out, "{}return ", indent)?;
+                self.write_expression(value, fun)?;
+                writeln!(self.out, ";")?;
+            }
+            crate::Statement::Kill => writeln!(self.out, "{}discard;", indent)?,
+            crate::Statement::Store { pointer, value } => {
+                self.out.write_str(&indent)?;
+                self.write_expression(pointer, fun)?;
+                self.out.write_str(" = ")?;
+                self.write_expression(value, fun)?;
+                writeln!(self.out, ";")?;
+            }
+            crate::Statement::Call { function, ref arguments } => {
+                let callee = &self.module.functions[function];
+                write!(self.out, "{}{}(", indent, name_or_index(&callee.name, "function", function))?;
+                for (index, &argument) in arguments.iter().enumerate() {
+                    if index != 0 {
+                        self.out.write_str(", ")?;
+                    }
+                    self.write_expression(argument, fun)?;
+                }
+                writeln!(self.out, ");")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_struct(&mut self, handle: Handle<crate::Type>, members: &[crate::StructMember]) -> Result<(), Error> {
+        writeln!(self.out, "struct {} {{", self.type_string(handle)?)?;
+        for (index, member) in members.iter().enumerate() {
+            let name = match member.name {
+                Some(ref name) => name.clone(),
+                None => format!("member{}", index),
+            };
+            let ty_name = self.type_string(member.ty)?;
+            writeln!(self.out, "{}{} {};", INDENT, ty_name, name)?;
+        }
+        writeln!(self.out, "}};")?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    fn write_global_variable(&mut self, handle: Handle<crate::GlobalVariable>) -> Result<(), Error> {
+        let var = &self.module.global_variables[handle];
+        match var.binding {
+            Some(crate::Binding::BuiltIn(built_in)) => {
+                // built-ins are predeclared, but we still check they are known
+                built_in_string(built_in)?;
+                return Ok(());
+            }
+            Some(crate::Binding::Location(location)) => {
+                write!(self.out, "layout(location = {}) ", location)?;
+            }
+            Some(crate::Binding::Resource { binding, .. }) => {
+                if !self.version.supports_binding_layout() {
+                    return Err(Error::UnsupportedStorageClass(var.class));
+                }
+                write!(self.out, "layout(binding = {}) ", binding)?;
+            }
+            None => {}
+        }
+        if let Some(interpolation) = var.interpolation {
+            let qualifier = match interpolation {
+                crate::Interpolation::Flat => "flat",
+                crate::Interpolation::Linear => "noperspective",
+            };
+            write!(self.out, "{} ", qualifier)?;
+        }
+        if let Some(sampling) = var.sampling {
+            let qualifier = match sampling {
+                crate::Sampling::Centroid => "centroid",
+                crate::Sampling::Sample => "sample",
+            };
+            write!(self.out, "{} ", qualifier)?;
+        }
+        if let Some(class) = storage_class_string(var.class)? {
+            write!(self.out, "{} ", class)?;
+        }
+        let ty_name = self.type_string(var.ty)?;
+        writeln!(self.out, "{} {};", ty_name, name_or_index(&var.name, "global", handle))?;
+        Ok(())
+    }
+
+    /// Write the return type, name and parameters of a function
+    /// other than the entry point.
+    fn write_function_signature(&mut self, handle: Handle<crate::Function>, fun: &crate::Function) -> Result<(), Error> {
+        let return_type = match fun.return_type {
+            Some(ty) => self.type_string(ty)?,
+            None => "void".to_owned(),
+        };
+        write!(self.out, "{} {}(", return_type, name_or_index(&fun.name, "function", handle))?;
+        for (index, &ty) in fun.parameter_types.iter().enumerate() {
+            if index != 0 {
+                self.out.write_str(", ")?;
+            }
+            let ty_name = self.type_string(ty)?;
+            write!(self.out, "{} param{}", ty_name, index)?;
+        }
+        self.out.write_str(")")?;
+        Ok(())
+    }
+
+    fn write_function(&mut self, handle: Handle<crate::Function>, fun: &crate::Function) -> Result<(), Error> {
+        self.types.reset();
+        if self.entry_point == Some(handle) {
+            if !fun.parameter_types.is_empty() || fun.return_type.is_some() {
+                return Err(Error::InvalidEntryPoint(handle));
+            }
+            writeln!(self.out, "void main() {{")?;
+        } else {
+            self.write_function_signature(handle, fun)?;
+            writeln!(self.out, " {{")?;
+        }
+        for (local_handle, local) in fun.local_variables.iter() {
+            let ty_name = self.type_string(local.ty)?;
+            write!(self.out, "{}{} {}", INDENT, ty_name, name_or_index(&local.name, "local", local_handle))?;
+            if let Some(init) = local.init {
+                self.out.write_str(" = ")?;
+                self.write_expression(init, fun)?;
+            }
+            writeln!(self.out, ";")?;
+        }
+        self.write_block(1, &fun.body, handle, fun)?;
+        writeln!(self.out, "}}")?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    fn write(&mut self) -> Result<(), Error> {
+        let module = self.module;
+        match self.version {
+            Version::Desktop(number) if number < 150 => writeln!(self.out, "#version {}", number)?,
+            Version::Desktop(number) => writeln!(self.out, "#version {} core", number)?,
+            Version::Embedded(number) => {
+                writeln!(self.out, "#version {} es", number)?;
+                writeln!(self.out, "precision highp float;")?;
+            }
+        }
+        writeln!(self.out)?;
+
+        for (handle, ty) in module.types.iter() {
+            if let crate::TypeInner::Struct { ref members } = ty.inner {
+                self.write_struct(handle, members)?;
+            }
+        }
+
+        let start = self.out.len();
+        for (handle, _) in module.global_variables.iter() {
+            self.write_global_variable(handle)?;
+        }
+        if self.out.len() != start {
+            writeln!(self.out)?;
+        }
+
+        let start = self.out.len();
+        for (handle, fun) in module.functions.iter() {
+            if self.entry_point != Some(handle) {
+                self.write_function_signature(handle, fun)?;
+                writeln!(self.out, ";")?;
+            }
+        }
+        if self.out.len() != start {
+            writeln!(self.out)?;
+        }
+
+        for (handle, fun) in module.functions.iter() {
+            self.write_function(handle, fun)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write(module: &crate::Module, version: Version) -> Result<String, Error> {
+    let entry_point = match module.entry_points[..] {
+        [] => None,
+        [ref entry_point] => Some(entry_point.function),
+        _ => return Err(Error::MultipleEntryPoints),
+    };
+    let mut writer = Writer {
+        module,
+        version,
+        out: String::new(),
+        entry_point,
+        types: FunctionTypes::new(module),
+    };
+    writer.write()?;
+    Ok(writer.out)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arena::Arena;
+
+    /// Builds a vertex shader storing `vec4(0.0, 0.0, 0.0, 1.0)`
+    /// into a `Position` built-in.
+    fn vertex_module() -> crate::Module {
+        let mut types = Arena::new();
+        let float_ty = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let vec4_ty = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Quad,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        });
+        let mut constants = Arena::new();
+        let components = [0.0, 0.0, 0.0, 1.0]
+            .iter()
+            .map(|&value| constants.fetch_or_append(crate::Constant {
+                name: None,
+                specialization: None,
+                inner: crate::ConstantInner::Float(value),
+                ty: float_ty,
+            }))
+            .collect();
+        let position = constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Composite(components),
+            ty: vec4_ty,
+        });
+        let mut global_variables = Arena::new();
+        let output = global_variables.append(crate::GlobalVariable {
+            name: Some("position".to_owned()),
+            class: crate::StorageClass::Output,
+            binding: Some(crate::Binding::BuiltIn(spirv::BuiltIn::Position)),
+            interpolation: None,
+            sampling: None,
+            ty: vec4_ty,
+            init: None,
+        });
+        let mut expressions = Arena::new();
+        let pointer = expressions.append(crate::Expression::GlobalVariable(output));
+        let value = expressions.append(crate::Expression::Constant(position));
+        let mut functions = Arena::new();
+        let function = functions.append(crate::Function {
+            name: Some("vs_main".to_owned()),
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: Arena::new(),
+            expressions,
+            expression_spans: crate::FastHashMap::default(),
+            body: vec![
+                crate::Statement::Store { pointer, value },
+                crate::Statement::Return { value: None },
+            ],
+        });
+        crate::Module {
+            header: crate::Header {
+                version: (1, 0, 0),
+                generator: 0,
+            },
+            types,
+            constants,
+            global_variables,
+            functions,
+            entry_points: vec![crate::EntryPoint {
+                stage: crate::ShaderStage::Vertex,
+                name: "main".to_owned(),
+                function,
+                workgroup_size: [0; 3],
+            }],
+        }
+    }
+
+    #[test]
+    fn write_vertex_position() {
+        let output = super::write(&vertex_module(), super::Version::Desktop(450)).unwrap();
+        assert!(output.starts_with("#version 450 core\n"));
+        assert!(output.contains("void main() {\n"));
+        assert!(output.contains("    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);\n"));
+        assert!(!output.contains("vs_main"));
+
+        let output = super::write(&vertex_module(), super::Version::Desktop(110)).unwrap();
+        assert!(output.starts_with("#version 110\n"));
+
+        let output = super::write(&vertex_module(), super::Version::Embedded(310)).unwrap();
+        assert!(output.starts_with("#version 310 es\n"));
+    }
+
+    #[test]
+    fn write_lines() {
+        let mut module = vertex_module();
+        let (_, fun) = module.functions.iter_mut().next().unwrap();
+        fun.body.insert(0, crate::Statement::Line(Some(crate::Span {
+            file: Some("position.vert".to_owned()),
+            line: 12,
+            column: 5,
+        })));
+
+        let output = super::write(&module, super::Version::Desktop(450)).unwrap();
+        assert!(output.contains("\
+void main() {
+#line 12
+    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+"));
+    }
+
+    #[test]
+    fn write_if_else() {
+        let mut module = vertex_module();
+        let bool_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Bool, width: 1 },
+        });
+        let flag = module.constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Bool(true),
+            ty: bool_ty,
+        });
+        let (_, fun) = module.functions.iter_mut().next().unwrap();
+        let condition = fun.expressions.append(crate::Expression::Constant(flag));
+        let (pointer, value) = match fun.body[0] {
+            crate::Statement::Store { pointer, value } => (pointer, value),
+            ref other => panic!("Unexpected statement {:?}", other),
+        };
+        fun.body = vec![
+            crate::Statement::If {
+                condition,
+                accept: vec![crate::Statement::Store { pointer, value }],
+                reject: Vec::new(),
+            },
+            crate::Statement::If {
+                condition,
+                accept: vec![crate::Statement::Store { pointer, value }],
+                reject: vec![crate::Statement::Return { value: None }],
+            },
+            crate::Statement::If {
+                condition,
+                accept: Vec::new(),
+                reject: vec![crate::Statement::Store { pointer, value }],
+            },
+        ];
+
+        let output = super::write(&module, super::Version::Desktop(450)).unwrap();
+        assert!(output.contains("\
+void main() {
+    if (true) {
+        gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+    }
+    if (true) {
+        gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+    } else {
+        return;
+    }
+    if (!true) {
+        gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+    }
+}
+"), "{}", output);
+    }
+
+    #[test]
+    fn write_prototypes() {
+        let mut module = vertex_module();
+        let helper = module.functions.append(crate::Function {
+            name: Some("helper".to_owned()),
+            control: spirv::FunctionControl::empty(),
+            parameter_types: vec![module.types.iter().next().unwrap().0],
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: Arena::new(),
+            expressions: Arena::new(),
+            expression_spans: crate::FastHashMap::default(),
+            body: vec![crate::Statement::Return { value: None }],
+        });
+        let (_, fun) = module.functions.iter_mut().next().unwrap();
+        let value = match fun.body[0] {
+            crate::Statement::Store { value, .. } => value,
+            ref other => panic!("Unexpected statement {:?}", other),
+        };
+        let argument = fun.expressions.append(crate::Expression::AccessIndex { base: value, index: 3 });
+        fun.body.insert(0, crate::Statement::Call { function: helper, arguments: vec![argument] });
+
+        let output = super::write(&module, super::Version::Desktop(450)).unwrap();
+        assert!(output.ends_with("\
+void helper(float param0);
+
+void main() {
+    helper(vec4(0.0, 0.0, 0.0, 1.0).w);
+    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+    return;
+}
+
+void helper(float param0) {
+    return;
+}
+
+"), "{}", output);
+    }
+
+    #[test]
+    fn write_resource_binding() {
+        let mut module = vertex_module();
+        let vec4_ty = module.types.iter().nth(1).unwrap().0;
+        module.global_variables.append(crate::GlobalVariable {
+            name: Some("tint".to_owned()),
+            class: crate::StorageClass::Uniform,
+            binding: Some(crate::Binding::Resource { group: 1, binding: 2 }),
+            interpolation: None,
+            sampling: None,
+            ty: vec4_ty,
+            init: None,
+        });
+
+        let output = super::write(&module, super::Version::Desktop(450)).unwrap();
+        assert!(output.contains("layout(binding = 2) uniform vec4 tint;\n"), "{}", output);
+        let output = super::write(&module, super::Version::Embedded(310)).unwrap();
+        assert!(output.contains("layout(binding = 2) uniform vec4 tint;\n"), "{}", output);
+
+        for &version in &[super::Version::Desktop(410), super::Version::Embedded(300)] {
+            match super::write(&module, version) {
+                Err(super::Error::UnsupportedStorageClass(crate::StorageClass::Uniform)) => (),
+                other => panic!("Unexpected result {:?}", other),
+            }
+        }
+    }
+}
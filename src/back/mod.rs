@@ -1 +1,106 @@
-pub mod msl;
\ No newline at end of file
+pub mod glsl;
+pub mod msl;
+pub mod spv;
+pub mod wgsl;
+
+use crate::{
+    arena::{Arena, Handle},
+    proc::{ResolveError, Typifier},
+};
+
+const INDENT: &str = "    ";
+const COMPONENTS: &[char] = &['x', 'y', 'z', 'w'];
+
+fn name_or_index<T>(name: &Option<String>, prefix: &str, handle: Handle<T>) -> String {
+    match *name {
+        Some(ref name) => name.clone(),
+        None => format!("{}{}", prefix, handle.index()),
+    }
+}
+
+/// Operators are spelled the same way in GLSL and WGSL.
+fn binary_operator_string(op: crate::BinaryOperator) -> &'static str {
+    use crate::BinaryOperator as Bo;
+    match op {
+        Bo::Add => "+",
+        Bo::Subtract => "-",
+        Bo::Multiply => "*",
+        Bo::Divide => "/",
+        Bo::Modulo => "%",
+        Bo::Equal => "==",
+        Bo::NotEqual => "!=",
+        Bo::Less => "<",
+        Bo::LessEqual => "<=",
+        Bo::Greater => ">",
+        Bo::GreaterEqual => ">=",
+        Bo::And => "&",
+        Bo::ExclusiveOr => "^",
+        Bo::InclusiveOr => "|",
+        Bo::LogicalAnd => "&&",
+        Bo::LogicalOr => "||",
+        Bo::ShiftLeftLogical => "<<",
+        Bo::ShiftRightLogical |
+        Bo::ShiftRightArithmetic => ">>",
+    }
+}
+
+/// Types of the expressions of the function being written.
+struct FunctionTypes {
+    typifier: Typifier,
+    /// Scratch copy of the module types, which the typifier may extend.
+    types: Arena<crate::Type>,
+}
+
+impl FunctionTypes {
+    fn new(module: &crate::Module) -> Self {
+        FunctionTypes {
+            typifier: Typifier::new(),
+            types: module.types.clone(),
+        }
+    }
+
+    /// Forget the expression types of the previous function.
+    fn reset(&mut self) {
+        self.typifier = Typifier::new();
+    }
+
+    /// Return the accessor of `AccessIndex { base, index }`, like `.name`,
+    /// `.x` or `[2]`, or `None` if the type of `base` can't be indexed
+    /// or `index` is out of its range.
+    fn access_index_string(
+        &mut self,
+        base: Handle<crate::Expression>,
+        index: u32,
+        fun: &crate::Function,
+        module: &crate::Module,
+    ) -> Result<Option<String>, ResolveError> {
+        let base_ty = self.typifier.resolve(
+            base,
+            &fun.expressions,
+            &mut self.types,
+            &module.constants,
+            &module.global_variables,
+            &fun.local_variables,
+            &module.functions,
+            &fun.parameter_types,
+        )?;
+        let mut inner = &self.types[base_ty].inner;
+        if let crate::TypeInner::Pointer { base, .. } = *inner {
+            inner = &self.types[base].inner;
+        }
+        Ok(match *inner {
+            crate::TypeInner::Struct { ref members } => match members.get(index as usize) {
+                Some(&crate::StructMember { name: Some(ref name), .. }) => Some(format!(".{}", name)),
+                Some(_) => Some(format!(".member{}", index)),
+                None => None,
+            },
+            crate::TypeInner::Vector { size, .. } if index < size as u32 => {
+                COMPONENTS.get(index as usize).map(|component| format!(".{}", component))
+            }
+            crate::TypeInner::Vector { .. } => None,
+            crate::TypeInner::Matrix { .. } |
+            crate::TypeInner::Array { .. } => Some(format!("[{}]", index)),
+            _ => None,
+        })
+    }
+}
@@ -68,14 +68,14 @@ impl Display for Level {
 #[derive(Debug)]
 pub enum Error {
     Format(FmtError),
-    UnsupportedExecutionModel(spirv::ExecutionModel),
     UnexpectedLocation,
-    MixedExecutionModels(crate::Handle<crate::Function>),
+    MixedShaderStages(crate::Handle<crate::Function>),
     MissingBinding(crate::Handle<crate::GlobalVariable>),
     MissingBindTarget(BindSource),
     InvalidImageFlags(crate::ImageFlags),
     MutabilityViolation(crate::Handle<crate::GlobalVariable>),
     BadName(String),
+    UnexpectedVoidCall(crate::Handle<crate::Function>),
 }
 
 impl From<FmtError> for Error {
@@ -110,8 +110,8 @@ impl Options<'_> {
                 }),
                 LocationMode::Uniform => Err(Error::UnexpectedLocation),
             },
-            crate::Binding::Descriptor { set, binding } => {
-                let source = BindSource { set, binding };
+            crate::Binding::Resource { group, binding } => {
+                let source = BindSource { set: group, binding };
                 self.binding_map
                     .get(&source)
                     .cloned()
@@ -232,9 +232,10 @@ impl Display for TypedGlobalVariable<'_> {
         let var = &self.module.global_variables[self.handle];
         let name = var.name.or_index(self.handle);
         let (space_qualifier, reference) = match var.class {
-            spirv::StorageClass::Uniform |
-            spirv::StorageClass::UniformConstant |
-            spirv::StorageClass::StorageBuffer => {
+            crate::StorageClass::Uniform |
+            crate::StorageClass::PushConstant |
+            crate::StorageClass::Handle |
+            crate::StorageClass::Storage => {
                 let space = if self.usage.contains(crate::GlobalUse::STORE) {
                     "device "
                 } else {
@@ -249,10 +250,11 @@ impl Display for TypedGlobalVariable<'_> {
             match ty.inner {
                 crate::TypeInner::Pointer { base, class }  => {
                     let ty_handle = match class {
-                        spirv::StorageClass::Input |
-                        spirv::StorageClass::Output |
-                        spirv::StorageClass::Uniform |
-                        spirv::StorageClass::UniformConstant => base,
+                        crate::StorageClass::Input |
+                        crate::StorageClass::Output |
+                        crate::StorageClass::Uniform |
+                        crate::StorageClass::PushConstant |
+                        crate::StorageClass::Handle => base,
                         _ => var.ty
                     };
                     let ty_name = self.module.types[ty_handle].name.or_index(ty_handle);
@@ -426,7 +428,7 @@ impl<W: Write> Writer<W> {
                 let var = &module.global_variables[handle];
                 let inner = &module.types[var.ty].inner;
                 match var.class {
-                    spirv::StorageClass::Output => {
+                    crate::StorageClass::Output => {
                         if GLOBAL_POINTERS {
                             if let crate::TypeInner::Pointer { base, .. } = *inner {
                                 let base_inner = &module.types[base].inner;
@@ -441,7 +443,7 @@ impl<W: Write> Writer<W> {
                         }
                         write!(self.out, "{}.", OUTPUT_STRUCT_NAME)?;
                     }
-                    spirv::StorageClass::Input => {
+                    crate::StorageClass::Input => {
                         if let Some(crate::Binding::Location(_)) = var.binding {
                             write!(self.out, "{}.", LOCATION_INPUT_STRUCT_NAME)?;
                         }
@@ -523,7 +525,22 @@ impl<W: Write> Writer<W> {
                     ref other => panic!("Unexpected image type {:?}", other),
                 }
             }
-            crate::Expression::Call { ref name, ref arguments } => {
+            crate::Expression::Call { origin: crate::FunctionOrigin::Local(fun_handle), ref arguments } => {
+                let callee = &module.functions[fun_handle];
+                write!(self.out, "{}(", callee.name.or_index(fun_handle))?;
+                for (i, &argument) in arguments.iter().enumerate() {
+                    if i != 0 {
+                        write!(self.out, ", ")?;
+                    }
+                    self.put_expression(argument, function, module)?;
+                }
+                write!(self.out, ")")?;
+                match callee.return_type {
+                    Some(ty) => Ok(module.borrow_type(ty)),
+                    None => Err(Error::UnexpectedVoidCall(fun_handle)),
+                }
+            }
+            crate::Expression::Call { origin: crate::FunctionOrigin::External(ref name), ref arguments } => {
                 match name.as_str() {
                     "cos" |
                     "normalize" |
@@ -629,7 +646,8 @@ impl<W: Write> Writer<W> {
     ) -> Result<(), Error> {
         log::trace!("statement[{}] {:?}", level.0, statement);
         match *statement {
-            crate::Statement::Empty => {}
+            crate::Statement::Empty |
+            crate::Statement::Line(_) => {}
             crate::Statement::If { condition, ref accept, ref reject } => {
                 write!(self.out, "{}if (", level)?;
                 self.put_expression(condition, function, module)?;
@@ -645,6 +663,27 @@ impl<W: Write> Writer<W> {
                 }
                 writeln!(self.out, "{}}}", level)?;
             }
+            crate::Statement::Switch { selector, ref cases, ref default } => {
+                write!(self.out, "{}switch(", level)?;
+                self.put_expression(selector, function, module)?;
+                writeln!(self.out, ") {{")?;
+                let lcase = level.next();
+                let mut values = cases.keys().cloned().collect::<Vec<_>>();
+                values.sort();
+                for value in values {
+                    writeln!(self.out, "{}case {}: {{", lcase, value)?;
+                    for s in &cases[&value].0 {
+                        self.put_statement(lcase.next(), s, function, has_output, module)?;
+                    }
+                    writeln!(self.out, "{}}}", lcase)?;
+                }
+                writeln!(self.out, "{}default: {{", lcase)?;
+                for s in default {
+                    self.put_statement(lcase.next(), s, function, has_output, module)?;
+                }
+                writeln!(self.out, "{}}}", lcase)?;
+                writeln!(self.out, "{}}}", level)?;
+            }
             crate::Statement::Loop { ref body, ref continuing } => {
                 writeln!(self.out, "{}while(true) {{", level)?;
                 for s in body {
@@ -718,10 +757,12 @@ impl<W: Write> Writer<W> {
                 crate::TypeInner::Pointer { base, class } => {
                     let base_name = module.types[base].name.or_index(base);
                     let class_name = match class {
-                        spirv::StorageClass::Input |
-                        spirv::StorageClass::Output => continue,
-                        spirv::StorageClass::Uniform |
-                        spirv::StorageClass::UniformConstant => "constant",
+                        crate::StorageClass::Input |
+                        crate::StorageClass::Output => continue,
+                        crate::StorageClass::Uniform |
+                        crate::StorageClass::PushConstant |
+                        crate::StorageClass::Handle => "constant",
+                        crate::StorageClass::Storage => "device",
                         other => {
                             log::warn!("Unexpected pointer class {:?}", other);
                             ""
@@ -776,7 +817,7 @@ impl<W: Write> Writer<W> {
                     };
                     write!(self.out, "typedef texture{}<{}, access::{}> {}", dim, base_name, access, name)?;
                 }
-                crate::TypeInner::Sampler => {
+                crate::TypeInner::Sampler { .. } => {
                     write!(self.out, "typedef sampler {}", name)?;
                 }
             }
@@ -789,16 +830,16 @@ impl<W: Write> Writer<W> {
         for (fun_handle, fun) in module.functions.iter() {
             let fun_name = fun.name.or_index(fun_handle);
             // find the entry point(s) and inputs/outputs
-            let mut exec_model = None;
+            let mut stage = None;
             let mut last_used_global = None;
             for ((handle, var), &usage) in module.global_variables.iter().zip(&fun.global_usage) {
                 match var.class {
-                    spirv::StorageClass::Input => {
+                    crate::StorageClass::Input => {
                         if let Some(crate::Binding::Location(_)) = var.binding {
                             continue
                         }
                     }
-                    spirv::StorageClass::Output => continue,
+                    crate::StorageClass::Output => continue,
                     _ => {}
                 }
                 if !usage.is_empty() {
@@ -807,32 +848,31 @@ impl<W: Write> Writer<W> {
             }
             for ep in module.entry_points.iter() {
                 if ep.function == fun_handle {
-                    if exec_model.is_some() {
-                        if exec_model != Some(ep.exec_model) {
-                            return Err(Error::MixedExecutionModels(fun_handle));
+                    if stage.is_some() {
+                        if stage != Some(ep.stage) {
+                            return Err(Error::MixedShaderStages(fun_handle));
                         }
                     } else {
-                        exec_model = Some(ep.exec_model);
+                        stage = Some(ep.stage);
                     }
                 }
             }
             let output_name = fun.name.or_index(OutputStructIndex(fun_handle));
 
             // make dedicated input/output structs
-            if let Some(em) = exec_model {
+            if let Some(em) = stage {
                 assert_eq!(fun.return_type, None);
                 let (em_str, in_mode, out_mode) = match em {
-                    spirv::ExecutionModel::Vertex => ("vertex", LocationMode::VertexInput, LocationMode::Intermediate),
-                    spirv::ExecutionModel::Fragment => ("fragment", LocationMode::Intermediate, LocationMode::FragmentOutput),
-                    spirv::ExecutionModel::GLCompute => ("kernel", LocationMode::Uniform, LocationMode::Uniform),
-                    _ => return Err(Error::UnsupportedExecutionModel(em)),
+                    crate::ShaderStage::Vertex => ("vertex", LocationMode::VertexInput, LocationMode::Intermediate),
+                    crate::ShaderStage::Fragment => ("fragment", LocationMode::Intermediate, LocationMode::FragmentOutput),
+                    crate::ShaderStage::Compute => ("kernel", LocationMode::Uniform, LocationMode::Uniform),
                 };
                 let location_input_name = fun.name.or_index(InputStructIndex(fun_handle));
 
-                if em != spirv::ExecutionModel::GLCompute {
+                if em != crate::ShaderStage::Compute {
                     writeln!(self.out, "struct {} {{", location_input_name)?;
                     for ((handle, var), &usage) in module.global_variables.iter().zip(&fun.global_usage) {
-                        if var.class != spirv::StorageClass::Input || !usage.contains(crate::GlobalUse::LOAD) {
+                        if var.class != crate::StorageClass::Input || !usage.contains(crate::GlobalUse::LOAD) {
                             continue
                         }
                         // if it's a struct, lift all the built-in contents up to the root
@@ -862,7 +902,7 @@ impl<W: Write> Writer<W> {
                     writeln!(self.out, "}};")?;
                     writeln!(self.out, "struct {} {{", output_name)?;
                     for ((handle, var), &usage) in module.global_variables.iter().zip(&fun.global_usage) {
-                        if var.class != spirv::StorageClass::Output || !usage.contains(crate::GlobalUse::STORE) {
+                        if var.class != crate::StorageClass::Output || !usage.contains(crate::GlobalUse::STORE) {
                             continue
                         }
                         // if it's a struct, lift all the built-in contents up to the root
@@ -902,20 +942,20 @@ impl<W: Write> Writer<W> {
                 }
 
                 for ((handle, var), &usage) in module.global_variables.iter().zip(&fun.global_usage) {
-                    if usage.is_empty() || var.class == spirv::StorageClass::Output {
+                    if usage.is_empty() || var.class == crate::StorageClass::Output {
                         continue
                     }
-                    if var.class == spirv::StorageClass::Input {
+                    if var.class == crate::StorageClass::Input {
                         if let Some(crate::Binding::Location(_)) = var.binding {
                             // location inputs are put into a separate struct
                             continue
                         }
                     }
                     let loc_mode = match (em, var.class) {
-                        (spirv::ExecutionModel::Vertex, spirv::StorageClass::Input) => LocationMode::VertexInput,
-                        (spirv::ExecutionModel::Vertex, spirv::StorageClass::Output) |
-                        (spirv::ExecutionModel::Fragment, spirv::StorageClass::Input) => LocationMode::Intermediate,
-                        (spirv::ExecutionModel::Fragment, spirv::StorageClass::Output) => LocationMode::FragmentOutput,
+                        (crate::ShaderStage::Vertex, crate::StorageClass::Input) => LocationMode::VertexInput,
+                        (crate::ShaderStage::Vertex, crate::StorageClass::Output) |
+                        (crate::ShaderStage::Fragment, crate::StorageClass::Input) => LocationMode::Intermediate,
+                        (crate::ShaderStage::Fragment, crate::StorageClass::Output) => LocationMode::FragmentOutput,
                         _ => LocationMode::Uniform,
                     };
                     let resolved = options.resolve_binding(var.binding.as_ref().unwrap(), loc_mode)?;
@@ -942,9 +982,9 @@ impl<W: Write> Writer<W> {
             writeln!(self.out, ") {{")?;
 
             // write down function body
-            let has_output = match exec_model {
-                Some(spirv::ExecutionModel::Vertex) |
-                Some(spirv::ExecutionModel::Fragment) => {
+            let has_output = match stage {
+                Some(crate::ShaderStage::Vertex) |
+                Some(crate::ShaderStage::Fragment) => {
                     writeln!(self.out, "\t{} {};", output_name, OUTPUT_STRUCT_NAME)?;
                     true
                 }
@@ -974,3 +1014,91 @@ pub fn write_string(module: &crate::Module, options: Options) -> Result<String,
     w.write(module, options)?;
     Ok(w.out)
 }
+
+#[cfg(test)]
+mod test {
+    use crate::arena::Arena;
+
+    #[test]
+    fn compute_store() {
+        let mut types = Arena::new();
+        let uint_ty = types.append(crate::Type {
+            name: Some("uint".to_owned()),
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Uint, width: 32 },
+        });
+        let data_ty = types.append(crate::Type {
+            name: Some("Data".to_owned()),
+            inner: crate::TypeInner::Struct {
+                members: vec![crate::StructMember {
+                    name: Some("value".to_owned()),
+                    binding: None,
+                    ty: uint_ty,
+                    offset: 0,
+                }],
+            },
+        });
+        let mut constants = Arena::new();
+        let constant = constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Uint(7),
+            ty: uint_ty,
+        });
+        let mut global_variables = Arena::new();
+        let global = global_variables.append(crate::GlobalVariable {
+            name: Some("data".to_owned()),
+            class: crate::StorageClass::Storage,
+            binding: Some(crate::Binding::Resource { group: 0, binding: 0 }),
+            interpolation: None,
+            sampling: None,
+            ty: data_ty,
+            init: None,
+        });
+
+        let mut expressions = Arena::new();
+        let base = expressions.append(crate::Expression::GlobalVariable(global));
+        let pointer = expressions.append(crate::Expression::AccessIndex { base, index: 0 });
+        let value = expressions.append(crate::Expression::Constant(constant));
+        let body = vec![
+            crate::Statement::Store { pointer, value },
+            crate::Statement::Return { value: None },
+        ];
+        let mut functions = Arena::new();
+        let main = functions.append(crate::Function {
+            name: Some("fill".to_owned()),
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: None,
+            global_usage: crate::GlobalUse::scan(&expressions, &body, &global_variables),
+            local_variables: Arena::new(),
+            expressions,
+            expression_spans: crate::FastHashMap::default(),
+            body,
+        });
+        let module = crate::Module {
+            header: crate::Header::default(),
+            types,
+            constants,
+            global_variables,
+            functions,
+            entry_points: vec![crate::EntryPoint {
+                stage: crate::ShaderStage::Compute,
+                name: "fill".to_owned(),
+                function: main,
+                workgroup_size: [1; 3],
+            }],
+        };
+
+        let mut binding_map = super::BindingMap::default();
+        binding_map.insert(
+            super::BindSource { set: 0, binding: 0 },
+            super::BindTarget { buffer: Some(0), mutable: true, ..Default::default() },
+        );
+        let options = super::Options { binding_map: &binding_map };
+        let output = super::write_string(&module, options).unwrap();
+        assert!(output.starts_with("#include <metal_stdlib>"));
+        assert!(output.contains("kernel void fill("));
+        assert!(output.contains("device Data& data [[buffer(0)]]"));
+        assert!(output.contains("data.value = 7;"));
+    }
+}
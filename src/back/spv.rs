@@ -0,0 +1,1125 @@
+/*! SPIR-V backend
+
+## ID allocation
+
+Every result produced by the writer gets a fresh ID, allocated densely
+starting from 1. The writer keeps lookup tables from IR handles to these IDs,
+mirroring the lookup tables of the SPIR-V frontend.
+
+## Layout
+
+The instructions are collected into separate sections, which are
+concatenated in the order required by the SPIR-V logical layout once
+the whole module has been processed.
+!*/
+
+use crate::{
+    arena::Handle,
+    FastHashMap,
+};
+
+use spirv::Word;
+
+#[derive(Debug)]
+pub enum Error {
+    UnsupportedType(Handle<crate::Type>),
+    UnsupportedExpression(Handle<crate::Expression>),
+    UnsupportedStatement(crate::Statement),
+}
+
+/// Structural description of a type, used to emit each distinct type only once.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+enum LocalType {
+    Scalar { kind: crate::ScalarKind, width: crate::Bytes },
+    Vector { size: crate::VectorSize, kind: crate::ScalarKind, width: crate::Bytes },
+    Matrix { columns: crate::VectorSize, rows: crate::VectorSize, kind: crate::ScalarKind, width: crate::Bytes },
+    Pointer { base: Word, class: crate::StorageClass },
+}
+
+#[derive(Debug, Hash, Eq, PartialEq)]
+struct LookupFunctionType {
+    return_type_id: Word,
+    parameter_type_ids: Vec<Word>,
+}
+
+/// Instruction declaring a constant, used to emit each distinct value only once.
+#[derive(Debug, Hash, Eq, PartialEq)]
+struct LookupConstant {
+    op: spirv::Op,
+    type_id: Word,
+    value: Vec<Word>,
+}
+
+#[derive(Default)]
+struct Sections {
+    capabilities: Vec<Word>,
+    memory_model: Vec<Word>,
+    entry_points: Vec<Word>,
+    execution_modes: Vec<Word>,
+    strings: Vec<Word>,
+    debugs: Vec<Word>,
+    annotations: Vec<Word>,
+    declarations: Vec<Word>,
+    functions: Vec<Word>,
+}
+
+pub struct Writer {
+    id_count: Word,
+    sections: Sections,
+    void_type: Option<Word>,
+    lookup_type: FastHashMap<Handle<crate::Type>, Word>,
+    lookup_local_type: FastHashMap<LocalType, Word>,
+    lookup_constant: FastHashMap<Handle<crate::Constant>, Word>,
+    lookup_constant_value: FastHashMap<LookupConstant, Word>,
+    lookup_global_variable: FastHashMap<Handle<crate::GlobalVariable>, Word>,
+    lookup_function: FastHashMap<Handle<crate::Function>, Word>,
+    lookup_function_type: FastHashMap<LookupFunctionType, Word>,
+    /// Results of the expressions of the function being written.
+    lookup_expression: FastHashMap<Handle<crate::Expression>, Word>,
+    /// IDs of the `OpString` instructions naming the source files.
+    lookup_string: FastHashMap<String, Word>,
+    /// Location set by the last `OpLine` of the function being written.
+    current_span: Option<crate::Span>,
+    /// Location set by the last `Statement::Line` of the function being written.
+    statement_span: Option<crate::Span>,
+}
+
+fn map_shader_stage(stage: crate::ShaderStage) -> spirv::ExecutionModel {
+    match stage {
+        crate::ShaderStage::Vertex => spirv::ExecutionModel::Vertex,
+        crate::ShaderStage::Fragment => spirv::ExecutionModel::Fragment,
+        crate::ShaderStage::Compute => spirv::ExecutionModel::GLCompute,
+    }
+}
+
+fn map_storage_class(class: crate::StorageClass) -> spirv::StorageClass {
+    match class {
+        crate::StorageClass::Function => spirv::StorageClass::Function,
+        crate::StorageClass::Uniform => spirv::StorageClass::Uniform,
+        crate::StorageClass::Storage => spirv::StorageClass::StorageBuffer,
+        crate::StorageClass::Input => spirv::StorageClass::Input,
+        crate::StorageClass::Output => spirv::StorageClass::Output,
+        crate::StorageClass::Private => spirv::StorageClass::Private,
+        crate::StorageClass::WorkGroup => spirv::StorageClass::Workgroup,
+        crate::StorageClass::Handle => spirv::StorageClass::UniformConstant,
+        crate::StorageClass::PushConstant => spirv::StorageClass::PushConstant,
+    }
+}
+
+fn push_instruction(out: &mut Vec<Word>, op: spirv::Op, operands: &[Word]) {
+    let wc = operands.len() as Word + 1;
+    out.push((wc << 16) | op as Word);
+    out.extend_from_slice(operands);
+}
+
+/// The type of the value stored in `var`.
+///
+/// The SPIR-V front end gives global variables their pointer type,
+/// so an outer pointer is looked through.
+fn variable_value_type(var: &crate::GlobalVariable, module: &crate::Module) -> Handle<crate::Type> {
+    match module.types[var.ty].inner {
+        crate::TypeInner::Pointer { base, .. } => base,
+        _ => var.ty,
+    }
+}
+
+/// Encode a string as a nul-terminated sequence of little-endian words.
+fn string_to_words(value: &str) -> Vec<Word> {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.push(0);
+    while bytes.len() % 4 != 0 {
+        bytes.push(0);
+    }
+    bytes
+        .chunks(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Writer {
+            id_count: 0,
+            sections: Sections::default(),
+            void_type: None,
+            lookup_type: FastHashMap::default(),
+            lookup_local_type: FastHashMap::default(),
+            lookup_constant: FastHashMap::default(),
+            lookup_constant_value: FastHashMap::default(),
+            lookup_global_variable: FastHashMap::default(),
+            lookup_function: FastHashMap::default(),
+            lookup_function_type: FastHashMap::default(),
+            lookup_expression: FastHashMap::default(),
+            lookup_string: FastHashMap::default(),
+            current_span: None,
+            statement_span: None,
+        }
+    }
+
+    /// Return the ID bound of the last written module,
+    /// which is one past the largest result ID.
+    pub fn bound(&self) -> Word {
+        self.id_count + 1
+    }
+
+    fn generate_id(&mut self) -> Word {
+        self.id_count += 1;
+        self.id_count
+    }
+
+    fn get_void_type_id(&mut self) -> Word {
+        match self.void_type {
+            Some(id) => id,
+            None => {
+                let id = self.generate_id();
+                push_instruction(&mut self.sections.declarations, spirv::Op::TypeVoid, &[id]);
+                self.void_type = Some(id);
+                id
+            }
+        }
+    }
+
+    fn get_function_type_id(&mut self, lookup: LookupFunctionType) -> Word {
+        if let Some(&id) = self.lookup_function_type.get(&lookup) {
+            return id;
+        }
+        let id = self.generate_id();
+        let mut operands = vec![id, lookup.return_type_id];
+        operands.extend_from_slice(&lookup.parameter_type_ids);
+        push_instruction(&mut self.sections.declarations, spirv::Op::TypeFunction, &operands);
+        self.lookup_function_type.insert(lookup, id);
+        id
+    }
+
+    fn get_type_id(&mut self, local: LocalType) -> Word {
+        if let Some(&id) = self.lookup_local_type.get(&local) {
+            return id;
+        }
+        let id = match local {
+            LocalType::Scalar { kind, width } => {
+                let id = self.generate_id();
+                let width = Word::from(width);
+                let out = &mut self.sections.declarations;
+                match kind {
+                    crate::ScalarKind::Sint => push_instruction(out, spirv::Op::TypeInt, &[id, width, 1]),
+                    crate::ScalarKind::Uint => push_instruction(out, spirv::Op::TypeInt, &[id, width, 0]),
+                    crate::ScalarKind::Float => push_instruction(out, spirv::Op::TypeFloat, &[id, width]),
+                    crate::ScalarKind::Bool => push_instruction(out, spirv::Op::TypeBool, &[id]),
+                }
+                id
+            }
+            LocalType::Vector { size, kind, width } => {
+                let scalar_id = self.get_type_id(LocalType::Scalar { kind, width });
+                let id = self.generate_id();
+                push_instruction(
+                    &mut self.sections.declarations,
+                    spirv::Op::TypeVector,
+                    &[id, scalar_id, size as Word],
+                );
+                id
+            }
+            LocalType::Matrix { columns, rows, kind, width } => {
+                let column_id = self.get_type_id(LocalType::Vector { size: rows, kind, width });
+                let id = self.generate_id();
+                push_instruction(
+                    &mut self.sections.declarations,
+                    spirv::Op::TypeMatrix,
+                    &[id, column_id, columns as Word],
+                );
+                id
+            }
+            LocalType::Pointer { base, class } => {
+                let id = self.generate_id();
+                push_instruction(
+                    &mut self.sections.declarations,
+                    spirv::Op::TypePointer,
+                    &[id, map_storage_class(class) as Word, base],
+                );
+                id
+            }
+        };
+        self.lookup_local_type.insert(local, id);
+        id
+    }
+
+    fn write_type(&mut self, handle: Handle<crate::Type>, ty: &crate::Type) -> Result<(), Error> {
+        let local = match ty.inner {
+            crate::TypeInner::Scalar { kind, width } => LocalType::Scalar { kind, width },
+            crate::TypeInner::Vector { size, kind, width } => LocalType::Vector { size, kind, width },
+            crate::TypeInner::Matrix { columns, rows, kind, width } => LocalType::Matrix { columns, rows, kind, width },
+            crate::TypeInner::Pointer { base, class } => LocalType::Pointer {
+                base: self.lookup_type[&base],
+                class,
+            },
+            crate::TypeInner::Struct { ref members } => {
+                self.write_struct(handle, ty, members);
+                return Ok(());
+            }
+            _ => return Err(Error::UnsupportedType(handle)),
+        };
+        let id = self.get_type_id(local);
+        self.lookup_type.insert(handle, id);
+        Ok(())
+    }
+
+    fn write_struct(&mut self, handle: Handle<crate::Type>, ty: &crate::Type, members: &[crate::StructMember]) {
+        let id = self.generate_id();
+        let mut operands = vec![id];
+        operands.extend(members.iter().map(|member| self.lookup_type[&member.ty]));
+        push_instruction(&mut self.sections.declarations, spirv::Op::TypeStruct, &operands);
+        if let Some(ref name) = ty.name {
+            self.write_name(id, name);
+        }
+        for (index, member) in members.iter().enumerate() {
+            let index = index as Word;
+            if let Some(ref name) = member.name {
+                let mut operands = vec![id, index];
+                operands.extend(string_to_words(name));
+                push_instruction(&mut self.sections.debugs, spirv::Op::MemberName, &operands);
+            }
+            push_instruction(
+                &mut self.sections.annotations,
+                spirv::Op::MemberDecorate,
+                &[id, index, spirv::Decoration::Offset as Word, member.offset],
+            );
+        }
+        self.lookup_type.insert(handle, id);
+    }
+
+    fn write_name(&mut self, id: Word, name: &str) {
+        let mut operands = vec![id];
+        operands.extend(string_to_words(name));
+        push_instruction(&mut self.sections.debugs, spirv::Op::Name, &operands);
+    }
+
+    fn write_constant(&mut self, handle: Handle<crate::Constant>, module: &crate::Module) -> Word {
+        if let Some(&id) = self.lookup_constant.get(&handle) {
+            return id;
+        }
+        let constant = &module.constants[handle];
+        let type_id = self.lookup_type[&constant.ty];
+        let width = match module.types[constant.ty].inner {
+            crate::TypeInner::Scalar { width, .. } => width,
+            _ => 0,
+        };
+        let (op, value) = match constant.inner {
+            crate::ConstantInner::Sint(value) => {
+                let bits = value as u64;
+                if width == 64 {
+                    (spirv::Op::Constant, vec![bits as Word, (bits >> 32) as Word])
+                } else {
+                    (spirv::Op::Constant, vec![bits as Word])
+                }
+            }
+            crate::ConstantInner::Uint(value) => {
+                if width == 64 {
+                    (spirv::Op::Constant, vec![value as Word, (value >> 32) as Word])
+                } else {
+                    (spirv::Op::Constant, vec![value as Word])
+                }
+            }
+            crate::ConstantInner::Float(value) => {
+                if width == 64 {
+                    let bits = value.to_bits();
+                    (spirv::Op::Constant, vec![bits as Word, (bits >> 32) as Word])
+                } else {
+                    (spirv::Op::Constant, vec![(value as f32).to_bits()])
+                }
+            }
+            crate::ConstantInner::Bool(true) => (spirv::Op::ConstantTrue, Vec::new()),
+            crate::ConstantInner::Bool(false) => (spirv::Op::ConstantFalse, Vec::new()),
+            crate::ConstantInner::Composite(ref constituents) => {
+                // the constituents have to be declared first
+                let ids = constituents
+                    .iter()
+                    .map(|&constituent| self.write_constant(constituent, module))
+                    .collect();
+                (spirv::Op::ConstantComposite, ids)
+            }
+        };
+
+        let lookup = LookupConstant { op, type_id, value };
+        let id = match self.lookup_constant_value.get(&lookup) {
+            Some(&id) => id,
+            None => {
+                let id = self.generate_id();
+                let mut operands = vec![type_id, id];
+                operands.extend_from_slice(&lookup.value);
+                push_instruction(&mut self.sections.declarations, op, &operands);
+                self.lookup_constant_value.insert(lookup, id);
+                id
+            }
+        };
+        self.lookup_constant.insert(handle, id);
+        id
+    }
+
+    fn write_global_variable(
+        &mut self,
+        handle: Handle<crate::GlobalVariable>,
+        var: &crate::GlobalVariable,
+        module: &crate::Module,
+    ) {
+        let pointer_type_id = self.get_type_id(LocalType::Pointer {
+            base: self.lookup_type[&variable_value_type(var, module)],
+            class: var.class,
+        });
+        let id = self.generate_id();
+        let mut operands = vec![pointer_type_id, id, map_storage_class(var.class) as Word];
+        operands.extend(var.init.map(|init| self.lookup_constant[&init]));
+        push_instruction(&mut self.sections.declarations, spirv::Op::Variable, &operands);
+        if let Some(ref name) = var.name {
+            self.write_name(id, name);
+        }
+        let out = &mut self.sections.annotations;
+        match var.binding {
+            Some(crate::Binding::BuiltIn(built_in)) => {
+                push_instruction(out, spirv::Op::Decorate, &[id, spirv::Decoration::BuiltIn as Word, built_in as Word]);
+            }
+            Some(crate::Binding::Location(location)) => {
+                push_instruction(out, spirv::Op::Decorate, &[id, spirv::Decoration::Location as Word, location]);
+            }
+            Some(crate::Binding::Resource { group, binding }) => {
+                push_instruction(out, spirv::Op::Decorate, &[id, spirv::Decoration::DescriptorSet as Word, group]);
+                push_instruction(out, spirv::Op::Decorate, &[id, spirv::Decoration::Binding as Word, binding]);
+            }
+            None => {}
+        }
+        self.lookup_global_variable.insert(handle, id);
+    }
+
+    fn get_string_id(&mut self, value: &str) -> Word {
+        if let Some(&id) = self.lookup_string.get(value) {
+            return id;
+        }
+        let id = self.generate_id();
+        let mut operands = vec![id];
+        operands.extend(string_to_words(value));
+        push_instruction(&mut self.sections.strings, spirv::Op::String, &operands);
+        self.lookup_string.insert(value.to_owned(), id);
+        id
+    }
+
+    /// Emit `OpLine` or `OpNoLine` if the location changes.
+    ///
+    /// Spans without a file name refer to an empty `OpString`.
+    fn write_span(&mut self, out: &mut Vec<Word>, span: Option<crate::Span>) {
+        if span == self.current_span {
+            return;
+        }
+        match span {
+            Some(ref span) => {
+                let file_id = self.get_string_id(span.file.as_ref().map_or("", String::as_str));
+                push_instruction(out, spirv::Op::Line, &[file_id, span.line, span.column]);
+            }
+            None => push_instruction(out, spirv::Op::NoLine, &[]),
+        }
+        self.current_span = span;
+    }
+
+    /// Emit the location of the current statement, which may have been
+    /// changed by the spans of its expressions.
+    fn write_statement_span(&mut self, out: &mut Vec<Word>) {
+        let span = self.statement_span.clone();
+        self.write_span(out, span);
+    }
+
+    fn write_expression(
+        &mut self,
+        out: &mut Vec<Word>,
+        handle: Handle<crate::Expression>,
+        fun: &crate::Function,
+        module: &crate::Module,
+    ) -> Result<Word, Error> {
+        if let Some(&id) = self.lookup_expression.get(&handle) {
+            return Ok(id);
+        }
+        let id = match fun.expressions[handle] {
+            crate::Expression::Constant(constant) => self.lookup_constant[&constant],
+            crate::Expression::GlobalVariable(var) => self.lookup_global_variable[&var],
+            crate::Expression::Load { pointer } => {
+                let ty = match fun.expressions[pointer] {
+                    crate::Expression::GlobalVariable(var) => {
+                        variable_value_type(&module.global_variables[var], module)
+                    }
+                    _ => return Err(Error::UnsupportedExpression(pointer)),
+                };
+                let type_id = self.lookup_type[&ty];
+                let pointer_id = self.write_expression(out, pointer, fun, module)?;
+                // expressions without a span of their own belong to the statement
+                let span = fun.expression_spans
+                    .get(&handle)
+                    .cloned()
+                    .or_else(|| self.statement_span.clone());
+                self.write_span(out, span);
+                let id = self.generate_id();
+                push_instruction(out, spirv::Op::Load, &[type_id, id, pointer_id]);
+                id
+            }
+            _ => return Err(Error::UnsupportedExpression(handle)),
+        };
+        self.lookup_expression.insert(handle, id);
+        Ok(id)
+    }
+
+    fn write_function(
+        &mut self,
+        handle: Handle<crate::Function>,
+        fun: &crate::Function,
+        module: &crate::Module,
+    ) -> Result<(), Error> {
+        let return_type_id = match fun.return_type {
+            Some(ty) => self.lookup_type[&ty],
+            None => self.get_void_type_id(),
+        };
+        let parameter_type_ids = fun.parameter_types
+            .iter()
+            .map(|ty| self.lookup_type[ty])
+            .collect::<Vec<_>>();
+        let function_type_id = self.get_function_type_id(LookupFunctionType {
+            return_type_id,
+            parameter_type_ids: parameter_type_ids.clone(),
+        });
+
+        let id = self.generate_id();
+        let mut out = Vec::new();
+        push_instruction(
+            &mut out,
+            spirv::Op::Function,
+            &[return_type_id, id, fun.control.bits(), function_type_id],
+        );
+        for type_id in parameter_type_ids {
+            let param_id = self.generate_id();
+            push_instruction(&mut out, spirv::Op::FunctionParameter, &[type_id, param_id]);
+        }
+        let label_id = self.generate_id();
+        push_instruction(&mut out, spirv::Op::Label, &[label_id]);
+        self.lookup_expression.clear();
+        self.current_span = None;
+        self.statement_span = None;
+        self.write_block(&mut out, &fun.body, fun, module)?;
+        match fun.body.last() {
+            Some(&crate::Statement::Return { .. }) |
+            Some(&crate::Statement::Kill) => {}
+            _ => push_instruction(&mut out, spirv::Op::Return, &[]),
+        }
+        push_instruction(&mut out, spirv::Op::FunctionEnd, &[]);
+
+        self.sections.functions.extend(out);
+        if let Some(ref name) = fun.name {
+            self.write_name(id, name);
+        }
+        self.lookup_function.insert(handle, id);
+        Ok(())
+    }
+
+    fn write_block(
+        &mut self,
+        out: &mut Vec<Word>,
+        block: &[crate::Statement],
+        fun: &crate::Function,
+        module: &crate::Module,
+    ) -> Result<(), Error> {
+        for statement in block {
+            match *statement {
+                crate::Statement::Empty => {}
+                crate::Statement::Line(ref span) => {
+                    self.statement_span = span.clone();
+                    self.write_statement_span(out);
+                }
+                crate::Statement::Return { value: None } => {
+                    self.write_statement_span(out);
+                    push_instruction(out, spirv::Op::Return, &[]);
+                }
+                crate::Statement::Return { value: Some(expr) } => {
+                    let value_id = self.write_expression(out, expr, fun, module)?;
+                    self.write_statement_span(out);
+                    push_instruction(out, spirv::Op::ReturnValue, &[value_id]);
+                }
+                crate::Statement::Kill => {
+                    self.write_statement_span(out);
+                    push_instruction(out, spirv::Op::Kill, &[]);
+                }
+                ref other => return Err(Error::UnsupportedStatement(other.clone())),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_entry_point(&mut self, entry_point: &crate::EntryPoint, module: &crate::Module) {
+        let function_id = self.lookup_function[&entry_point.function];
+        let mut operands = vec![map_shader_stage(entry_point.stage) as Word, function_id];
+        operands.extend(string_to_words(&entry_point.name));
+        // The interface is made of all the input/output globals used by the entry point.
+        let fun = &module.functions[entry_point.function];
+        for ((handle, var), usage) in module.global_variables.iter().zip(&fun.global_usage) {
+            match var.class {
+                crate::StorageClass::Input |
+                crate::StorageClass::Output if !usage.is_empty() => {
+                    operands.push(self.lookup_global_variable[&handle]);
+                }
+                _ => {}
+            }
+        }
+        push_instruction(&mut self.sections.entry_points, spirv::Op::EntryPoint, &operands);
+        if entry_point.stage == crate::ShaderStage::Fragment {
+            push_instruction(
+                &mut self.sections.execution_modes,
+                spirv::Op::ExecutionMode,
+                &[function_id, spirv::ExecutionMode::OriginUpperLeft as Word],
+            );
+        }
+        if entry_point.stage == crate::ShaderStage::Compute {
+            let [x, y, z] = entry_point.workgroup_size;
+            push_instruction(
+                &mut self.sections.execution_modes,
+                spirv::Op::ExecutionMode,
+                &[function_id, spirv::ExecutionMode::LocalSize as Word, x, y, z],
+            );
+        }
+    }
+
+    pub fn write(&mut self, module: &crate::Module) -> Result<Vec<Word>, Error> {
+        *self = Writer::new();
+        push_instruction(
+            &mut self.sections.capabilities,
+            spirv::Op::Capability,
+            &[spirv::Capability::Shader as Word],
+        );
+        push_instruction(
+            &mut self.sections.memory_model,
+            spirv::Op::MemoryModel,
+            &[spirv::AddressingModel::Logical as Word, spirv::MemoryModel::GLSL450 as Word],
+        );
+
+        for (handle, ty) in module.types.iter() {
+            self.write_type(handle, ty)?;
+        }
+        for handle in module.constants.handles() {
+            self.write_constant(handle, module);
+        }
+        for (handle, var) in module.global_variables.iter() {
+            self.write_global_variable(handle, var, module);
+        }
+        for (handle, fun) in module.functions.iter() {
+            self.write_function(handle, fun, module)?;
+        }
+        for entry_point in module.entry_points.iter() {
+            self.write_entry_point(entry_point, module);
+        }
+
+        let (major, minor, patch) = module.header.version;
+        let mut words = vec![
+            spirv::MAGIC_NUMBER,
+            (Word::from(major) << 16) | (Word::from(minor) << 8) | Word::from(patch),
+            module.header.generator,
+            self.bound(),
+            0,
+        ];
+        let sections = std::mem::take(&mut self.sections);
+        words.extend(sections.capabilities);
+        words.extend(sections.memory_model);
+        words.extend(sections.entry_points);
+        words.extend(sections.execution_modes);
+        words.extend(sections.strings);
+        words.extend(sections.debugs);
+        words.extend(sections.annotations);
+        words.extend(sections.declarations);
+        words.extend(sections.functions);
+        Ok(words)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arena::Arena;
+
+    fn simple_module() -> crate::Module {
+        let mut types = Arena::new();
+        let float_ty = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Quad,
+                kind: crate::ScalarKind::Uint,
+                width: 32,
+            },
+        });
+        let mut constants = Arena::new();
+        let constant = constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Float(1.5),
+            ty: float_ty,
+        });
+
+        let mut functions = Arena::new();
+        let main = functions.append(crate::Function {
+            name: None,
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: Arena::new(),
+            expressions: Arena::new(),
+            expression_spans: crate::FastHashMap::default(),
+            body: vec![crate::Statement::Return { value: None }],
+        });
+        let mut expressions = Arena::new();
+        let value = expressions.append(crate::Expression::Constant(constant));
+        functions.append(crate::Function {
+            name: None,
+            control: spirv::FunctionControl::empty(),
+            parameter_types: vec![float_ty],
+            return_type: Some(float_ty),
+            global_usage: Vec::new(),
+            local_variables: Arena::new(),
+            expressions,
+            expression_spans: crate::FastHashMap::default(),
+            body: vec![crate::Statement::Return { value: Some(value) }],
+        });
+
+        crate::Module {
+            header: crate::Header {
+                version: (1, 0, 0),
+                generator: 0,
+            },
+            types,
+            constants,
+            global_variables: Arena::new(),
+            functions,
+            entry_points: vec![crate::EntryPoint {
+                stage: crate::ShaderStage::Fragment,
+                name: "main".to_owned(),
+                function: main,
+                workgroup_size: [0; 3],
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let module = simple_module();
+        let words = super::Writer::new().write(&module).unwrap();
+        let parsed = crate::front::spirv::Parser::new(words.into_iter()).parse().unwrap();
+
+        assert_eq!(parsed.header.version, module.header.version);
+        for (_, ty) in module.types.iter() {
+            assert!(parsed.types.iter().any(|(_, other)| other.inner == ty.inner));
+        }
+        assert_eq!(parsed.constants.len(), 1);
+        let (_, constant) = parsed.constants.iter().next().unwrap();
+        assert_eq!(constant.inner, crate::ConstantInner::Float(1.5));
+
+        assert_eq!(parsed.functions.len(), 2);
+        let (_, fun) = parsed.functions.iter().nth(1).unwrap();
+        assert_eq!(fun.parameter_types.len(), 1);
+        assert!(fun.return_type.is_some());
+        match fun.body[..] {
+            [crate::Statement::Return { value: Some(value) }] => match fun.expressions[value] {
+                crate::Expression::Constant(_) => (),
+                ref other => panic!("Unexpected return value {:?}", other),
+            },
+            ref other => panic!("Unexpected body {:?}", other),
+        }
+
+        assert_eq!(parsed.entry_points.len(), 1);
+        assert_eq!(parsed.entry_points[0].name, "main");
+        assert_eq!(parsed.entry_points[0].stage, crate::ShaderStage::Fragment);
+    }
+
+    #[test]
+    fn round_trip_shader_stages() {
+        for &stage in &[
+            crate::ShaderStage::Vertex,
+            crate::ShaderStage::Fragment,
+            crate::ShaderStage::Compute,
+        ] {
+            let mut module = simple_module();
+            module.entry_points[0].stage = stage;
+            if stage == crate::ShaderStage::Compute {
+                module.entry_points[0].workgroup_size = [4, 2, 1];
+            }
+            let words = super::Writer::new().write(&module).unwrap();
+            let parsed = crate::front::spirv::Parser::new(words.into_iter()).parse().unwrap();
+            assert_eq!(parsed.entry_points[0].stage, stage);
+            assert_eq!(parsed.entry_points[0].workgroup_size, module.entry_points[0].workgroup_size);
+        }
+    }
+
+    #[test]
+    fn bound() {
+        use num_traits::cast::FromPrimitive;
+        let module = simple_module();
+        let mut writer = super::Writer::new();
+        let words = writer.write(&module).unwrap();
+        assert_eq!(words[3], writer.bound());
+
+        let mut result_ids = Vec::new();
+        let mut offset = 5;
+        while offset < words.len() {
+            let wc = (words[offset] >> 16) as usize;
+            let op = spirv::Op::from_u32(words[offset] & 0xFFFF).unwrap();
+            let result_index = match op {
+                spirv::Op::TypeVoid |
+                spirv::Op::TypeBool |
+                spirv::Op::TypeInt |
+                spirv::Op::TypeFloat |
+                spirv::Op::TypeVector |
+                spirv::Op::TypeFunction |
+                spirv::Op::Label => Some(1),
+                spirv::Op::Constant |
+                spirv::Op::Function |
+                spirv::Op::FunctionParameter => Some(2),
+                _ => None,
+            };
+            if let Some(index) = result_index {
+                result_ids.push(words[offset + index]);
+            }
+            offset += wc;
+        }
+        result_ids.sort();
+        result_ids.dedup();
+        assert_eq!(result_ids.last(), Some(&(result_ids.len() as spirv::Word)));
+        assert_eq!(writer.bound(), result_ids.len() as spirv::Word + 1);
+    }
+
+    #[test]
+    fn write_names() {
+        let mut module = simple_module();
+        let (main, _) = module.functions.iter().next().unwrap();
+        module.functions[main].name = Some("shade_fragment".to_owned());
+        let float_ty = module.types.iter().next().unwrap().0;
+        module.types.append(crate::Type {
+            name: Some("Light".to_owned()),
+            inner: crate::TypeInner::Struct {
+                members: vec![crate::StructMember {
+                    name: Some("intensity".to_owned()),
+                    binding: None,
+                    ty: float_ty,
+                    offset: 0,
+                }],
+            },
+        });
+        let words = super::Writer::new().write(&module).unwrap();
+
+        let mut names = Vec::new();
+        let mut offset = 5;
+        while offset < words.len() {
+            let wc = (words[offset] >> 16) as usize;
+            let op = words[offset] & 0xFFFF;
+            if op == spirv::Op::Name as u32 {
+                names.push(words[offset + 2 .. offset + wc].to_vec());
+            }
+            offset += wc;
+        }
+        assert!(names.contains(&super::string_to_words("shade_fragment")));
+
+        let parsed = crate::front::spirv::Parser::new(words.into_iter()).parse().unwrap();
+        let (_, fun) = parsed.functions.iter().next().unwrap();
+        assert_eq!(fun.name.as_deref(), Some("shade_fragment"));
+        let (_, ty) = parsed.types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some("Light"))
+            .unwrap();
+        match ty.inner {
+            crate::TypeInner::Struct { ref members } => {
+                assert_eq!(members[0].name.as_deref(), Some("intensity"));
+            }
+            ref other => panic!("Unexpected type {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deduplicate_types() {
+        let mut module = simple_module();
+        let float = crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 };
+        let float_ty = module.types.append(crate::Type {
+            name: Some("duplicate".to_owned()),
+            inner: float.clone(),
+        });
+        module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Tri,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        });
+        module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Pointer { base: float_ty, class: crate::StorageClass::Function },
+        });
+        let words = super::Writer::new().write(&module).unwrap();
+
+        let mut float_ids = Vec::new();
+        let mut vector_component_ids = Vec::new();
+        let mut offset = 5;
+        while offset < words.len() {
+            let wc = (words[offset] >> 16) as usize;
+            let op = words[offset] & 0xFFFF;
+            if op == spirv::Op::TypeFloat as u32 {
+                float_ids.push(words[offset + 1]);
+            }
+            if op == spirv::Op::TypeVector as u32 {
+                vector_component_ids.push(words[offset + 2]);
+            }
+            offset += wc;
+        }
+        assert_eq!(float_ids.len(), 1);
+        assert!(vector_component_ids.contains(&float_ids[0]));
+
+        let parsed = crate::front::spirv::Parser::new(words.into_iter()).parse().unwrap();
+        let float_count = parsed.types.iter().filter(|&(_, ty)| ty.inner == float).count();
+        assert_eq!(float_count, 1);
+    }
+
+    #[test]
+    fn write_entry_point_interface() {
+        let mut module = simple_module();
+        let float_ty = module.types.iter().next().unwrap().0;
+        module.global_variables.append(crate::GlobalVariable {
+            name: Some("color".to_owned()),
+            class: crate::StorageClass::Output,
+            binding: Some(crate::Binding::Location(0)),
+            interpolation: None,
+            sampling: None,
+            ty: float_ty,
+            init: None,
+        });
+        let main = module.entry_points[0].function;
+        module.functions[main].global_usage = vec![crate::GlobalUse::STORE];
+        let words = super::Writer::new().write(&module).unwrap();
+
+        let mut entry_point = None;
+        let mut execution_modes = Vec::new();
+        let mut variable_ids = Vec::new();
+        let mut offset = 5;
+        while offset < words.len() {
+            let wc = (words[offset] >> 16) as usize;
+            let op = words[offset] & 0xFFFF;
+            if op == spirv::Op::EntryPoint as u32 {
+                entry_point = Some(words[offset + 1 .. offset + wc].to_vec());
+            }
+            if op == spirv::Op::ExecutionMode as u32 {
+                execution_modes.push(words[offset + 2]);
+            }
+            if op == spirv::Op::Variable as u32 {
+                variable_ids.push(words[offset + 2]);
+            }
+            offset += wc;
+        }
+        let entry_point = entry_point.unwrap();
+        assert_eq!(entry_point[0], spirv::ExecutionModel::Fragment as u32);
+        let name = super::string_to_words("main");
+        assert_eq!(entry_point[2 .. 2 + name.len()], name[..]);
+        assert_eq!(entry_point[2 + name.len() ..], variable_ids[..]);
+        assert_eq!(execution_modes, vec![spirv::ExecutionMode::OriginUpperLeft as u32]);
+
+        let parsed = crate::front::spirv::Parser::new(words.clone().into_iter()).parse().unwrap();
+        assert_eq!(parsed.entry_points[0].stage, crate::ShaderStage::Fragment);
+        let (_, var) = parsed.global_variables.iter().next().unwrap();
+        assert_eq!(var.class, crate::StorageClass::Output);
+        assert_eq!(var.binding, Some(crate::Binding::Location(0)));
+
+        // the parsed variable has a pointer type, which isn't wrapped again
+        let rewritten = super::Writer::new().write(&parsed).unwrap();
+        let reparsed = crate::front::spirv::Parser::new(rewritten.into_iter()).parse().unwrap();
+        let pointee = |module: &crate::Module| {
+            let (_, var) = module.global_variables.iter().next().unwrap();
+            match module.types[var.ty].inner {
+                crate::TypeInner::Pointer { base, class } => (module.types[base].inner.clone(), class),
+                ref other => panic!("Unexpected type {:?}", other),
+            }
+        };
+        assert_eq!(pointee(&reparsed), pointee(&parsed));
+        assert_eq!(pointee(&parsed).0, module.types[float_ty].inner);
+    }
+
+    #[test]
+    fn write_lines() {
+        let mut module = simple_module();
+        let float_ty = module.types.iter().next().unwrap().0;
+        let var = module.global_variables.append(crate::GlobalVariable {
+            name: None,
+            class: crate::StorageClass::Private,
+            binding: None,
+            interpolation: None,
+            sampling: None,
+            ty: float_ty,
+            init: None,
+        });
+        let mut expressions = Arena::new();
+        let pointer = expressions.append(crate::Expression::GlobalVariable(var));
+        let value = expressions.append(crate::Expression::Load { pointer });
+        let span = |line, column| crate::Span {
+            file: Some("light.wgsl".to_owned()),
+            line,
+            column,
+        };
+        let mut expression_spans = crate::FastHashMap::default();
+        expression_spans.insert(value, span(3, 5));
+        module.functions.append(crate::Function {
+            name: None,
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: Some(float_ty),
+            global_usage: Vec::new(),
+            local_variables: Arena::new(),
+            expressions,
+            expression_spans,
+            body: vec![
+                crate::Statement::Line(Some(span(4, 1))),
+                crate::Statement::Return { value: Some(value) },
+            ],
+        });
+        let words = super::Writer::new().write(&module).unwrap();
+
+        let mut strings = Vec::new();
+        let mut lines = Vec::new();
+        let mut offset = 5;
+        while offset < words.len() {
+            let wc = (words[offset] >> 16) as usize;
+            let op = words[offset] & 0xFFFF;
+            if op == spirv::Op::String as u32 {
+                strings.push(words[offset + 2 .. offset + wc].to_vec());
+            }
+            if op == spirv::Op::Line as u32 {
+                lines.push((words[offset + 2], words[offset + 3]));
+            }
+            offset += wc;
+        }
+        assert_eq!(strings, vec![super::string_to_words("light.wgsl")]);
+        // the return goes back to the line of the statement
+        assert_eq!(lines, vec![(4, 1), (3, 5), (4, 1)]);
+
+        let parsed = crate::front::spirv::Parser::new(words.into_iter()).parse().unwrap();
+        let (_, fun) = parsed.functions.iter().last().unwrap();
+        let (load, _) = fun.expressions
+            .iter()
+            .find(|&(_, expr)| matches!(*expr, crate::Expression::Load { .. }))
+            .unwrap();
+        assert_eq!(fun.expression_spans.get(&load), Some(&span(3, 5)));
+        match fun.body[..] {
+            [crate::Statement::Line(Some(ref line)), crate::Statement::Return { .. }] => {
+                assert_eq!(*line, span(4, 1));
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        }
+    }
+
+    #[test]
+    fn write_constants() {
+        let mut module = simple_module();
+        let float_ty = module.types.iter().next().unwrap().0;
+        let vec2_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Bi,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        });
+        // the composite refers to constants that come later in the arena
+        let first = crate::Handle::from_usize(module.constants.len() + 1).unwrap();
+        let second = crate::Handle::from_usize(module.constants.len() + 2).unwrap();
+        module.constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Composite(vec![first, second]),
+            ty: vec2_ty,
+        });
+        for _ in 0 .. 2 {
+            module.constants.append(crate::Constant {
+                name: None,
+                specialization: None,
+                inner: crate::ConstantInner::Float(2.0),
+                ty: float_ty,
+            });
+        }
+        let words = super::Writer::new().write(&module).unwrap();
+
+        let mut constant_ids = Vec::new();
+        let mut composites = Vec::new();
+        let mut offset = 5;
+        while offset < words.len() {
+            let wc = (words[offset] >> 16) as usize;
+            let op = words[offset] & 0xFFFF;
+            if op == spirv::Op::Constant as u32 {
+                assert!(composites.is_empty());
+                constant_ids.push(words[offset + 2]);
+            }
+            if op == spirv::Op::ConstantComposite as u32 {
+                composites.push(words[offset + 3 .. offset + wc].to_vec());
+            }
+            offset += wc;
+        }
+        // 1.5 and a single 2.0
+        assert_eq!(constant_ids.len(), 2);
+        assert_eq!(composites, vec![vec![constant_ids[1], constant_ids[1]]]);
+
+        let parsed = crate::front::spirv::Parser::new(words.into_iter()).parse().unwrap();
+        assert!(parsed.constants
+            .iter()
+            .any(|(_, constant)| matches!(constant.inner, crate::ConstantInner::Composite(_))));
+    }
+
+    #[test]
+    fn unsupported() {
+        let mut module = simple_module();
+        let float_ty = module.types.iter().next().unwrap().0;
+        let array_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Array {
+                base: float_ty,
+                size: crate::ArraySize::Dynamic,
+            },
+        });
+        match super::Writer::new().write(&module) {
+            Err(super::Error::UnsupportedType(handle)) => assert_eq!(handle, array_ty),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let mut module = simple_module();
+        let (main, _) = module.functions.iter().next().unwrap();
+        module.functions[main].body = vec![crate::Statement::Break];
+        match super::Writer::new().write(&module) {
+            Err(super::Error::UnsupportedStatement(crate::Statement::Break)) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn write_matrix() {
+        let mut module = crate::Module::new(crate::Header::default());
+        let matrix = crate::TypeInner::Matrix {
+            columns: crate::VectorSize::Tri,
+            rows: crate::VectorSize::Tri,
+            kind: crate::ScalarKind::Float,
+            width: 32,
+        };
+        module.types.append(crate::Type { name: None, inner: matrix.clone() });
+        let words = super::Writer::new().write(&module).unwrap();
+
+        let mut ops = Vec::new();
+        let mut offset = 5;
+        while offset < words.len() {
+            let wc = (words[offset] >> 16) as usize;
+            let op = words[offset] & 0xFFFF;
+            if op == spirv::Op::TypeVector as u32 || op == spirv::Op::TypeMatrix as u32 {
+                assert_eq!(words[offset + 3], 3);
+            }
+            ops.push(op);
+            offset += wc;
+        }
+        for &op in &[spirv::Op::TypeFloat, spirv::Op::TypeVector, spirv::Op::TypeMatrix] {
+            assert_eq!(ops.iter().filter(|&&other| other == op as u32).count(), 1);
+        }
+
+        let parsed = crate::front::spirv::Parser::new(words.into_iter()).parse().unwrap();
+        assert!(parsed.types.iter().any(|(_, ty)| ty.inner == matrix));
+    }
+}
@@ -0,0 +1,773 @@
+/*! WebGPU Shading Language (WGSL) backend
+
+## Dialect
+
+The output follows the same WGSL dialect that `front::wgsl` accepts,
+so that a written module can be parsed back. Global variables get
+`@group(n) @binding(m)`, `@location(n)`, `@builtin(name)` and
+`@interpolate(kind)` attributes, struct members keep `[[offset n]]`,
+and entry points are written as `entry_point` declarations.
+
+## Names
+
+The IR doesn't require names, so anything unnamed gets a generated one
+based on its handle, like `global1` or `Struct2`. Function parameters
+are not named in the IR and are always written as `paramN`.
+
+## Coverage
+
+Only a subset of the IR is supported so far. Anything else produces an
+`Error` instead of malformed output.
+!*/
+
+use std::fmt::{Error as FmtError, Write};
+
+use super::{binary_operator_string, name_or_index, FunctionTypes, COMPONENTS, INDENT};
+use crate::{arena::Handle, proc::ResolveError};
+
+#[derive(Debug)]
+pub enum Error {
+    Format(FmtError),
+    Resolve(ResolveError),
+    UnsupportedType(Handle<crate::Type>),
+    UnsupportedStorageClass(crate::StorageClass),
+    UnsupportedBuiltIn(spirv::BuiltIn),
+    UnsupportedExpression(Handle<crate::Expression>),
+    /// A statement of the given function can't be written.
+    UnsupportedStatement(Handle<crate::Function>, crate::Statement),
+}
+
+impl From<FmtError> for Error {
+    fn from(e: FmtError) -> Self {
+        Error::Format(e)
+    }
+}
+
+fn scalar_string(kind: crate::ScalarKind, width: crate::Bytes) -> Option<&'static str> {
+    match (kind, width) {
+        (crate::ScalarKind::Float, 32) => Some("f32"),
+        (crate::ScalarKind::Sint, 32) => Some("i32"),
+        (crate::ScalarKind::Uint, 32) => Some("u32"),
+        (crate::ScalarKind::Bool, 1) => Some("bool"),
+        _ => None,
+    }
+}
+
+/// Return the storage class qualifier, or `None` for plain `var`.
+fn storage_class_string(class: crate::StorageClass) -> Result<Option<&'static str>, Error> {
+    match class {
+        crate::StorageClass::Input => Ok(Some("in")),
+        crate::StorageClass::Output => Ok(Some("out")),
+        crate::StorageClass::Uniform => Ok(Some("uniform")),
+        crate::StorageClass::Storage => Ok(Some("storage_buffer")),
+        crate::StorageClass::Private => Ok(None),
+        other => Err(Error::UnsupportedStorageClass(other)),
+    }
+}
+
+fn built_in_string(built_in: spirv::BuiltIn) -> Result<&'static str, Error> {
+    match built_in {
+        spirv::BuiltIn::Position => Ok("position"),
+        spirv::BuiltIn::VertexId |
+        spirv::BuiltIn::VertexIndex => Ok("vertex_idx"),
+        spirv::BuiltIn::InstanceId |
+        spirv::BuiltIn::InstanceIndex => Ok("instance_idx"),
+        spirv::BuiltIn::FrontFacing => Ok("front_facing"),
+        spirv::BuiltIn::FragCoord => Ok("frag_coord"),
+        spirv::BuiltIn::FragDepth => Ok("frag_depth"),
+        spirv::BuiltIn::LocalInvocationId => Ok("local_invocation_id"),
+        spirv::BuiltIn::LocalInvocationIndex => Ok("local_invocation_idx"),
+        spirv::BuiltIn::GlobalInvocationId => Ok("global_invocation_id"),
+        spirv::BuiltIn::WorkgroupId => Ok("workgroup_id"),
+        other => Err(Error::UnsupportedBuiltIn(other)),
+    }
+}
+
+fn interpolation_string(interpolation: crate::Interpolation) -> &'static str {
+    match interpolation {
+        crate::Interpolation::Flat => "flat",
+        crate::Interpolation::Linear => "linear",
+    }
+}
+
+fn sampling_string(sampling: crate::Sampling) -> &'static str {
+    match sampling {
+        crate::Sampling::Centroid => "centroid",
+        crate::Sampling::Sample => "sample",
+    }
+}
+
+struct Writer<'a> {
+    module: &'a crate::Module,
+    out: String,
+    types: FunctionTypes,
+}
+
+impl Writer<'_> {
+    fn type_string(&self, handle: Handle<crate::Type>) -> Result<String, Error> {
+        let ty = &self.module.types[handle];
+        let scalar = |kind, width| scalar_string(kind, width).ok_or(Error::UnsupportedType(handle));
+        Ok(match ty.inner {
+            crate::TypeInner::Scalar { kind, width } => scalar(kind, width)?.to_owned(),
+            crate::TypeInner::Vector { size, kind, width } => {
+                format!("vec{}<{}>", size as u8, scalar(kind, width)?)
+            }
+            crate::TypeInner::Matrix { columns, rows, kind, width } => {
+                format!("mat{}x{}<{}>", columns as u8, rows as u8, scalar(kind, width)?)
+            }
+            crate::TypeInner::Pointer { base, class } => {
+                let class = storage_class_string(class)?.ok_or(Error::UnsupportedType(handle))?;
+                format!("ptr<{}, {}>", class, self.type_string(base)?)
+            }
+            crate::TypeInner::Array { base, size: crate::ArraySize::Static(length) } => {
+                format!("array<{}, {}>", self.type_string(base)?, length)
+            }
+            crate::TypeInner::Array { base, size: crate::ArraySize::Dynamic } => {
+                format!("array<{}>", self.type_string(base)?)
+            }
+            crate::TypeInner::Struct { .. } => name_or_index(&ty.name, "Struct", handle),
+            crate::TypeInner::Image { .. } |
+            crate::TypeInner::Sampler { .. } => return Err(Error::UnsupportedType(handle)),
+        })
+    }
+
+    fn write_constant(&mut self, handle: Handle<crate::Constant>) -> Result<(), Error> {
+        let constant = &self.module.constants[handle];
+        match constant.inner {
+            crate::ConstantInner::Sint(value) => write!(self.out, "{}", value)?,
+            crate::ConstantInner::Uint(value) => write!(self.out, "{}", value)?,
+            crate::ConstantInner::Float(value) => {
+                write!(self.out, "{}", value)?;
+                if value.fract() == 0.0 {
+                    self.out.write_str(".0")?;
+                }
+            }
+            crate::ConstantInner::Bool(value) => write!(self.out, "{}", value)?,
+            crate::ConstantInner::Composite(ref constituents) => {
+                let ty_name = self.type_string(constant.ty)?;
+                write!(self.out, "{}(", ty_name)?;
+                for (i, &constituent) in constituents.iter().enumerate() {
+                    if i != 0 {
+                        self.out.write_str(", ")?;
+                    }
+                    self.write_constant(constituent)?;
+                }
+                self.out.write_str(")")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_expression(
+        &mut self,
+        expr_handle: Handle<crate::Expression>,
+        fun: &crate::Function,
+    ) -> Result<(), Error> {
+        let module = self.module;
+        match fun.expressions[expr_handle] {
+            crate::Expression::Access { base, index } => {
+                self.write_expression(base, fun)?;
+                self.out.write_str("[")?;
+                self.write_expression(index, fun)?;
+                self.out.write_str("]")?;
+            }
+            crate::Expression::AccessIndex { base, index } => {
+                let accessor = self.types
+                    .access_index_string(base, index, fun, module)
+                    .map_err(Error::Resolve)?
+                    .ok_or(Error::UnsupportedExpression(expr_handle))?;
+                self.write_expression(base, fun)?;
+                self.out.write_str(&accessor)?;
+            }
+            crate::Expression::Swizzle { size, vector, ref pattern } => {
+                self.write_expression(vector, fun)?;
+                self.out.write_str(".")?;
+                for &component in &pattern[.. size as usize] {
+                    self.out.write_char(COMPONENTS[component as usize])?;
+                }
+            }
+            crate::Expression::Constant(handle) => self.write_constant(handle)?,
+            crate::Expression::Compose { ty, ref components } => {
+                let ty_name = self.type_string(ty)?;
+                write!(self.out, "{}(", ty_name)?;
+                for (i, &component) in components.iter().enumerate() {
+                    if i != 0 {
+                        self.out.write_str(", ")?;
+                    }
+                    self.write_expression(component, fun)?;
+                }
+                self.out.write_str(")")?;
+            }
+            crate::Expression::FunctionParameter(index) => write!(self.out, "param{}", index)?,
+            crate::Expression::GlobalVariable(handle) => {
+                let var = &module.global_variables[handle];
+                self.out.write_str(&name_or_index(&var.name, "global", handle))?;
+            }
+            crate::Expression::LocalVariable(handle) => {
+                let var = &fun.local_variables[handle];
+                self.out.write_str(&name_or_index(&var.name, "local", handle))?;
+            }
+            crate::Expression::Load { pointer } => self.write_expression(pointer, fun)?,
+            crate::Expression::Unary { op, expr } => {
+                self.out.write_str(match op {
+                    crate::UnaryOperator::Negate => "-",
+                    crate::UnaryOperator::Not => "!",
+                })?;
+                self.write_expression(expr, fun)?;
+            }
+            crate::Expression::Binary { op, left, right } => {
+                self.out.write_str("(")?;
+                self.write_expression(left, fun)?;
+                write!(self.out, " {} ", binary_operator_string(op))?;
+                self.write_expression(right, fun)?;
+                self.out.write_str(")")?;
+            }
+            _ => return Err(Error::UnsupportedExpression(expr_handle)),
+        }
+        Ok(())
+    }
+
+    fn write_block(
+        &mut self,
+        level: usize,
+        block: &[crate::Statement],
+        fun_handle: Handle<crate::Function>,
+        fun: &crate::Function,
+    ) -> Result<(), Error> {
+        for statement in block {
+            self.write_statement(level, statement, fun_handle, fun)?;
+        }
+        Ok(())
+    }
+
+    fn write_statement(
+        &mut self,
+        level: usize,
+        statement: &crate::Statement,
+        fun_handle: Handle<crate::Function>,
+        fun: &crate::Function,
+    ) -> Result<(), Error> {
+        let indent = INDENT.repeat(level);
+        match *statement {
+            crate::Statement::Empty |
+            crate::Statement::Line(None) => {}
+            crate::Statement::Line(Some(ref span)) => match span.file {
+                Some(ref file) => writeln!(self.out, "{}# {}:{}:{}", indent, file, span.line, span.column)?,
+                None => writeln!(self.out, "{}# {}:{}", indent, span.line, span.column)?,
+            },
+            crate::Statement::Block(ref block) => {
+                writeln!(self.out, "{}{{", indent)?;
+                self.write_block(level + 1, block, fun_handle, fun)?;
+                writeln!(self.out, "{}}}", indent)?;
+            }
+            crate::Statement::If { condition, ref accept, ref reject } => {
+                // an empty `accept` is written by negating the condition instead
+                let (prefix, accept, reject) = if accept.is_empty() && !reject.is_empty() {
+                    ("!", reject, accept)
+                } else {
+                    ("", accept, reject)
+                };
+                write!(self.out, "{}if ({}", indent, prefix)?;
+                self.write_expression(condition, fun)?;
+                writeln!(self.out, ") {{")?;
+                self.write_block(level + 1, accept, fun_handle, fun)?;
+                if !reject.is_empty() {
+                    writeln!(self.out, "{}}} else {{", indent)?;
+                    self.write_block(level + 1, reject, fun_handle, fun)?;
+                }
+                writeln!(self.out, "{}}}", indent)?;
+            }
+            crate::Statement::Loop { ref body, ref continuing } => {
+                writeln!(self.out, "{}loop {{", indent)?;
+                self.write_block(level + 1, body, fun_handle, fun)?;
+                if !continuing.is_empty() {
+                    writeln!(self.out, "{}{}continuing {{", indent, INDENT)?;
+                    self.write_block(level + 2, continuing, fun_handle, fun)?;
+                    writeln!(self.out, "{}{}}}", indent, INDENT)?;
+                }
+                writeln!(self.out, "{}}}", indent)?;
+            }
+            crate::Statement::Break => writeln!(self.out, "{}break;", indent)?,
+            crate::Statement::Continue => writeln!(self.out, "{}continue;", indent)?,
+            crate::Statement::Return { value: None } => writeln!(self.out, "{}return;", indent)?,
+            crate::Statement::Return { value: Some(value) } => {
+                write!(self.out, "{}return ", indent)?;
+                self.write_expression(value, fun)?;
+                writeln!(self.out, ";")?;
+            }
+            crate::Statement::Kill => writeln!(self.out, "{}discard;", indent)?,
+            crate::Statement::Store { pointer, value } => {
+                self.out.write_str(&indent)?;
+                self.write_expression(pointer, fun)?;
+                self.out.write_str(" = ")?;
+                self.write_expression(value, fun)?;
+                writeln!(self.out, ";")?;
+            }
+            crate::Statement::Call { function, ref arguments } => {
+                let callee = &self.module.functions[function];
+                write!(self.out, "{}{}(", indent, name_or_index(&callee.name, "function", function))?;
+                for (index, &argument) in arguments.iter().enumerate() {
+                    if index != 0 {
+                        self.out.write_str(", ")?;
+                    }
+                    self.write_expression(argument, fun)?;
+                }
+                writeln!(self.out, ");")?;
+            }
+            crate::Statement::Switch { .. } => {
+                return Err(Error::UnsupportedStatement(fun_handle, statement.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_struct(&mut self, handle: Handle<crate::Type>, members: &[crate::StructMember]) -> Result<(), Error> {
+        writeln!(self.out, "type {} = struct {{", self.type_string(handle)?)?;
+        for (index, member) in members.iter().enumerate() {
+            let name = match member.name {
+                Some(ref name) => name.clone(),
+                None => format!("member{}", index),
+            };
+            let ty_name = self.type_string(member.ty)?;
+            writeln!(self.out, "{}[[offset {}]] {} : {};", INDENT, member.offset, name, ty_name)?;
+        }
+        writeln!(self.out, "}};")?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    fn write_global_variable(&mut self, handle: Handle<crate::GlobalVariable>) -> Result<(), Error> {
+        let var = &self.module.global_variables[handle];
+        match var.binding {
+            Some(crate::Binding::BuiltIn(built_in)) => {
+                write!(self.out, "@builtin({}) ", built_in_string(built_in)?)?;
+            }
+            Some(crate::Binding::Location(location)) => {
+                write!(self.out, "@location({}) ", location)?;
+            }
+            Some(crate::Binding::Resource { group, binding }) => {
+                write!(self.out, "@group({}) @binding({}) ", group, binding)?;
+            }
+            None => {}
+        }
+        match (var.interpolation, var.sampling) {
+            (None, None) => {}
+            (Some(interpolation), None) => {
+                write!(self.out, "@interpolate({}) ", interpolation_string(interpolation))?;
+            }
+            (interpolation, Some(sampling)) => {
+                let interpolation = interpolation.map_or("perspective", interpolation_string);
+                write!(self.out, "@interpolate({}, {}) ", interpolation, sampling_string(sampling))?;
+            }
+        }
+        self.out.write_str("var")?;
+        if let Some(class) = storage_class_string(var.class)? {
+            write!(self.out, "<{}>", class)?;
+        }
+        let ty_name = self.type_string(var.ty)?;
+        writeln!(self.out, " {} : {};", name_or_index(&var.name, "global", handle), ty_name)?;
+        Ok(())
+    }
+
+    fn write_function(&mut self, handle: Handle<crate::Function>, fun: &crate::Function) -> Result<(), Error> {
+        self.types.reset();
+        write!(self.out, "fn {}(", name_or_index(&fun.name, "function", handle))?;
+        for (index, &ty) in fun.parameter_types.iter().enumerate() {
+            if index != 0 {
+                self.out.write_str(", ")?;
+            }
+            let ty_name = self.type_string(ty)?;
+            write!(self.out, "param{} : {}", index, ty_name)?;
+        }
+        let return_type = match fun.return_type {
+            Some(ty) => self.type_string(ty)?,
+            None => "void".to_owned(),
+        };
+        writeln!(self.out, ") -> {} {{", return_type)?;
+        for (local_handle, local) in fun.local_variables.iter() {
+            let ty_name = self.type_string(local.ty)?;
+            write!(self.out, "{}var {} : {}", INDENT, name_or_index(&local.name, "local", local_handle), ty_name)?;
+            if let Some(init) = local.init {
+                self.out.write_str(" = ")?;
+                self.write_expression(init, fun)?;
+            }
+            writeln!(self.out, ";")?;
+        }
+        self.write_block(1, &fun.body, handle, fun)?;
+        writeln!(self.out, "}}")?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    fn write(&mut self) -> Result<(), Error> {
+        let module = self.module;
+        for (handle, ty) in module.types.iter() {
+            if let crate::TypeInner::Struct { ref members } = ty.inner {
+                self.write_struct(handle, members)?;
+            }
+        }
+
+        for (handle, _) in module.global_variables.iter() {
+            self.write_global_variable(handle)?;
+        }
+        if !module.global_variables.is_empty() {
+            writeln!(self.out)?;
+        }
+
+        for (handle, fun) in module.functions.iter() {
+            self.write_function(handle, fun)?;
+        }
+
+        for entry_point in module.entry_points.iter() {
+            let stage = match entry_point.stage {
+                crate::ShaderStage::Vertex => "vertex",
+                crate::ShaderStage::Fragment => "fragment",
+                crate::ShaderStage::Compute => "compute",
+            };
+            let fun = &module.functions[entry_point.function];
+            writeln!(
+                self.out,
+                "entry_point {} as \"{}\" = {};",
+                stage,
+                entry_point.name,
+                name_or_index(&fun.name, "function", entry_point.function),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write(module: &crate::Module) -> Result<String, Error> {
+    let mut writer = Writer {
+        module,
+        out: String::new(),
+        types: FunctionTypes::new(module),
+    };
+    writer.write()?;
+    Ok(writer.out)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arena::{Arena, Handle};
+
+    /// Builds a module with a fragment entry point returning `vec4<f32>`,
+    /// with a body built from a constant `vec4<f32>(1.0, 0.0, 0.0, 1.0)`.
+    fn fragment_module(
+        build_body: impl FnOnce(Handle<crate::Constant>, &mut Arena<crate::Expression>) -> Vec<crate::Statement>,
+    ) -> crate::Module {
+        let mut types = Arena::new();
+        let float_ty = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let vec4_ty = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Quad,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        });
+        let mut constants = Arena::new();
+        let components = [1.0, 0.0, 0.0, 1.0]
+            .iter()
+            .map(|&value| constants.fetch_or_append(crate::Constant {
+                name: None,
+                specialization: None,
+                inner: crate::ConstantInner::Float(value),
+                ty: float_ty,
+            }))
+            .collect();
+        let color = constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Composite(components),
+            ty: vec4_ty,
+        });
+        let mut expressions = Arena::new();
+        let body = build_body(color, &mut expressions);
+        let mut functions = Arena::new();
+        let function = functions.append(crate::Function {
+            name: Some("main".to_owned()),
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: Some(vec4_ty),
+            global_usage: Vec::new(),
+            local_variables: Arena::new(),
+            expressions,
+            expression_spans: crate::FastHashMap::default(),
+            body,
+        });
+        crate::Module {
+            header: crate::Header {
+                version: (1, 0, 0),
+                generator: 0,
+            },
+            types,
+            constants,
+            global_variables: Arena::new(),
+            functions,
+            entry_points: vec![crate::EntryPoint {
+                stage: crate::ShaderStage::Fragment,
+                name: "main".to_owned(),
+                function,
+                workgroup_size: [0; 3],
+            }],
+        }
+    }
+
+    #[test]
+    fn write_fragment_constant() {
+        let module = fragment_module(|color, expressions| {
+            let value = expressions.append(crate::Expression::Constant(color));
+            vec![crate::Statement::Return { value: Some(value) }]
+        });
+
+        let output = super::write(&module).unwrap();
+        assert_eq!(output, "\
+fn main() -> vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+
+entry_point fragment as \"main\" = main;
+");
+    }
+
+    #[test]
+    fn write_global_attributes() {
+        let mut module = fragment_module(|color, expressions| {
+            let value = expressions.append(crate::Expression::Constant(color));
+            vec![crate::Statement::Return { value: Some(value) }]
+        });
+        let vec4_ty = module.functions.iter().next().unwrap().1.return_type.unwrap();
+        module.global_variables.append(crate::GlobalVariable {
+            name: Some("tint".to_owned()),
+            class: crate::StorageClass::Uniform,
+            binding: Some(crate::Binding::Resource { group: 0, binding: 1 }),
+            interpolation: None,
+            sampling: None,
+            ty: vec4_ty,
+            init: None,
+        });
+        module.global_variables.append(crate::GlobalVariable {
+            name: Some("position".to_owned()),
+            class: crate::StorageClass::Output,
+            binding: Some(crate::Binding::BuiltIn(spirv::BuiltIn::Position)),
+            interpolation: None,
+            sampling: None,
+            ty: vec4_ty,
+            init: None,
+        });
+        module.global_variables.append(crate::GlobalVariable {
+            name: Some("normal".to_owned()),
+            class: crate::StorageClass::Input,
+            binding: Some(crate::Binding::Location(0)),
+            interpolation: Some(crate::Interpolation::Flat),
+            sampling: None,
+            ty: vec4_ty,
+            init: None,
+        });
+        module.global_variables.append(crate::GlobalVariable {
+            name: Some("uv".to_owned()),
+            class: crate::StorageClass::Input,
+            binding: Some(crate::Binding::Location(1)),
+            interpolation: Some(crate::Interpolation::Linear),
+            sampling: Some(crate::Sampling::Centroid),
+            ty: vec4_ty,
+            init: None,
+        });
+        module.global_variables.append(crate::GlobalVariable {
+            name: Some("depth".to_owned()),
+            class: crate::StorageClass::Input,
+            binding: Some(crate::Binding::Location(2)),
+            interpolation: None,
+            sampling: Some(crate::Sampling::Sample),
+            ty: vec4_ty,
+            init: None,
+        });
+
+        let output = super::write(&module).unwrap();
+        assert!(output.starts_with("\
+@group(0) @binding(1) var<uniform> tint : vec4<f32>;
+@builtin(position) var<out> position : vec4<f32>;
+@location(0) @interpolate(flat) var<in> normal : vec4<f32>;
+@location(1) @interpolate(linear, centroid) var<in> uv : vec4<f32>;
+@location(2) @interpolate(perspective, sample) var<in> depth : vec4<f32>;
+
+"));
+
+        let parsed = crate::front::wgsl::parse_str(&output).unwrap();
+        let globals = parsed.global_variables
+            .iter()
+            .map(|(_, var)| (var.binding.clone(), var.interpolation, var.sampling))
+            .collect::<Vec<_>>();
+        let expected = module.global_variables
+            .iter()
+            .map(|(_, var)| (var.binding.clone(), var.interpolation, var.sampling))
+            .collect::<Vec<_>>();
+        assert_eq!(globals, expected);
+    }
+
+    #[test]
+    fn write_lines() {
+        let module = fragment_module(|color, expressions| {
+            let value = expressions.append(crate::Expression::Constant(color));
+            vec![
+                crate::Statement::Line(Some(crate::Span {
+                    file: Some("color.wgsl".to_owned()),
+                    line: 7,
+                    column: 5,
+                })),
+                crate::Statement::Line(Some(crate::Span { file: None, line: 8, column: 1 })),
+                crate::Statement::Line(None),
+                crate::Statement::Return { value: Some(value) },
+            ]
+        });
+
+        let output = super::write(&module).unwrap();
+        assert!(output.starts_with("\
+fn main() -> vec4<f32> {
+    # color.wgsl:7:5
+    # 8:1
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+"));
+        crate::front::wgsl::parse_str(&output).unwrap();
+    }
+
+    #[test]
+    fn write_if_else() {
+        let mut module = fragment_module(|color, expressions| {
+            expressions.append(crate::Expression::Constant(color));
+            Vec::new()
+        });
+        let bool_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Bool, width: 1 },
+        });
+        let flag = module.constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Bool(true),
+            ty: bool_ty,
+        });
+        let (_, fun) = module.functions.iter_mut().next().unwrap();
+        let color = fun.expressions.iter().next().unwrap().0;
+        let condition = fun.expressions.append(crate::Expression::Constant(flag));
+        fun.body = vec![
+            crate::Statement::If {
+                condition,
+                accept: vec![crate::Statement::Kill],
+                reject: Vec::new(),
+            },
+            crate::Statement::If {
+                condition,
+                accept: vec![crate::Statement::Kill],
+                reject: vec![crate::Statement::Return { value: Some(color) }],
+            },
+            crate::Statement::If {
+                condition,
+                accept: Vec::new(),
+                reject: vec![crate::Statement::Return { value: Some(color) }],
+            },
+        ];
+
+        let output = super::write(&module).unwrap();
+        assert_eq!(output, "\
+fn main() -> vec4<f32> {
+    if (true) {
+        discard;
+    }
+    if (true) {
+        discard;
+    } else {
+        return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+    }
+    if (!true) {
+        return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+    }
+}
+
+entry_point fragment as \"main\" = main;
+");
+    }
+
+    #[test]
+    fn write_unnamed_member() {
+        let mut module = fragment_module(|color, expressions| {
+            let value = expressions.append(crate::Expression::Constant(color));
+            vec![crate::Statement::Return { value: Some(value) }]
+        });
+        let vec4_ty = module.functions.iter().next().unwrap().1.return_type.unwrap();
+        let struct_ty = module.types.append(crate::Type {
+            name: Some("Light".to_owned()),
+            inner: crate::TypeInner::Struct {
+                members: vec![crate::StructMember {
+                    name: None,
+                    binding: None,
+                    ty: vec4_ty,
+                    offset: 0,
+                }],
+            },
+        });
+        let light = module.global_variables.append(crate::GlobalVariable {
+            name: Some("light".to_owned()),
+            class: crate::StorageClass::Private,
+            binding: None,
+            interpolation: None,
+            sampling: None,
+            ty: struct_ty,
+            init: None,
+        });
+        let tint = module.global_variables.append(crate::GlobalVariable {
+            name: Some("tint".to_owned()),
+            class: crate::StorageClass::Private,
+            binding: None,
+            interpolation: None,
+            sampling: None,
+            ty: vec4_ty,
+            init: None,
+        });
+        let (_, fun) = module.functions.iter_mut().next().unwrap();
+        let base = fun.expressions.append(crate::Expression::GlobalVariable(light));
+        let value = fun.expressions.append(crate::Expression::AccessIndex { base, index: 0 });
+        fun.body = vec![crate::Statement::Return { value: Some(value) }];
+
+        let output = super::write(&module).unwrap();
+        assert!(output.contains("    [[offset 0]] member0 : vec4<f32>;\n"));
+        assert!(output.contains("    return light.member0;\n"));
+        crate::front::wgsl::parse_str(&output).unwrap();
+
+        // indices past the last member or component are rejected
+        for &(global, index) in &[(light, 1), (tint, 4)] {
+            let (_, fun) = module.functions.iter_mut().next().unwrap();
+            let base = fun.expressions.append(crate::Expression::GlobalVariable(global));
+            let value = fun.expressions.append(crate::Expression::AccessIndex { base, index });
+            fun.body = vec![crate::Statement::Return { value: Some(value) }];
+            match super::write(&module) {
+                Err(super::Error::Resolve(crate::proc::ResolveError::InvalidAccessIndex)) => (),
+                other => panic!("Unexpected result {:?}", other),
+            }
+            // leave the expressions valid for the next index
+            let (_, fun) = module.functions.iter_mut().next().unwrap();
+            fun.expressions[value] = crate::Expression::GlobalVariable(global);
+        }
+    }
+
+    #[test]
+    fn write_unsupported() {
+        let module = fragment_module(|_, expressions| {
+            let selector = expressions.append(crate::Expression::FunctionParameter(0));
+            vec![crate::Statement::Switch {
+                selector,
+                cases: crate::FastHashMap::default(),
+                default: Vec::new(),
+            }]
+        });
+        match super::write(&module) {
+            Err(super::Error::UnsupportedStatement(_, crate::Statement::Switch { .. })) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
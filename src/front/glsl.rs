@@ -0,0 +1,1030 @@
+/*! OpenGL Shading Language (GLSL) front end
+
+Parses a subset of GLSL into the IR: scalar, vector and matrix types,
+`in`/`out`/`uniform` globals with `layout` qualifiers, functions,
+arithmetic, `if`/`else`, and `return`. Preprocessor directives,
+including `#version`, are skipped.
+
+## Calls
+
+Functions have to be defined before they are called, since prototypes
+are not supported. Built-in functions like `sin` or `dot` map to math
+expressions, and anything else is an unknown identifier.
+
+## Entry points
+
+The `main` function becomes the single entry point of the module,
+for the stage given to `parse`.
+
+## Built-ins
+
+Built-in variables like `gl_Position` are declared as global variables
+with a built-in binding when they are first used.
+!*/
+
+use crate::{
+    arena::{Arena, Handle},
+    proc::{ResolveError, Typifier},
+    FastHashMap,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Token<'a> {
+    Word(&'a str),
+    Number(&'a str),
+    Operator(&'a str),
+    Unknown(char),
+    End,
+}
+
+mod lex {
+    use super::Token;
+
+    const OPERATORS: &[&str] = &["==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/="];
+    const PUNCTUATION: &str = "(){}[];,.=+-*/%<>!";
+
+    /// Skip whitespace, comments, and preprocessor directives.
+    fn skip_ignored(mut input: &str) -> &str {
+        loop {
+            input = input.trim_start();
+            if input.starts_with("//") || input.starts_with('#') {
+                input = match input.find('\n') {
+                    Some(pos) => &input[pos ..],
+                    None => "",
+                };
+            } else if input.starts_with("/*") {
+                input = match input[2 ..].find("*/") {
+                    Some(pos) => &input[pos + 4 ..],
+                    None => "",
+                };
+            } else {
+                return input;
+            }
+        }
+    }
+
+    fn consume_number(input: &str) -> (&str, &str) {
+        let mut prev = ' ';
+        let pos = input
+            .find(|c: char| {
+                let exponent_sign = (c == '+' || c == '-') && (prev == 'e' || prev == 'E');
+                prev = c;
+                !(c.is_ascii_alphanumeric() || c == '.' || exponent_sign)
+            })
+            .unwrap_or(input.len());
+        input.split_at(pos)
+    }
+
+    pub fn consume_token(input: &str) -> (Token<'_>, &str) {
+        let input = skip_ignored(input);
+        let mut chars = input.chars();
+        let cur = match chars.next() {
+            Some(c) => c,
+            None => return (Token::End, input),
+        };
+        if cur.is_ascii_alphabetic() || cur == '_' {
+            let pos = input
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(input.len());
+            let (word, rest) = input.split_at(pos);
+            (Token::Word(word), rest)
+        } else if cur.is_ascii_digit() || (cur == '.' && chars.next().map_or(false, |c| c.is_ascii_digit())) {
+            let (number, rest) = consume_number(input);
+            (Token::Number(number), rest)
+        } else if let Some(op) = OPERATORS.iter().find(|op| input.starts_with(*op)) {
+            let (op, rest) = input.split_at(op.len());
+            (Token::Operator(op), rest)
+        } else if PUNCTUATION.contains(cur) {
+            let (op, rest) = input.split_at(1);
+            (Token::Operator(op), rest)
+        } else {
+            (Token::Unknown(cur), &input[cur.len_utf8() ..])
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error<'a> {
+    Unexpected(Token<'a>),
+    BadInteger(&'a str, std::num::ParseIntError),
+    BadFloat(&'a str, std::num::ParseFloatError),
+    BadAccessor(&'a str),
+    BadConstructor(&'a str),
+    InvalidResolve(ResolveError),
+    UnknownLayoutQualifier(&'a str),
+    UnknownIdent(&'a str),
+    UnknownType(&'a str),
+    MissingEntryPoint,
+}
+
+#[derive(Debug)]
+pub struct ParseError<'a> {
+    pub error: Error<'a>,
+    pub pos: (usize, usize),
+}
+
+#[derive(Clone)]
+struct Lexer<'a> {
+    input: &'a str,
+}
+
+impl<'a> Lexer<'a> {
+    #[must_use]
+    fn next(&mut self) -> Token<'a> {
+        let (token, rest) = lex::consume_token(self.input);
+        self.input = rest;
+        token
+    }
+
+    #[must_use]
+    fn peek(&mut self) -> Token<'a> {
+        self.clone().next()
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Error<'a>> {
+        match self.next() {
+            Token::Operator(op) if op == expected => Ok(()),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+
+    fn skip(&mut self, what: &str) -> bool {
+        match lex::consume_token(self.input) {
+            (Token::Operator(op), rest) | (Token::Word(op), rest) if op == what => {
+                self.input = rest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn next_ident(&mut self) -> Result<&'a str, Error<'a>> {
+        match self.next() {
+            Token::Word(word) => Ok(word),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+
+    fn next_uint_literal(&mut self) -> Result<u32, Error<'a>> {
+        match self.next() {
+            Token::Number(word) => word.parse().map_err(|err| Error::BadInteger(word, err)),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+}
+
+const BINARY_OPERATORS: &[&[(&str, crate::BinaryOperator)]] = &[
+    &[("||", crate::BinaryOperator::LogicalOr)],
+    &[("&&", crate::BinaryOperator::LogicalAnd)],
+    &[
+        ("==", crate::BinaryOperator::Equal),
+        ("!=", crate::BinaryOperator::NotEqual),
+    ],
+    &[
+        ("<", crate::BinaryOperator::Less),
+        ("<=", crate::BinaryOperator::LessEqual),
+        (">", crate::BinaryOperator::Greater),
+        (">=", crate::BinaryOperator::GreaterEqual),
+    ],
+    &[
+        ("+", crate::BinaryOperator::Add),
+        ("-", crate::BinaryOperator::Subtract),
+    ],
+    &[
+        ("*", crate::BinaryOperator::Multiply),
+        ("/", crate::BinaryOperator::Divide),
+        ("%", crate::BinaryOperator::Modulo),
+    ],
+];
+
+fn get_vector_size(word: &str) -> Option<crate::VectorSize> {
+    match word {
+        "2" => Some(crate::VectorSize::Bi),
+        "3" => Some(crate::VectorSize::Tri),
+        "4" => Some(crate::VectorSize::Quad),
+        _ => None,
+    }
+}
+
+fn get_type_inner(word: &str) -> Option<crate::TypeInner> {
+    let (kind, width) = match word {
+        "float" => (crate::ScalarKind::Float, 32),
+        "int" => (crate::ScalarKind::Sint, 32),
+        "uint" => (crate::ScalarKind::Uint, 32),
+        "bool" => (crate::ScalarKind::Bool, 1),
+        _ => {
+            for &(prefix, kind, width) in &[
+                ("vec", crate::ScalarKind::Float, 32),
+                ("ivec", crate::ScalarKind::Sint, 32),
+                ("uvec", crate::ScalarKind::Uint, 32),
+                ("bvec", crate::ScalarKind::Bool, 1),
+            ] {
+                if word.starts_with(prefix) {
+                    let size = get_vector_size(&word[prefix.len() ..])?;
+                    return Some(crate::TypeInner::Vector { size, kind, width });
+                }
+            }
+            if !word.starts_with("mat") {
+                return None;
+            }
+            let rest = &word[3 ..];
+            let (columns, rows) = match rest.find('x') {
+                Some(pos) => (get_vector_size(&rest[.. pos])?, get_vector_size(&rest[pos + 1 ..])?),
+                None => (get_vector_size(rest)?, get_vector_size(rest)?),
+            };
+            return Some(crate::TypeInner::Matrix {
+                columns,
+                rows,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            });
+        }
+    };
+    Some(crate::TypeInner::Scalar { kind, width })
+}
+
+fn get_built_in(word: &str) -> Option<(spirv::BuiltIn, crate::StorageClass, crate::TypeInner)> {
+    let vec4 = crate::TypeInner::Vector {
+        size: crate::VectorSize::Quad,
+        kind: crate::ScalarKind::Float,
+        width: 32,
+    };
+    let uvec3 = crate::TypeInner::Vector {
+        size: crate::VectorSize::Tri,
+        kind: crate::ScalarKind::Uint,
+        width: 32,
+    };
+    let float = crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 };
+    let int = crate::TypeInner::Scalar { kind: crate::ScalarKind::Sint, width: 32 };
+    let (input, output) = (crate::StorageClass::Input, crate::StorageClass::Output);
+    Some(match word {
+        "gl_Position" => (spirv::BuiltIn::Position, output, vec4),
+        "gl_FragCoord" => (spirv::BuiltIn::FragCoord, input, vec4),
+        "gl_FragDepth" => (spirv::BuiltIn::FragDepth, output, float),
+        "gl_VertexID" => (spirv::BuiltIn::VertexId, input, int),
+        "gl_VertexIndex" => (spirv::BuiltIn::VertexIndex, input, int),
+        "gl_InstanceID" => (spirv::BuiltIn::InstanceId, input, int),
+        "gl_GlobalInvocationID" => (spirv::BuiltIn::GlobalInvocationId, input, uvec3),
+        "gl_LocalInvocationID" => (spirv::BuiltIn::LocalInvocationId, input, uvec3),
+        _ => return None,
+    })
+}
+
+/// Returns the built-in function called `word` with the given number of arguments.
+fn get_math_function(word: &str, argument_count: usize) -> Option<crate::MathFunction> {
+    use crate::MathFunction as Mf;
+    Some(match (word, argument_count) {
+        ("abs", 1) => Mf::Abs,
+        ("min", 2) => Mf::Min,
+        ("max", 2) => Mf::Max,
+        ("clamp", 3) => Mf::Clamp,
+        ("sin", 1) => Mf::Sin,
+        ("cos", 1) => Mf::Cos,
+        ("tan", 1) => Mf::Tan,
+        ("asin", 1) => Mf::Asin,
+        ("acos", 1) => Mf::Acos,
+        ("atan", 1) => Mf::Atan,
+        ("atan", 2) => Mf::Atan2,
+        ("floor", 1) => Mf::Floor,
+        ("ceil", 1) => Mf::Ceil,
+        ("round", 1) => Mf::Round,
+        ("fract", 1) => Mf::Fract,
+        ("trunc", 1) => Mf::Trunc,
+        ("exp", 1) => Mf::Exp,
+        ("exp2", 1) => Mf::Exp2,
+        ("log", 1) => Mf::Log,
+        ("log2", 1) => Mf::Log2,
+        ("pow", 2) => Mf::Pow,
+        ("distance", 2) => Mf::Distance,
+        ("length", 1) => Mf::Length,
+        ("normalize", 1) => Mf::Normalize,
+        ("reflect", 2) => Mf::Reflect,
+        ("sign", 1) => Mf::Sign,
+        ("sqrt", 1) => Mf::Sqrt,
+        ("inversesqrt", 1) => Mf::InverseSqrt,
+        ("mix", 3) => Mf::Mix,
+        ("step", 2) => Mf::Step,
+        ("smoothstep", 3) => Mf::SmoothStep,
+        _ => return None,
+    })
+}
+
+fn get_component(accessor: char) -> Option<u32> {
+    match accessor {
+        'x' | 'r' | 's' => Some(0),
+        'y' | 'g' | 't' => Some(1),
+        'z' | 'b' | 'p' => Some(2),
+        'w' | 'a' | 'q' => Some(3),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Layout {
+    location: Option<u32>,
+    set: Option<u32>,
+    binding: Option<u32>,
+    local_size: [Option<u32>; 3],
+}
+
+impl Layout {
+    fn get_binding(&self) -> Option<crate::Binding> {
+        match *self {
+            Layout { location: Some(location), .. } => Some(crate::Binding::Location(location)),
+            Layout { binding: Some(binding), set, .. } => Some(crate::Binding::Resource {
+                group: set.unwrap_or(0),
+                binding,
+            }),
+            _ => None,
+        }
+    }
+}
+
+struct FunctionContext<'a> {
+    lookup_ident: FastHashMap<&'a str, Handle<crate::Expression>>,
+    typifier: Typifier,
+    expressions: Arena<crate::Expression>,
+    local_variables: Arena<crate::LocalVariable>,
+    parameter_types: Vec<Handle<crate::Type>>,
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    module: crate::Module,
+    lookup_global: FastHashMap<&'a str, Handle<crate::GlobalVariable>>,
+    lookup_function: FastHashMap<&'a str, Handle<crate::Function>>,
+    entry_point: Option<Handle<crate::Function>>,
+    workgroup_size: [u32; 3],
+}
+
+impl<'a> Parser<'a> {
+    fn parse_type(&mut self, word: &'a str) -> Result<Handle<crate::Type>, Error<'a>> {
+        let inner = get_type_inner(word).ok_or(Error::UnknownType(word))?;
+        Ok(Typifier::deduce_type_handle(inner, &mut self.module.types))
+    }
+
+    fn append_constant(&mut self, inner: crate::ConstantInner, kind: crate::ScalarKind) -> crate::Expression {
+        let width = if kind == crate::ScalarKind::Bool { 1 } else { 32 };
+        let ty = Typifier::deduce_type_handle(
+            crate::TypeInner::Scalar { kind, width },
+            &mut self.module.types,
+        );
+        crate::Expression::Constant(self.module.constants.fetch_or_append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner,
+            ty,
+        }))
+    }
+
+    fn parse_number(&mut self, word: &'a str) -> Result<crate::Expression, Error<'a>> {
+        let (inner, kind) = if word.ends_with(&['u', 'U'][..]) {
+            let value = word[.. word.len() - 1].parse().map_err(|err| Error::BadInteger(word, err))?;
+            (crate::ConstantInner::Uint(value), crate::ScalarKind::Uint)
+        } else if word.contains(&['.', 'e', 'E'][..]) || word.ends_with(&['f', 'F'][..]) {
+            let number = word.trim_end_matches(&['f', 'F'][..]);
+            let value = number.parse().map_err(|err| Error::BadFloat(word, err))?;
+            (crate::ConstantInner::Float(value), crate::ScalarKind::Float)
+        } else {
+            let value = word.parse().map_err(|err| Error::BadInteger(word, err))?;
+            (crate::ConstantInner::Sint(value), crate::ScalarKind::Sint)
+        };
+        Ok(self.append_constant(inner, kind))
+    }
+
+    fn resolve_type(
+        &mut self,
+        ctx: &mut FunctionContext<'a>,
+        handle: Handle<crate::Expression>,
+    ) -> Result<Handle<crate::Type>, Error<'a>> {
+        ctx.typifier
+            .resolve(
+                handle,
+                &ctx.expressions,
+                &mut self.module.types,
+                &self.module.constants,
+                &self.module.global_variables,
+                &ctx.local_variables,
+                &self.module.functions,
+                &ctx.parameter_types,
+            )
+            .map_err(Error::InvalidResolve)
+    }
+
+    fn parse_arguments(&mut self, ctx: &mut FunctionContext<'a>) -> Result<Vec<Handle<crate::Expression>>, Error<'a>> {
+        let mut arguments = Vec::new();
+        self.lexer.expect("(")?;
+        while !self.lexer.skip(")") {
+            if !arguments.is_empty() {
+                self.lexer.expect(",")?;
+            }
+            arguments.push(self.parse_expression(ctx)?);
+        }
+        Ok(arguments)
+    }
+
+    fn lookup_variable(&mut self, ctx: &mut FunctionContext<'a>, word: &'a str) -> Result<Handle<crate::Expression>, Error<'a>> {
+        if let Some(&handle) = ctx.lookup_ident.get(word) {
+            return Ok(handle);
+        }
+        let var_handle = match self.lookup_global.get(word) {
+            Some(&handle) => handle,
+            None => {
+                let (built_in, class, inner) = get_built_in(word).ok_or(Error::UnknownIdent(word))?;
+                let ty = Typifier::deduce_type_handle(inner, &mut self.module.types);
+                let handle = self.module.global_variables.append(crate::GlobalVariable {
+                    name: Some(word.to_owned()),
+                    class,
+                    binding: Some(crate::Binding::BuiltIn(built_in)),
+                    interpolation: None,
+                    sampling: None,
+                    ty,
+                    init: None,
+                });
+                self.lookup_global.insert(word, handle);
+                handle
+            }
+        };
+        let handle = ctx.expressions.append(crate::Expression::GlobalVariable(var_handle));
+        ctx.lookup_ident.insert(word, handle);
+        Ok(handle)
+    }
+
+    fn parse_primary_expression(&mut self, ctx: &mut FunctionContext<'a>) -> Result<Handle<crate::Expression>, Error<'a>> {
+        let expression = match self.lexer.next() {
+            Token::Operator("(") => {
+                let expr = self.parse_expression(ctx)?;
+                self.lexer.expect(")")?;
+                return Ok(expr);
+            }
+            Token::Number(word) => self.parse_number(word)?,
+            Token::Word("true") => self.append_constant(crate::ConstantInner::Bool(true), crate::ScalarKind::Bool),
+            Token::Word("false") => self.append_constant(crate::ConstantInner::Bool(false), crate::ScalarKind::Bool),
+            Token::Word(word) => match get_type_inner(word) {
+                Some(crate::TypeInner::Vector { .. }) |
+                Some(crate::TypeInner::Matrix { .. }) => {
+                    let ty = self.parse_type(word)?;
+                    let components = self.parse_arguments(ctx)?;
+                    crate::Expression::Compose { ty, components }
+                }
+                Some(_) => return Err(Error::BadConstructor(word)),
+                None if self.lexer.peek() == Token::Operator("(") => {
+                    let arguments = self.parse_arguments(ctx)?;
+                    match (self.lookup_function.get(word), word, &arguments[..]) {
+                        (Some(&function), _, _) => crate::Expression::Call {
+                            origin: crate::FunctionOrigin::Local(function),
+                            arguments,
+                        },
+                        (None, "dot", &[left, right]) => crate::Expression::DotProduct(left, right),
+                        (None, "cross", &[left, right]) => crate::Expression::CrossProduct(left, right),
+                        (None, _, _) => crate::Expression::Math {
+                            fun: get_math_function(word, arguments.len()).ok_or(Error::UnknownIdent(word))?,
+                            arguments,
+                        },
+                    }
+                }
+                None => return self.lookup_variable(ctx, word),
+            },
+            other => return Err(Error::Unexpected(other)),
+        };
+        Ok(ctx.expressions.append(expression))
+    }
+
+    fn parse_swizzle(
+        &mut self,
+        ctx: &mut FunctionContext<'a>,
+        base: Handle<crate::Expression>,
+        accessor: &'a str,
+    ) -> Result<Handle<crate::Expression>, Error<'a>> {
+        let base_ty = self.resolve_type(ctx, base)?;
+        let (size, kind, width) = match self.module.types[base_ty].inner {
+            crate::TypeInner::Vector { size, kind, width } => (size, kind, width),
+            _ => return Err(Error::BadAccessor(accessor)),
+        };
+        let mut components = Vec::with_capacity(accessor.len());
+        for ch in accessor.chars() {
+            let index = get_component(ch)
+                .filter(|&index| index < size as u32)
+                .ok_or(Error::BadAccessor(accessor))?;
+            components.push(ctx.expressions.append(crate::Expression::AccessIndex { base, index }));
+        }
+        if components.len() == 1 {
+            return Ok(components[0]);
+        }
+        let size = get_vector_size(&components.len().to_string()).ok_or(Error::BadAccessor(accessor))?;
+        let ty = Typifier::deduce_type_handle(
+            crate::TypeInner::Vector { size, kind, width },
+            &mut self.module.types,
+        );
+        Ok(ctx.expressions.append(crate::Expression::Compose { ty, components }))
+    }
+
+    fn parse_postfix_expression(&mut self, ctx: &mut FunctionContext<'a>) -> Result<Handle<crate::Expression>, Error<'a>> {
+        let mut handle = self.parse_primary_expression(ctx)?;
+        loop {
+            if self.lexer.skip(".") {
+                let accessor = self.lexer.next_ident()?;
+                handle = self.parse_swizzle(ctx, handle, accessor)?;
+            } else if self.lexer.skip("[") {
+                let index = self.parse_expression(ctx)?;
+                self.lexer.expect("]")?;
+                handle = ctx.expressions.append(crate::Expression::Access { base: handle, index });
+            } else {
+                return Ok(handle);
+            }
+        }
+    }
+
+    fn parse_unary_expression(&mut self, ctx: &mut FunctionContext<'a>) -> Result<Handle<crate::Expression>, Error<'a>> {
+        let op = match self.lexer.peek() {
+            Token::Operator("-") => crate::UnaryOperator::Negate,
+            Token::Operator("!") => crate::UnaryOperator::Not,
+            _ => return self.parse_postfix_expression(ctx),
+        };
+        let _ = self.lexer.next();
+        let expr = self.parse_unary_expression(ctx)?;
+        Ok(ctx.expressions.append(crate::Expression::Unary { op, expr }))
+    }
+
+    fn parse_binary_expression(
+        &mut self,
+        ctx: &mut FunctionContext<'a>,
+        level: usize,
+    ) -> Result<Handle<crate::Expression>, Error<'a>> {
+        let operators = match BINARY_OPERATORS.get(level) {
+            Some(operators) => operators,
+            None => return self.parse_unary_expression(ctx),
+        };
+        let mut left = self.parse_binary_expression(ctx, level + 1)?;
+        loop {
+            let op = match self.lexer.peek() {
+                Token::Operator(token) => operators
+                    .iter()
+                    .find(|&&(name, _)| name == token)
+                    .map(|&(_, op)| op),
+                _ => None,
+            };
+            let op = match op {
+                Some(op) => op,
+                None => return Ok(left),
+            };
+            let _ = self.lexer.next();
+            let right = self.parse_binary_expression(ctx, level + 1)?;
+            left = ctx.expressions.append(crate::Expression::Binary { op, left, right });
+        }
+    }
+
+    fn parse_expression(&mut self, ctx: &mut FunctionContext<'a>) -> Result<Handle<crate::Expression>, Error<'a>> {
+        self.parse_binary_expression(ctx, 0)
+    }
+
+    fn parse_local_declaration(&mut self, ctx: &mut FunctionContext<'a>) -> Result<crate::Statement, Error<'a>> {
+        let ty_word = self.lexer.next_ident()?;
+        let ty = self.parse_type(ty_word)?;
+        let name = self.lexer.next_ident()?;
+        let value = if self.lexer.skip("=") {
+            Some(self.parse_expression(ctx)?)
+        } else {
+            None
+        };
+        self.lexer.expect(";")?;
+        // constant initializers are stored on the variable itself
+        let init = value.filter(|&value| matches!(ctx.expressions[value], crate::Expression::Constant(_)));
+        let var_handle = ctx.local_variables.append(crate::LocalVariable {
+            name: Some(name.to_owned()),
+            ty,
+            init,
+        });
+        let pointer = ctx.expressions.append(crate::Expression::LocalVariable(var_handle));
+        ctx.lookup_ident.insert(name, pointer);
+        Ok(match value {
+            Some(value) if init.is_none() => crate::Statement::Store { pointer, value },
+            _ => crate::Statement::Empty,
+        })
+    }
+
+    /// Parse the body of an `if` or `else`, which is either a block or a single statement.
+    fn parse_branch(&mut self, ctx: &mut FunctionContext<'a>) -> Result<crate::Block, Error<'a>> {
+        if self.lexer.skip("{") {
+            self.parse_block_body(ctx)
+        } else {
+            Ok(vec![self.parse_statement(ctx)?])
+        }
+    }
+
+    /// Check if the statement ahead is a call, like `helper(1.0);`,
+    /// with its result unused.
+    fn is_call_statement(&self) -> bool {
+        let mut lexer = self.lexer.clone();
+        let _ = lexer.next();
+        if lexer.next() != Token::Operator("(") {
+            return false;
+        }
+        // skip to the matching parenthesis
+        let mut depth = 1;
+        while depth != 0 {
+            match lexer.next() {
+                Token::Operator("(") => depth += 1,
+                Token::Operator(")") => depth -= 1,
+                Token::End => return false,
+                _ => {}
+            }
+        }
+        lexer.next() == Token::Operator(";")
+    }
+
+    fn parse_statement(&mut self, ctx: &mut FunctionContext<'a>) -> Result<crate::Statement, Error<'a>> {
+        Ok(match self.lexer.peek() {
+            Token::Operator("{") => {
+                let _ = self.lexer.next();
+                crate::Statement::Block(self.parse_block_body(ctx)?)
+            }
+            Token::Operator(";") => {
+                let _ = self.lexer.next();
+                crate::Statement::Empty
+            }
+            Token::Word("if") => {
+                let _ = self.lexer.next();
+                self.lexer.expect("(")?;
+                let condition = self.parse_expression(ctx)?;
+                self.lexer.expect(")")?;
+                let accept = self.parse_branch(ctx)?;
+                let reject = if self.lexer.skip("else") {
+                    self.parse_branch(ctx)?
+                } else {
+                    Vec::new()
+                };
+                crate::Statement::If { condition, accept, reject }
+            }
+            Token::Word("return") => {
+                let _ = self.lexer.next();
+                let value = if self.lexer.skip(";") {
+                    None
+                } else {
+                    let value = self.parse_expression(ctx)?;
+                    self.lexer.expect(";")?;
+                    Some(value)
+                };
+                crate::Statement::Return { value }
+            }
+            Token::Word("discard") => {
+                let _ = self.lexer.next();
+                self.lexer.expect(";")?;
+                crate::Statement::Kill
+            }
+            Token::Word(word) if get_type_inner(word).is_some() => self.parse_local_declaration(ctx)?,
+            Token::Word(word) if self.lookup_function.contains_key(word) && self.is_call_statement() => {
+                let _ = self.lexer.next();
+                let arguments = self.parse_arguments(ctx)?;
+                self.lexer.expect(";")?;
+                crate::Statement::Call {
+                    function: self.lookup_function[word],
+                    arguments,
+                }
+            }
+            _ => {
+                // assignment, or an expression without side effects
+                let pointer = self.parse_expression(ctx)?;
+                let op = match self.lexer.next() {
+                    Token::Operator(";") => return Ok(crate::Statement::Empty),
+                    Token::Operator("=") => None,
+                    Token::Operator("+=") => Some(crate::BinaryOperator::Add),
+                    Token::Operator("-=") => Some(crate::BinaryOperator::Subtract),
+                    Token::Operator("*=") => Some(crate::BinaryOperator::Multiply),
+                    Token::Operator("/=") => Some(crate::BinaryOperator::Divide),
+                    other => return Err(Error::Unexpected(other)),
+                };
+                let mut value = self.parse_expression(ctx)?;
+                if let Some(op) = op {
+                    value = ctx.expressions.append(crate::Expression::Binary {
+                        op,
+                        left: pointer,
+                        right: value,
+                    });
+                }
+                self.lexer.expect(";")?;
+                crate::Statement::Store { pointer, value }
+            }
+        })
+    }
+
+    /// Parse statements up to and including the closing brace.
+    fn parse_block_body(&mut self, ctx: &mut FunctionContext<'a>) -> Result<crate::Block, Error<'a>> {
+        let mut block = Vec::new();
+        while !self.lexer.skip("}") {
+            block.push(self.parse_statement(ctx)?);
+        }
+        Ok(block)
+    }
+
+    fn parse_function(&mut self, return_word: &'a str, name: &'a str) -> Result<(), Error<'a>> {
+        let return_type = match return_word {
+            "void" => None,
+            _ => Some(self.parse_type(return_word)?),
+        };
+        let mut ctx = FunctionContext {
+            lookup_ident: FastHashMap::default(),
+            typifier: Typifier::new(),
+            expressions: Arena::new(),
+            local_variables: Arena::new(),
+            parameter_types: Vec::new(),
+        };
+        if !self.lexer.skip("void") {
+            while self.lexer.peek() != Token::Operator(")") {
+                if !ctx.parameter_types.is_empty() {
+                    self.lexer.expect(",")?;
+                }
+                self.lexer.skip("in");
+                let ty_word = self.lexer.next_ident()?;
+                let ty = self.parse_type(ty_word)?;
+                let param_name = self.lexer.next_ident()?;
+                let index = ctx.parameter_types.len() as u32;
+                let handle = ctx.expressions.append(crate::Expression::FunctionParameter(index));
+                ctx.lookup_ident.insert(param_name, handle);
+                ctx.parameter_types.push(ty);
+            }
+        }
+        self.lexer.expect(")")?;
+        self.lexer.expect("{")?;
+        let body = self.parse_block_body(&mut ctx)?;
+
+        let handle = self.module.functions.append(crate::Function {
+            name: Some(name.to_owned()),
+            control: spirv::FunctionControl::empty(),
+            parameter_types: ctx.parameter_types,
+            return_type,
+            global_usage: Vec::new(),
+            local_variables: ctx.local_variables,
+            expressions: ctx.expressions,
+            expression_spans: crate::FastHashMap::default(),
+            body,
+        });
+        self.lookup_function.insert(name, handle);
+        if name == "main" {
+            self.entry_point = Some(handle);
+        }
+        Ok(())
+    }
+
+    fn parse_layout(&mut self) -> Result<Layout, Error<'a>> {
+        let mut layout = Layout::default();
+        self.lexer.expect("(")?;
+        loop {
+            let qualifier = self.lexer.next_ident()?;
+            match qualifier {
+                "std140" | "std430" => {}
+                _ => {
+                    self.lexer.expect("=")?;
+                    let value = Some(self.lexer.next_uint_literal()?);
+                    match qualifier {
+                        "location" => layout.location = value,
+                        "set" => layout.set = value,
+                        "binding" => layout.binding = value,
+                        "local_size_x" => layout.local_size[0] = value,
+                        "local_size_y" => layout.local_size[1] = value,
+                        "local_size_z" => layout.local_size[2] = value,
+                        other => return Err(Error::UnknownLayoutQualifier(other)),
+                    }
+                }
+            }
+            if !self.lexer.skip(",") {
+                break;
+            }
+        }
+        self.lexer.expect(")")?;
+        Ok(layout)
+    }
+
+    fn parse_global_decl(&mut self) -> Result<bool, Error<'a>> {
+        if self.lexer.peek() == Token::End {
+            return Ok(false);
+        }
+        let layout = if self.lexer.skip("layout") {
+            self.parse_layout()?
+        } else {
+            Layout::default()
+        };
+        let class = if self.lexer.skip("in") {
+            Some(crate::StorageClass::Input)
+        } else if self.lexer.skip("out") {
+            Some(crate::StorageClass::Output)
+        } else if self.lexer.skip("uniform") {
+            Some(crate::StorageClass::Uniform)
+        } else {
+            None
+        };
+        // `layout(local_size_x = ...) in;` declares the workgroup size
+        if class == Some(crate::StorageClass::Input) && self.lexer.skip(";") {
+            for (size, &value) in self.workgroup_size.iter_mut().zip(&layout.local_size) {
+                *size = value.unwrap_or(1);
+            }
+            return Ok(true);
+        }
+        let ty_word = self.lexer.next_ident()?;
+        let name = self.lexer.next_ident()?;
+        if class.is_none() && self.lexer.skip("(") {
+            self.parse_function(ty_word, name)?;
+            return Ok(true);
+        }
+        let ty = self.parse_type(ty_word)?;
+        self.lexer.expect(";")?;
+        let handle = self.module.global_variables.append(crate::GlobalVariable {
+            name: Some(name.to_owned()),
+            class: class.unwrap_or(crate::StorageClass::Private),
+            binding: layout.get_binding(),
+            interpolation: None,
+            sampling: None,
+            ty,
+            init: None,
+        });
+        self.lookup_global.insert(name, handle);
+        Ok(true)
+    }
+}
+
+pub fn parse(source: &str, stage: crate::ShaderStage) -> Result<crate::Module, ParseError<'_>> {
+    let mut parser = Parser {
+        lexer: Lexer { input: source },
+        module: crate::Module::generate_empty(),
+        lookup_global: FastHashMap::default(),
+        lookup_function: FastHashMap::default(),
+        entry_point: None,
+        workgroup_size: [0; 3],
+    };
+    let result = loop {
+        match parser.parse_global_decl() {
+            Ok(true) => {}
+            Ok(false) => break parser.entry_point.ok_or(Error::MissingEntryPoint),
+            Err(error) => break Err(error),
+        }
+    };
+    let function = match result {
+        Ok(function) => function,
+        Err(error) => {
+            let pos = source.len() - parser.lexer.input.len();
+            let (mut rows, mut cols) = (0, 1);
+            for line in source[..pos].lines() {
+                rows += 1;
+                cols = line.len();
+            }
+            return Err(ParseError { error, pos: (rows, cols) });
+        }
+    };
+
+    let mut module = parser.module;
+    // built-ins may be declared after a function is parsed,
+    // so the usage is only known once everything is parsed
+    for (_, fun) in module.functions.iter_mut() {
+        fun.global_usage = crate::GlobalUse::scan(&fun.expressions, &fun.body, &module.global_variables);
+    }
+    module.entry_points.push(crate::EntryPoint {
+        stage,
+        name: "main".to_owned(),
+        function,
+        workgroup_size: if stage == crate::ShaderStage::Compute {
+            parser.workgroup_size
+        } else {
+            [0; 3]
+        },
+    });
+    Ok(module)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse_vertex_shader() {
+        let module = super::parse("
+            #version 450
+            layout(location = 0) in vec2 a_pos;
+
+            void main() {
+                gl_Position = vec4(a_pos * 2.0, 0.0, 1.0);
+            }
+        ", crate::ShaderStage::Vertex).unwrap();
+
+        assert_eq!(module.entry_points.len(), 1);
+        let entry_point = &module.entry_points[0];
+        assert_eq!(entry_point.stage, crate::ShaderStage::Vertex);
+        assert_eq!(entry_point.name, "main");
+
+        let (_, a_pos) = module.global_variables.iter().next().unwrap();
+        assert_eq!(a_pos.class, crate::StorageClass::Input);
+        assert_eq!(a_pos.binding, Some(crate::Binding::Location(0)));
+        let (_, position) = module.global_variables.iter().nth(1).unwrap();
+        assert_eq!(position.binding, Some(crate::Binding::BuiltIn(spirv::BuiltIn::Position)));
+
+        let fun = &module.functions[entry_point.function];
+        let product = fun.expressions.iter().find_map(|(_, expr)| match *expr {
+            crate::Expression::Binary { op, left, right } => Some((op, left, right)),
+            _ => None,
+        });
+        match product {
+            Some((crate::BinaryOperator::Multiply, left, right)) => {
+                assert!(matches!(fun.expressions[left], crate::Expression::GlobalVariable(_)));
+                assert!(matches!(fun.expressions[right], crate::Expression::Constant(_)));
+            }
+            other => panic!("Unexpected binary expression {:?}", other),
+        }
+        assert!(matches!(fun.body[..], [crate::Statement::Store { .. }]));
+        crate::proc::Validator::new().validate(&module).unwrap();
+    }
+
+    #[test]
+    fn parse_if_else() {
+        let module = super::parse("
+            float pick(float a, float b) {
+                if (a < b) {
+                    return a;
+                } else
+                    return b;
+            }
+            void main() {
+                float x = pick(1.0, 2.0);
+            }
+        ", crate::ShaderStage::Fragment).unwrap();
+
+        let (pick, fun) = module.functions.iter().next().unwrap();
+        assert_eq!(fun.parameter_types.len(), 2);
+        match fun.body[..] {
+            [crate::Statement::If { ref accept, ref reject, .. }] => {
+                assert!(matches!(accept[..], [crate::Statement::Return { value: Some(_) }]));
+                assert!(matches!(reject[..], [crate::Statement::Return { value: Some(_) }]));
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        }
+        let main = &module.functions[module.entry_points[0].function];
+        assert!(main.expressions.iter().any(|(_, expr)| match *expr {
+            crate::Expression::Call { ref origin, ref arguments } => {
+                *origin == crate::FunctionOrigin::Local(pick) && arguments.len() == 2
+            }
+            _ => false,
+        }));
+        crate::proc::Validator::new().validate(&module).unwrap();
+    }
+
+    #[test]
+    fn parse_errors() {
+        match super::parse("void helper() {}", crate::ShaderStage::Vertex) {
+            Err(super::ParseError { error: super::Error::MissingEntryPoint, .. }) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match super::parse("void main() {\n    x = 1;\n}", crate::ShaderStage::Vertex) {
+            Err(super::ParseError { error: super::Error::UnknownIdent("x"), pos }) => assert_eq!(pos.0, 2),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        // functions have to be declared before they are called
+        match super::parse("
+            void main() {
+                float x = g(1.0).x;
+            }
+            vec2 g(float a) {
+                return vec2(a, a);
+            }
+        ", crate::ShaderStage::Vertex) {
+            Err(super::ParseError { error: super::Error::UnknownIdent("g"), .. }) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_calls() {
+        let module = super::parse("
+            void helper(float a) {
+            }
+            float twice(float a) {
+                return a * 2.0;
+            }
+            float main_value(float a) {
+                return sin(a) + dot(vec2(a, a), vec2(1.0, 2.0));
+            }
+            void main() {
+                helper(1.0);
+                twice(1.0);
+            }
+        ", crate::ShaderStage::Fragment).unwrap();
+
+        let mut functions = module.functions.iter();
+        let (helper, _) = functions.next().unwrap();
+        let (twice, _) = functions.next().unwrap();
+        let (_, main_value) = functions.next().unwrap();
+        assert!(main_value.expressions.iter().any(|(_, expr)| matches!(*expr, crate::Expression::Math {
+            fun: crate::MathFunction::Sin,
+            ..
+        })));
+        assert!(main_value.expressions.iter().any(|(_, expr)| matches!(*expr, crate::Expression::DotProduct(..))));
+
+        let main = &module.functions[module.entry_points[0].function];
+        match main.body[..] {
+            [
+                crate::Statement::Call { function: first, .. },
+                crate::Statement::Call { function: second, .. },
+            ] => {
+                assert_eq!(first, helper);
+                assert_eq!(second, twice);
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        }
+        crate::proc::Validator::new().validate(&module).unwrap();
+    }
+}
@@ -8,6 +8,14 @@ There map `spirv::Word` into a specific IR handle, plus potentially a bit of
 extra info, such as the related SPIR-V type ID.
 TODO: would be nice to find ways that avoid looking up as much
 
+## Control flow
+
+SPIR-V functions are made of basic blocks, each ending with a terminator
+instruction. We parse all the blocks of a function first, and then
+rebuild the structured statements of the IR by following the branches
+from the entry block, using the merge instructions to find where each
+construct ends.
+
 !*/
 
 use crate::{
@@ -20,6 +28,10 @@ use std::convert::TryInto;
 
 pub const SUPPORTED_CAPABILITIES: &[spirv::Capability] = &[
     spirv::Capability::Shader,
+    spirv::Capability::Matrix,
+    spirv::Capability::Sampled1D,
+    spirv::Capability::Image1D,
+    spirv::Capability::DerivativeControl,
 ];
 pub const SUPPORTED_EXTENSIONS: &[&str] = &[
 ];
@@ -30,6 +42,7 @@ pub const SUPPORTED_EXT_SETS: &[&str] = &[
 #[derive(Debug)]
 pub enum Error {
     InvalidHeader,
+    UnsupportedVersion(u8, u8),
     InvalidWordCount,
     UnknownInstruction(u16),
     UnknownCapability(u32),
@@ -37,6 +50,9 @@ pub enum Error {
     UnsupportedCapability(spirv::Capability),
     UnsupportedExtension(String),
     UnsupportedExtSet(String),
+    UnsupportedExtInst(spirv::Word),
+    /// A vector shuffle picks components from two different vectors.
+    UnsupportedVectorShuffle(spirv::Word),
     UnsupportedType(Handle<crate::Type>),
     UnsupportedExecutionModel(u32),
     UnsupportedStorageClass(u32),
@@ -51,16 +67,58 @@ pub enum Error {
     InvalidSign(spirv::Word),
     InvalidInnerType(spirv::Word),
     InvalidVectorSize(spirv::Word),
-    InvalidVariableClass(spirv::StorageClass),
+    InvalidArraySize(Handle<crate::Constant>),
+    InvalidVariableClass(crate::StorageClass),
     InvalidAccessType(spirv::Word),
     InvalidAccessIndex(Handle<crate::Expression>),
     InvalidLoadType(spirv::Word),
     InvalidStoreType(spirv::Word),
     InvalidBinding(spirv::Word),
+    ConflictingBinding(spirv::Word),
+    UnsupportedControlFlow(spirv::Word),
     WrongFunctionResultType(spirv::Word),
     WrongFunctionParameterType(spirv::Word),
     BadString,
     IncompleteData,
+    /// The module goes beyond one of the limits in `ParseOptions`.
+    LimitExceeded,
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub error: Error,
+    /// Offset, in words, of the instruction that failed to parse.
+    /// The header is at offset 0.
+    pub offset: usize,
+}
+
+/// Limits that keep the parser from exhausting resources on malformed input.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    /// Maximum number of instructions in the module.
+    pub max_instructions: usize,
+    /// Maximum ID bound declared by the header.
+    pub max_bound: spirv::Word,
+    /// Maximum number of IDs to reserve room for ahead of time.
+    /// The ID lookup tables are reserved up to the declared bound,
+    /// capped by this value, and grow on demand past it.
+    pub max_reserve: spirv::Word,
+    /// Maximum length of an `OpTypeArray`.
+    pub max_array_length: spirv::Word,
+    /// Maximum nesting depth of the structured control flow.
+    pub max_nesting_depth: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_instructions: 1 << 24,
+            max_bound: 1 << 22,
+            max_reserve: 1 << 12,
+            max_array_length: 1 << 24,
+            max_nesting_depth: 256,
+        }
+    }
 }
 
 struct Instruction {
@@ -115,6 +173,16 @@ impl<T> LookupHelper for FastHashMap<spirv::Word, T> {
     }
 }
 
+fn map_swizzle_component(index: spirv::Word) -> Option<crate::SwizzleComponent> {
+    match index {
+        0 => Some(crate::SwizzleComponent::X),
+        1 => Some(crate::SwizzleComponent::Y),
+        2 => Some(crate::SwizzleComponent::Z),
+        3 => Some(crate::SwizzleComponent::W),
+        _ => None,
+    }
+}
+
 fn map_vector_size(word: spirv::Word) -> Result<crate::VectorSize, Error> {
     match word {
         2 => Ok(crate::VectorSize::Bi),
@@ -124,8 +192,73 @@ fn map_vector_size(word: spirv::Word) -> Result<crate::VectorSize, Error> {
     }
 }
 
-fn map_storage_class(word: spirv::Word) -> Result<spirv::StorageClass, Error> {
-    spirv::StorageClass::from_u32(word).ok_or(Error::UnsupportedStorageClass(word))
+fn map_math_function(word: spirv::Word) -> Result<crate::MathFunction, Error> {
+    use spirv::GLOp as Gl;
+    use crate::MathFunction as Mf;
+    Ok(match Gl::from_u32(word) {
+        Some(Gl::FAbs) | Some(Gl::SAbs) => Mf::Abs,
+        Some(Gl::FMin) | Some(Gl::UMin) | Some(Gl::SMin) => Mf::Min,
+        Some(Gl::FMax) | Some(Gl::UMax) | Some(Gl::SMax) => Mf::Max,
+        Some(Gl::FClamp) | Some(Gl::UClamp) | Some(Gl::SClamp) => Mf::Clamp,
+        Some(Gl::Sin) => Mf::Sin,
+        Some(Gl::Cos) => Mf::Cos,
+        Some(Gl::Tan) => Mf::Tan,
+        Some(Gl::Asin) => Mf::Asin,
+        Some(Gl::Acos) => Mf::Acos,
+        Some(Gl::Atan) => Mf::Atan,
+        Some(Gl::Atan2) => Mf::Atan2,
+        Some(Gl::Floor) => Mf::Floor,
+        Some(Gl::Ceil) => Mf::Ceil,
+        Some(Gl::Round) => Mf::Round,
+        Some(Gl::Fract) => Mf::Fract,
+        Some(Gl::Trunc) => Mf::Trunc,
+        Some(Gl::Exp) => Mf::Exp,
+        Some(Gl::Exp2) => Mf::Exp2,
+        Some(Gl::Log) => Mf::Log,
+        Some(Gl::Log2) => Mf::Log2,
+        Some(Gl::Pow) => Mf::Pow,
+        Some(Gl::Distance) => Mf::Distance,
+        Some(Gl::Length) => Mf::Length,
+        Some(Gl::Normalize) => Mf::Normalize,
+        Some(Gl::Reflect) => Mf::Reflect,
+        Some(Gl::FSign) | Some(Gl::SSign) => Mf::Sign,
+        Some(Gl::Sqrt) => Mf::Sqrt,
+        Some(Gl::InverseSqrt) => Mf::InverseSqrt,
+        Some(Gl::FMix) => Mf::Mix,
+        Some(Gl::Step) => Mf::Step,
+        Some(Gl::SmoothStep) => Mf::SmoothStep,
+        _ => return Err(Error::UnsupportedExtInst(word)),
+    })
+}
+
+fn map_execution_model(word: spirv::Word) -> Result<crate::ShaderStage, Error> {
+    use spirv::ExecutionModel as Em;
+    match Em::from_u32(word) {
+        Some(Em::Vertex) => Ok(crate::ShaderStage::Vertex),
+        Some(Em::Fragment) => Ok(crate::ShaderStage::Fragment),
+        Some(Em::GLCompute) => Ok(crate::ShaderStage::Compute),
+        _ => Err(Error::UnsupportedExecutionModel(word)),
+    }
+}
+
+/// Map a storage class that the IR can represent.
+///
+/// The rest, like `Image`, `AtomicCounter` or `CrossWorkgroup`, are not
+/// used by graphics shaders and are rejected instead of being conflated.
+fn map_storage_class(word: spirv::Word) -> Result<crate::StorageClass, Error> {
+    use spirv::StorageClass as Sc;
+    match Sc::from_u32(word) {
+        Some(Sc::Function) => Ok(crate::StorageClass::Function),
+        Some(Sc::Uniform) => Ok(crate::StorageClass::Uniform),
+        Some(Sc::StorageBuffer) => Ok(crate::StorageClass::Storage),
+        Some(Sc::Input) => Ok(crate::StorageClass::Input),
+        Some(Sc::Output) => Ok(crate::StorageClass::Output),
+        Some(Sc::Private) => Ok(crate::StorageClass::Private),
+        Some(Sc::Workgroup) => Ok(crate::StorageClass::WorkGroup),
+        Some(Sc::UniformConstant) => Ok(crate::StorageClass::Handle),
+        Some(Sc::PushConstant) => Ok(crate::StorageClass::PushConstant),
+        _ => Err(Error::UnsupportedStorageClass(word)),
+    }
 }
 
 type MemberIndex = u32;
@@ -137,6 +270,10 @@ struct Decoration {
     location: Option<spirv::Word>,
     desc_set: Option<spirv::Word>,
     desc_index: Option<spirv::Word>,
+    offset: Option<spirv::Word>,
+    spec_id: Option<spirv::Word>,
+    interpolation: Option<crate::Interpolation>,
+    sampling: Option<crate::Sampling>,
 }
 
 impl Decoration {
@@ -163,7 +300,7 @@ impl Decoration {
                 desc_set: Some(set),
                 desc_index: Some(binding),
                 ..
-            } => Some(crate::Binding::Descriptor { set, binding }),
+            } => Some(crate::Binding::Resource { group: set, binding }),
             _ => None,
         }
     }
@@ -177,9 +314,11 @@ struct LookupFunctionType {
 
 #[derive(Debug)]
 struct EntryPoint {
-    exec_model: spirv::ExecutionModel,
+    stage: crate::ShaderStage,
     name: String,
     function_id: spirv::Word,
+    workgroup_size: [u32; 3],
+    #[allow(dead_code)]
     variable_ids: Vec<spirv::Word>,
 }
 
@@ -201,12 +340,82 @@ struct LookupVariable {
     type_id: spirv::Word,
 }
 
+#[derive(Debug)]
+struct LookupUndef {
+    ty: Handle<crate::Type>,
+    type_id: spirv::Word,
+}
+
 #[derive(Clone, Debug)]
 struct LookupExpression {
     handle: Handle<crate::Expression>,
     type_id: spirv::Word,
 }
 
+#[derive(Debug)]
+enum MergeInstruction {
+    Selection {
+        merge_id: spirv::Word,
+    },
+    Loop {
+        merge_id: spirv::Word,
+        continue_id: spirv::Word,
+    },
+}
+
+#[derive(Debug)]
+enum Terminator {
+    Return {
+        value: Option<Handle<crate::Expression>>,
+    },
+    Kill,
+    Unreachable,
+    Branch {
+        target_id: spirv::Word,
+    },
+    BranchConditional {
+        condition: Handle<crate::Expression>,
+        true_id: spirv::Word,
+        false_id: spirv::Word,
+    },
+    Switch {
+        selector: Handle<crate::Expression>,
+        default_id: spirv::Word,
+        targets: Vec<(i32, spirv::Word)>,
+    },
+}
+
+/// The blocks that `break` and `continue` statements branch to,
+/// from inside of the innermost enclosing constructs.
+#[derive(Clone, Copy, Debug, Default)]
+struct FlowExits {
+    /// Merge block of the innermost loop or switch.
+    break_id: Option<spirv::Word>,
+    /// Continue target of the innermost loop.
+    continue_id: Option<spirv::Word>,
+}
+
+impl FlowExits {
+    /// Returns the statement that leaves the construct, if branching
+    /// to `block_id` does that.
+    fn statement(&self, block_id: spirv::Word) -> Option<crate::Statement> {
+        if self.break_id == Some(block_id) {
+            Some(crate::Statement::Break)
+        } else if self.continue_id == Some(block_id) {
+            Some(crate::Statement::Continue)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BasicBlock {
+    statements: crate::Block,
+    merge: Option<MergeInstruction>,
+    terminator: Terminator,
+}
+
 #[derive(Clone, Debug)]
 struct LookupSampledImage {
     image: Handle<crate::Expression>,
@@ -215,6 +424,12 @@ struct LookupSampledImage {
 
 pub struct Parser<I> {
     data: I,
+    /// Number of words consumed so far.
+    offset: usize,
+    /// Offset of the instruction being parsed.
+    inst_offset: usize,
+    /// The words are in the opposite byte order.
+    swap_bytes: bool,
     state: ModuleState,
     temp_bytes: Vec<u8>,
     future_decor: FastHashMap<spirv::Word, Decoration>,
@@ -224,16 +439,37 @@ pub struct Parser<I> {
     lookup_void_type: FastHashSet<spirv::Word>,
     lookup_constant: FastHashMap<spirv::Word, LookupConstant>,
     lookup_variable: FastHashMap<spirv::Word, LookupVariable>,
+    lookup_undef: FastHashMap<spirv::Word, LookupUndef>,
     lookup_expression: FastHashMap<spirv::Word, LookupExpression>,
     lookup_sampled_image: FastHashMap<spirv::Word, LookupSampledImage>,
     lookup_function_type: FastHashMap<spirv::Word, LookupFunctionType>,
     lookup_function: FastHashMap<spirv::Word, Handle<crate::Function>>,
+    /// Functions that are called before being defined. Their handles
+    /// are reserved in the module, to be filled in by the definition.
+    pending_functions: FastHashSet<spirv::Word>,
+    /// Result ID of the imported GLSL.std.450 instruction set.
+    ext_glsl_id: Option<spirv::Word>,
+    /// Names of the source files declared with `OpString`.
+    lookup_string: FastHashMap<spirv::Word, String>,
+    /// Source location set by the last `OpLine` of the current block.
+    current_span: Option<crate::Span>,
+    /// Source location of the last statement of the current block.
+    statement_span: Option<crate::Span>,
+    options: ParseOptions,
+    instruction_count: usize,
 }
 
 impl<I: Iterator<Item = u32>> Parser<I> {
     pub fn new(data: I) -> Self {
+        Self::with_options(data, ParseOptions::default())
+    }
+
+    pub fn with_options(data: I, options: ParseOptions) -> Self {
         Parser {
             data,
+            offset: 0,
+            inst_offset: 0,
+            swap_bytes: false,
             state: ModuleState::Empty,
             temp_bytes: Vec::new(),
             future_decor: FastHashMap::default(),
@@ -243,18 +479,33 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             lookup_void_type: FastHashSet::default(),
             lookup_constant: FastHashMap::default(),
             lookup_variable: FastHashMap::default(),
+            lookup_undef: FastHashMap::default(),
             lookup_expression: FastHashMap::default(),
             lookup_sampled_image: FastHashMap::default(),
             lookup_function_type: FastHashMap::default(),
             lookup_function: FastHashMap::default(),
+            pending_functions: FastHashSet::default(),
+            ext_glsl_id: None,
+            lookup_string: FastHashMap::default(),
+            current_span: None,
+            statement_span: None,
+            options,
+            instruction_count: 0,
         }
     }
 
     fn next(&mut self) -> Result<u32, Error> {
-        self.data.next().ok_or(Error::IncompleteData)
+        let word = self.data.next().ok_or(Error::IncompleteData)?;
+        self.offset += 1;
+        Ok(if self.swap_bytes { word.swap_bytes() } else { word })
     }
 
     fn next_inst(&mut self) -> Result<Instruction, Error> {
+        self.inst_offset = self.offset;
+        self.instruction_count += 1;
+        if self.instruction_count > self.options.max_instructions {
+            return Err(Error::LimitExceeded);
+        }
         let word = self.next()?;
         let (wc, opcode) = ((word >> 16) as u16, (word & 0xffff) as u16);
         if wc == 0 {
@@ -265,6 +516,11 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         Ok(Instruction { op, wc })
     }
 
+    /// Read a nul-terminated UTF-8 string literal of at most `count` words,
+    /// with the bytes of each word in little-endian order.
+    ///
+    /// Returns the string and the number of words left in `count`
+    /// after the one holding the terminator.
     fn next_string(&mut self, mut count: u16) -> Result<(String, u16), Error>{
         self.temp_bytes.clear();
         loop {
@@ -315,6 +571,30 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                 inst.expect(base_words + 2)?;
                 dec.desc_index = Some(self.next()?);
             }
+            spirv::Decoration::Offset => {
+                inst.expect(base_words + 2)?;
+                dec.offset = Some(self.next()?);
+            }
+            spirv::Decoration::SpecId => {
+                inst.expect(base_words + 2)?;
+                dec.spec_id = Some(self.next()?);
+            }
+            spirv::Decoration::Flat => {
+                inst.expect(base_words + 1)?;
+                dec.interpolation = Some(crate::Interpolation::Flat);
+            }
+            spirv::Decoration::NoPerspective => {
+                inst.expect(base_words + 1)?;
+                dec.interpolation = Some(crate::Interpolation::Linear);
+            }
+            spirv::Decoration::Centroid => {
+                inst.expect(base_words + 1)?;
+                dec.sampling = Some(crate::Sampling::Centroid);
+            }
+            spirv::Decoration::Sample => {
+                inst.expect(base_words + 1)?;
+                dec.sampling = Some(crate::Sampling::Sample);
+            }
             other => {
                 log::warn!("Unknown decoration {:?}", other);
                 for _ in base_words + 1 .. inst.wc {
@@ -325,17 +605,89 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         Ok(())
     }
 
+    fn parse_line(&mut self, inst: Instruction) -> Result<(), Error> {
+        inst.expect(4)?;
+        let file_id = self.next()?;
+        let line = self.next()?;
+        let column = self.next()?;
+        let file = self.lookup_string
+            .get(&file_id)
+            .ok_or(Error::InvalidId(file_id))?;
+        // an empty name stands for an unknown file
+        let file = if file.is_empty() { None } else { Some(file.clone()) };
+        self.current_span = Some(crate::Span { file, line, column });
+        Ok(())
+    }
+
+    fn parse_no_line(&mut self, inst: Instruction) -> Result<(), Error> {
+        inst.expect(1)?;
+        self.current_span = None;
+        Ok(())
+    }
+
+    /// Push a `Statement::Line` if the source location has changed
+    /// since the last statement of the current block.
+    fn push_line(&mut self, statements: &mut crate::Block) {
+        if self.statement_span != self.current_span {
+            self.statement_span = self.current_span.clone();
+            statements.push(crate::Statement::Line(self.current_span.clone()));
+        }
+    }
+
     fn next_block(
         &mut self,
         fun: &mut crate::Function,
+        functions: &mut Arena<crate::Function>,
         type_arena: &Arena<crate::Type>,
         const_arena: &Arena<crate::Constant>,
-    ) -> Result<(), Error> {
-        loop {
+    ) -> Result<BasicBlock, Error> {
+        let mut statements = Vec::new();
+        let mut merge = None;
+        // line information doesn't cross block boundaries
+        self.current_span = None;
+        self.statement_span = None;
+        let terminator = loop {
             use spirv::Op;
             let inst = self.next_inst()?;
             log::debug!("\t\t{:?} [{}]", inst.op, inst.wc);
+            let expression_count = fun.expressions.len();
             match inst.op {
+                Op::Line => self.parse_line(inst)?,
+                Op::NoLine => self.parse_no_line(inst)?,
+                Op::Variable => {
+                    inst.expect_at_least(4)?;
+                    let result_type_id = self.next()?;
+                    let result_id = self.next()?;
+                    let storage = self.next()?;
+                    match map_storage_class(storage)? {
+                        crate::StorageClass::Function => (),
+                        class => return Err(Error::InvalidVariableClass(class)),
+                    }
+                    let init = if inst.wc > 4 {
+                        inst.expect(5)?;
+                        let init_id = self.next()?;
+                        let lexp = self.lookup_expression.lookup(init_id)?;
+                        Some(lexp.handle)
+                    } else {
+                        None
+                    };
+                    let name = self.future_decor
+                        .remove(&result_id)
+                        .and_then(|decor| decor.name);
+                    let base_id = self.lookup_type
+                        .lookup(result_type_id)?
+                        .base_id
+                        .ok_or(Error::InvalidAccessType(result_type_id))?;
+                    let var_handle = fun.local_variables.append(crate::LocalVariable {
+                        name,
+                        ty: self.lookup_type.lookup(base_id)?.handle,
+                        init,
+                    });
+                    self.lookup_expression.insert(result_id, LookupExpression {
+                        handle: fun.expressions.append(crate::Expression::LocalVariable(var_handle)),
+                        type_id: result_type_id,
+                    });
+                }
                 Op::AccessChain => {
                     struct AccessExpression {
                         base_handle: Handle<crate::Expression>,
@@ -518,15 +870,306 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     if base_type.base_id != Some(value_expr.type_id) {
                         return Err(Error::InvalidStoreType(value_expr.type_id));
                     }
-                    fun.body.push(crate::Statement::Store {
+                    let statement = crate::Statement::Store {
                         pointer: base_expr.handle,
                         value: value_expr.handle,
-                    })
+                    };
+                    self.push_line(&mut statements);
+                    statements.push(statement);
+                }
+                Op::FunctionCall => {
+                    inst.expect_at_least(4)?;
+                    let result_type_id = self.next()?;
+                    let result_id = self.next()?;
+                    let function_id = self.next()?;
+                    let mut arguments = Vec::with_capacity(inst.wc as usize - 4);
+                    for _ in 4 .. inst.wc {
+                        let arg_id = self.next()?;
+                        arguments.push(self.lookup_expression.lookup(arg_id)?.handle);
+                    }
+                    if self.lookup_void_type.contains(&result_type_id) {
+                        self.push_line(&mut statements);
+                        statements.push(crate::Statement::Call {
+                            function: self.reserve_function(functions, function_id),
+                            arguments,
+                        });
+                        continue;
+                    }
+                    let expr = crate::Expression::Call {
+                        origin: crate::FunctionOrigin::Local(self.reserve_function(functions, function_id)),
+                        arguments,
+                    };
+                    let handle = fun.expressions.append(expr);
+                    self.lookup_expression.insert(result_id, LookupExpression {
+                        handle,
+                        type_id: result_type_id,
+                    });
+                }
+                Op::ExtInst => {
+                    inst.expect_at_least(5)?;
+                    let result_type_id = self.next()?;
+                    let result_id = self.next()?;
+                    let set_id = self.next()?;
+                    if Some(set_id) != self.ext_glsl_id {
+                        return Err(Error::InvalidId(set_id));
+                    }
+                    let inst_id = self.next()?;
+                    let mut arguments = Vec::with_capacity(inst.wc as usize - 5);
+                    for _ in 5 .. inst.wc {
+                        let arg_id = self.next()?;
+                        arguments.push(self.lookup_expression.lookup(arg_id)?.handle);
+                    }
+                    // cross products have their own expression
+                    let expr = if inst_id == spirv::GLOp::Cross as spirv::Word {
+                        match arguments[..] {
+                            [a, b] => crate::Expression::CrossProduct(a, b),
+                            _ => return Err(Error::InvalidOperandCount(inst.op, inst.wc)),
+                        }
+                    } else {
+                        crate::Expression::Math {
+                            fun: map_math_function(inst_id)?,
+                            arguments,
+                        }
+                    };
+                    self.lookup_expression.insert(result_id, LookupExpression {
+                        handle: fun.expressions.append(expr),
+                        type_id: result_type_id,
+                    });
+                }
+                Op::SelectionMerge => {
+                    inst.expect(3)?;
+                    let merge_id = self.next()?;
+                    let _selection_control = self.next()?;
+                    merge = Some(MergeInstruction::Selection { merge_id });
+                }
+                Op::LoopMerge => {
+                    inst.expect_at_least(4)?;
+                    let merge_id = self.next()?;
+                    let continue_id = self.next()?;
+                    let _loop_control = self.next()?;
+                    for _ in 4 .. inst.wc {
+                        let _parameter = self.next()?;
+                    }
+                    merge = Some(MergeInstruction::Loop { merge_id, continue_id });
                 }
                 Op::Return => {
                     inst.expect(1)?;
-                    fun.body.push(crate::Statement::Return { value: None });
-                    break
+                    self.push_line(&mut statements);
+                    break Terminator::Return { value: None };
+                }
+                Op::ReturnValue => {
+                    inst.expect(2)?;
+                    let value_id = self.next()?;
+                    let value_expr = self.lookup_expression.lookup(value_id)?;
+                    let value = value_expr.handle;
+                    self.push_line(&mut statements);
+                    break Terminator::Return { value: Some(value) };
+                }
+                Op::Kill => {
+                    inst.expect(1)?;
+                    self.push_line(&mut statements);
+                    break Terminator::Kill;
+                }
+                Op::Unreachable => {
+                    inst.expect(1)?;
+                    break Terminator::Unreachable;
+                }
+                Op::Branch => {
+                    inst.expect(2)?;
+                    let target_id = self.next()?;
+                    break Terminator::Branch { target_id };
+                }
+                Op::BranchConditional => {
+                    inst.expect_at_least(4)?;
+                    let condition_id = self.next()?;
+                    let true_id = self.next()?;
+                    let false_id = self.next()?;
+                    for _ in 4 .. inst.wc {
+                        let _weight = self.next()?;
+                    }
+                    let condition = self.lookup_expression.lookup(condition_id)?.handle;
+                    break Terminator::BranchConditional { condition, true_id, false_id };
+                }
+                Op::Switch => {
+                    inst.expect_at_least(3)?;
+                    let selector_id = self.next()?;
+                    let default_id = self.next()?;
+                    if inst.wc % 2 == 0 {
+                        return Err(Error::InvalidOperandCount(inst.op, inst.wc));
+                    }
+                    let mut targets = Vec::with_capacity((inst.wc as usize - 3) / 2);
+                    for _ in 0 .. targets.capacity() {
+                        let literal = self.next()?;
+                        let target_id = self.next()?;
+                        targets.push((literal as i32, target_id));
+                    }
+                    let selector = self.lookup_expression.lookup(selector_id)?.handle;
+                    break Terminator::Switch { selector, default_id, targets };
+                }
+                Op::SNegate | Op::FNegate => {
+                    self.parse_expr_unary_op(inst, fun, crate::UnaryOperator::Negate)?;
+                }
+                Op::Not | Op::LogicalNot => {
+                    self.parse_expr_unary_op(inst, fun, crate::UnaryOperator::Not)?;
+                }
+                Op::IAdd | Op::FAdd => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::Add)?;
+                }
+                Op::ISub | Op::FSub => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::Subtract)?;
+                }
+                Op::IMul | Op::FMul => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::Multiply)?;
+                }
+                Op::SDiv | Op::UDiv | Op::FDiv => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::Divide)?;
+                }
+                Op::SMod | Op::UMod | Op::FMod => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::Modulo)?;
+                }
+                Op::IEqual | Op::FOrdEqual | Op::LogicalEqual => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::Equal)?;
+                }
+                Op::INotEqual | Op::FOrdNotEqual | Op::LogicalNotEqual => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::NotEqual)?;
+                }
+                Op::SLessThan | Op::ULessThan | Op::FOrdLessThan => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::Less)?;
+                }
+                Op::SLessThanEqual | Op::ULessThanEqual | Op::FOrdLessThanEqual => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::LessEqual)?;
+                }
+                Op::SGreaterThan | Op::UGreaterThan | Op::FOrdGreaterThan => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::Greater)?;
+                }
+                Op::SGreaterThanEqual | Op::UGreaterThanEqual | Op::FOrdGreaterThanEqual => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::GreaterEqual)?;
+                }
+                Op::BitwiseAnd => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::And)?;
+                }
+                Op::BitwiseXor => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::ExclusiveOr)?;
+                }
+                Op::BitwiseOr => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::InclusiveOr)?;
+                }
+                Op::LogicalAnd => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::LogicalAnd)?;
+                }
+                Op::LogicalOr => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::LogicalOr)?;
+                }
+                Op::ShiftLeftLogical => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::ShiftLeftLogical)?;
+                }
+                Op::ShiftRightLogical => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::ShiftRightLogical)?;
+                }
+                Op::ShiftRightArithmetic => {
+                    self.parse_expr_binary_op(inst, fun, crate::BinaryOperator::ShiftRightArithmetic)?;
+                }
+                Op::DPdx | Op::DPdxFine | Op::DPdxCoarse => {
+                    self.parse_expr_derivative(inst, fun, crate::DerivativeAxis::X)?;
+                }
+                Op::DPdy | Op::DPdyFine | Op::DPdyCoarse => {
+                    self.parse_expr_derivative(inst, fun, crate::DerivativeAxis::Y)?;
+                }
+                Op::Fwidth | Op::FwidthFine | Op::FwidthCoarse => {
+                    self.parse_expr_derivative(inst, fun, crate::DerivativeAxis::Width)?;
+                }
+                Op::Any => {
+                    self.parse_expr_intrinsic(inst, fun, crate::IntrinsicFunction::Any)?;
+                }
+                Op::All => {
+                    self.parse_expr_intrinsic(inst, fun, crate::IntrinsicFunction::All)?;
+                }
+                Op::IsNan => {
+                    self.parse_expr_intrinsic(inst, fun, crate::IntrinsicFunction::IsNan)?;
+                }
+                Op::IsInf => {
+                    self.parse_expr_intrinsic(inst, fun, crate::IntrinsicFunction::IsInf)?;
+                }
+                Op::IsFinite => {
+                    self.parse_expr_intrinsic(inst, fun, crate::IntrinsicFunction::IsFinite)?;
+                }
+                Op::IsNormal => {
+                    self.parse_expr_intrinsic(inst, fun, crate::IntrinsicFunction::IsNormal)?;
+                }
+                Op::ConvertFToU |
+                Op::ConvertFToS |
+                Op::ConvertSToF |
+                Op::ConvertUToF => {
+                    self.parse_expr_as(inst, fun, type_arena, true)?;
+                }
+                Op::Bitcast => {
+                    self.parse_expr_as(inst, fun, type_arena, false)?;
+                }
+                Op::VectorShuffle => {
+                    inst.expect_at_least(5)?;
+                    let result_type_id = self.next()?;
+                    let result_id = self.next()?;
+                    let v1_id = self.next()?;
+                    let v2_id = self.next()?;
+                    let v1_lexp = self.lookup_expression.lookup(v1_id)?.clone();
+                    let v1_type = self.lookup_type.lookup(v1_lexp.type_id)?;
+                    let v1_size = match type_arena[v1_type.handle].inner {
+                        crate::TypeInner::Vector { size, .. } => size as spirv::Word,
+                        _ => return Err(Error::InvalidInnerType(v1_lexp.type_id)),
+                    };
+                    let count = spirv::Word::from(inst.wc) - 5;
+                    let size = map_vector_size(count)?;
+                    // only shuffles within a single vector are swizzles
+                    let mut pattern = [crate::SwizzleComponent::X; 4];
+                    for component in pattern[.. count as usize].iter_mut() {
+                        let index = self.next()?;
+                        let local_index = if v1_id == v2_id && index >= v1_size {
+                            index - v1_size
+                        } else {
+                            index
+                        };
+                        if local_index >= v1_size {
+                            return Err(Error::UnsupportedVectorShuffle(index));
+                        }
+                        *component = map_swizzle_component(local_index)
+                            .ok_or(Error::UnsupportedVectorShuffle(index))?;
+                    }
+                    let expr = crate::Expression::Swizzle {
+                        size,
+                        vector: v1_lexp.handle,
+                        pattern,
+                    };
+                    self.lookup_expression.insert(result_id, LookupExpression {
+                        handle: fun.expressions.append(expr),
+                        type_id: result_type_id,
+                    });
+                }
+                Op::Undef => {
+                    inst.expect(3)?;
+                    let result_type_id = self.next()?;
+                    let result_id = self.next()?;
+                    let ty = self.lookup_type.lookup(result_type_id)?.handle;
+                    self.lookup_expression.insert(result_id, LookupExpression {
+                        handle: fun.expressions.append(crate::Expression::Undef(ty)),
+                        type_id: result_type_id,
+                    });
+                }
+                Op::Select => {
+                    inst.expect(6)?;
+                    let result_type_id = self.next()?;
+                    let result_id = self.next()?;
+                    let condition_id = self.next()?;
+                    let accept_id = self.next()?;
+                    let reject_id = self.next()?;
+                    let expr = crate::Expression::Select {
+                        condition: self.lookup_expression.lookup(condition_id)?.handle,
+                        accept: self.lookup_expression.lookup(accept_id)?.handle,
+                        reject: self.lookup_expression.lookup(reject_id)?.handle,
+                    };
+                    self.lookup_expression.insert(result_id, LookupExpression {
+                        handle: fun.expressions.append(expr),
+                        type_id: result_type_id,
+                    });
                 }
                 Op::VectorTimesScalar => {
                     inst.expect(5)?;
@@ -635,57 +1278,409 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                 }
                 _ => return Err(Error::UnsupportedInstruction(self.state, inst.op)),
             }
-        }
-        Ok(())
+            if let Some(ref span) = self.current_span {
+                for (handle, _) in fun.expressions.iter().skip(expression_count) {
+                    fun.expression_spans.insert(handle, span.clone());
+                }
+            }
+        };
+        Ok(BasicBlock {
+            statements,
+            merge,
+            terminator,
+        })
     }
 
-    fn make_expression_storage(&mut self) -> Arena<crate::Expression> {
-        let mut expressions = Arena::new();
-        assert!(self.lookup_expression.is_empty());
-        // register global variables
-        for (&id, var) in self.lookup_variable.iter() {
-            self.lookup_expression.insert(id, LookupExpression {
-                type_id: var.type_id,
-                handle: expressions.append(crate::Expression::GlobalVariable(var.handle)),
-            });
-        }
-        // register constants
-        for (&id, con) in self.lookup_constant.iter() {
-            self.lookup_expression.insert(id, LookupExpression {
-                type_id: con.type_id,
-                handle: expressions.append(crate::Expression::Constant(con.handle)),
-            });
+    /// Build the structured statements starting at the block `block_id`,
+    /// up to (but not including) any of the blocks in `stop_ids`.
+    /// Branches to the blocks in `exits` become `Break` and `Continue`.
+    ///
+    /// Returns the statements, and the ID of the stop block that was reached,
+    /// if the control didn't leave the construct. Fails if the constructs are
+    /// nested deeper than `depth_left`.
+    fn flow_block(
+        blocks: &mut FastHashMap<spirv::Word, BasicBlock>,
+        mut block_id: spirv::Word,
+        stop_ids: &[spirv::Word],
+        exits: FlowExits,
+        depth_left: usize,
+    ) -> Result<(crate::Block, Option<spirv::Word>), Error> {
+        let depth_left = depth_left.checked_sub(1).ok_or(Error::LimitExceeded)?;
+        let mut result = Vec::new();
+        loop {
+            if stop_ids.contains(&block_id) {
+                return Ok((result, Some(block_id)));
+            }
+            if let Some(statement) = exits.statement(block_id) {
+                result.push(statement);
+                return Ok((result, None));
+            }
+            let block = blocks.remove(&block_id).ok_or(Error::InvalidId(block_id))?;
+            if let Some(MergeInstruction::Loop { merge_id, continue_id }) = block.merge {
+                // the header is executed on every iteration, so it's a part of the body
+                let mut body = block.statements;
+                let (start_id, exit) = match block.terminator {
+                    Terminator::Branch { target_id } => (target_id, None),
+                    Terminator::BranchConditional { condition, true_id, false_id } if false_id == merge_id => {
+                        (true_id, Some(crate::Statement::If {
+                            condition,
+                            accept: Vec::new(),
+                            reject: vec![crate::Statement::Break],
+                        }))
+                    }
+                    Terminator::BranchConditional { condition, true_id, false_id } if true_id == merge_id => {
+                        (false_id, Some(crate::Statement::If {
+                            condition,
+                            accept: vec![crate::Statement::Break],
+                            reject: Vec::new(),
+                        }))
+                    }
+                    _ => return Err(Error::UnsupportedControlFlow(block_id)),
+                };
+                body.extend(exit);
+                // the body ends at the continue target, or at a back-edge
+                let stop_ids = [continue_id, merge_id, block_id];
+                let body_exits = FlowExits {
+                    break_id: Some(merge_id),
+                    continue_id: Some(continue_id),
+                };
+                let (rest, stop_id) = Self::flow_block(blocks, start_id, &stop_ids, body_exits, depth_left)?;
+                body.extend(rest);
+                if stop_id == Some(merge_id) {
+                    body.push(crate::Statement::Break);
+                }
+                let continuing = if continue_id == block_id {
+                    Vec::new()
+                } else {
+                    // the back-edge may be conditional, leaving the loop otherwise
+                    let continuing_exits = FlowExits {
+                        break_id: Some(merge_id),
+                        continue_id: None,
+                    };
+                    Self::flow_block(blocks, continue_id, &[block_id], continuing_exits, depth_left)?.0
+                };
+                result.push(crate::Statement::Loop { body, continuing });
+                block_id = merge_id;
+                continue;
+            }
+            result.extend(block.statements);
+            match (block.merge, block.terminator) {
+                (None, Terminator::Return { value }) => {
+                    result.push(crate::Statement::Return { value });
+                    break;
+                }
+                (None, Terminator::Kill) => {
+                    result.push(crate::Statement::Kill);
+                    break;
+                }
+                (None, Terminator::Unreachable) => break,
+                (None, Terminator::Branch { target_id }) => {
+                    block_id = target_id;
+                }
+                // a conditional `break` or `continue` doesn't need a selection merge
+                (None, Terminator::BranchConditional { condition, true_id, false_id }) => {
+                    let (accept, reject, next_id) = match (exits.statement(true_id), exits.statement(false_id)) {
+                        (Some(statement), _) => (vec![statement], Vec::new(), false_id),
+                        (None, Some(statement)) => (Vec::new(), vec![statement], true_id),
+                        (None, None) => return Err(Error::UnsupportedControlFlow(block_id)),
+                    };
+                    result.push(crate::Statement::If { condition, accept, reject });
+                    block_id = next_id;
+                }
+                (
+                    Some(MergeInstruction::Selection { merge_id }),
+                    Terminator::BranchConditional { condition, true_id, false_id },
+                ) => {
+                    let (accept, _) = Self::flow_block(blocks, true_id, &[merge_id], exits, depth_left)?;
+                    let (reject, _) = Self::flow_block(blocks, false_id, &[merge_id], exits, depth_left)?;
+                    result.push(crate::Statement::If { condition, accept, reject });
+                    block_id = merge_id;
+                }
+                (
+                    Some(MergeInstruction::Selection { merge_id }),
+                    Terminator::Switch { selector, default_id, targets },
+                ) => {
+                    let case_exits = FlowExits {
+                        break_id: Some(merge_id),
+                        ..exits
+                    };
+                    let target_ids = targets.iter().map(|&(_, target_id)| target_id).collect::<Vec<_>>();
+                    // literals branching to the default block are left to `default`,
+                    // and the rest are grouped by their target block
+                    let mut groups = Vec::<(spirv::Word, Vec<i32>)>::new();
+                    for &(literal, target_id) in targets.iter().filter(|&&(_, id)| id != default_id) {
+                        match groups.iter_mut().find(|&&mut (id, _)| id == target_id) {
+                            Some(&mut (_, ref mut literals)) => literals.push(literal),
+                            None => groups.push((target_id, vec![literal])),
+                        }
+                    }
+                    // the cases are written in the ascending order of their literals,
+                    // so that's the order in which a case falls through into the next one
+                    let mut sorted_targets = targets
+                        .iter()
+                        .cloned()
+                        .filter(|&(_, id)| id != default_id)
+                        .collect::<Vec<_>>();
+                    sorted_targets.sort_by_key(|&(literal, _)| literal);
+                    let next_target_id = |literal: i32| {
+                        sorted_targets
+                            .iter()
+                            .find(|&&(other, _)| other > literal)
+                            .map_or(default_id, |&(_, id)| id)
+                    };
+                    let mut cases = FastHashMap::default();
+                    for (target_id, literals) in groups {
+                        let (body, fall_through_id) = if target_id == merge_id {
+                            (vec![crate::Statement::Break], None)
+                        } else {
+                            let mut stop_ids = vec![merge_id, default_id];
+                            stop_ids.extend(target_ids.iter().cloned().filter(|&id| id != target_id));
+                            match Self::flow_block(blocks, target_id, &stop_ids, case_exits, depth_left)? {
+                                (mut body, Some(id)) if id == merge_id => {
+                                    body.push(crate::Statement::Break);
+                                    (body, None)
+                                }
+                                (body, stop_id) => (body, stop_id),
+                            }
+                        };
+                        for &literal in literals.iter() {
+                            let next_id = next_target_id(literal);
+                            let case = if next_id == target_id {
+                                // falls through into another literal of this group
+                                (Vec::new(), Some(crate::FallThrough))
+                            } else {
+                                match fall_through_id {
+                                    Some(id) if id != next_id => {
+                                        return Err(Error::UnsupportedControlFlow(target_id));
+                                    }
+                                    Some(_) => (body.clone(), Some(crate::FallThrough)),
+                                    None => (body.clone(), None),
+                                }
+                            };
+                            cases.insert(literal, case);
+                        }
+                    }
+                    let mut stop_ids = vec![merge_id];
+                    stop_ids.extend(target_ids.iter().cloned().filter(|&id| id != default_id));
+                    let default = match Self::flow_block(blocks, default_id, &stop_ids, case_exits, depth_left)? {
+                        (body, None) => body,
+                        (body, Some(id)) if id == merge_id => body,
+                        (_, Some(_)) => return Err(Error::UnsupportedControlFlow(default_id)),
+                    };
+                    result.push(crate::Statement::Switch { selector, cases, default });
+                    block_id = merge_id;
+                }
+                _ => return Err(Error::UnsupportedControlFlow(block_id)),
+            }
         }
-        // done
-        expressions
+        Ok((result, None))
     }
 
-    fn switch(&mut self, state: ModuleState, op: spirv::Op) -> Result<(), Error> {
-        if state < self.state {
-            Err(Error::UnsupportedInstruction(self.state, op))
-        } else {
-            self.state = state;
-            Ok(())
-        }
+    fn parse_expr_unary_op(
+        &mut self,
+        inst: Instruction,
+        fun: &mut crate::Function,
+        op: crate::UnaryOperator,
+    ) -> Result<(), Error> {
+        inst.expect(4)?;
+        let result_type_id = self.next()?;
+        let result_id = self.next()?;
+        let p_id = self.next()?;
+        let p_lexp = self.lookup_expression.lookup(p_id)?;
+        let expr = crate::Expression::Unary {
+            op,
+            expr: p_lexp.handle,
+        };
+        self.lookup_expression.insert(result_id, LookupExpression {
+            handle: fun.expressions.append(expr),
+            type_id: result_type_id,
+        });
+        Ok(())
     }
 
-    pub fn parse(&mut self) -> Result<crate::Module, Error> {
-        let mut module = crate::Module::from_header({
-            if self.next()? != spirv::MAGIC_NUMBER {
-                return Err(Error::InvalidHeader);
-            }
-            let version_raw = self.next()?.to_le_bytes();
-            let generator = self.next()?;
-            let _bound = self.next()?;
-            let _schema = self.next()?;
-            crate::Header {
-                version: (version_raw[2], version_raw[1], version_raw[0]),
-                generator,
-            }
+    fn parse_expr_binary_op(
+        &mut self,
+        inst: Instruction,
+        fun: &mut crate::Function,
+        op: crate::BinaryOperator,
+    ) -> Result<(), Error> {
+        inst.expect(5)?;
+        let result_type_id = self.next()?;
+        let result_id = self.next()?;
+        let p1_id = self.next()?;
+        let p2_id = self.next()?;
+        let p1_lexp = self.lookup_expression.lookup(p1_id)?;
+        let p2_lexp = self.lookup_expression.lookup(p2_id)?;
+        let expr = crate::Expression::Binary {
+            op,
+            left: p1_lexp.handle,
+            right: p2_lexp.handle,
+        };
+        self.lookup_expression.insert(result_id, LookupExpression {
+            handle: fun.expressions.append(expr),
+            type_id: result_type_id,
+        });
+        Ok(())
+    }
+
+    fn parse_expr_derivative(
+        &mut self,
+        inst: Instruction,
+        fun: &mut crate::Function,
+        axis: crate::DerivativeAxis,
+    ) -> Result<(), Error> {
+        inst.expect(4)?;
+        let result_type_id = self.next()?;
+        let result_id = self.next()?;
+        let p_id = self.next()?;
+        let p_lexp = self.lookup_expression.lookup(p_id)?;
+        let expr = crate::Expression::Derivative {
+            axis,
+            expr: p_lexp.handle,
+        };
+        self.lookup_expression.insert(result_id, LookupExpression {
+            handle: fun.expressions.append(expr),
+            type_id: result_type_id,
+        });
+        Ok(())
+    }
+
+    fn parse_expr_intrinsic(
+        &mut self,
+        inst: Instruction,
+        fun: &mut crate::Function,
+        intrinsic: crate::IntrinsicFunction,
+    ) -> Result<(), Error> {
+        inst.expect(4)?;
+        let result_type_id = self.next()?;
+        let result_id = self.next()?;
+        let arg_id = self.next()?;
+        let arg_lexp = self.lookup_expression.lookup(arg_id)?;
+        let expr = crate::Expression::Intrinsic {
+            fun: intrinsic,
+            argument: arg_lexp.handle,
+        };
+        self.lookup_expression.insert(result_id, LookupExpression {
+            handle: fun.expressions.append(expr),
+            type_id: result_type_id,
+        });
+        Ok(())
+    }
+
+    fn parse_expr_as(
+        &mut self,
+        inst: Instruction,
+        fun: &mut crate::Function,
+        type_arena: &Arena<crate::Type>,
+        convert: bool,
+    ) -> Result<(), Error> {
+        inst.expect(4)?;
+        let result_type_id = self.next()?;
+        let result_id = self.next()?;
+        let value_id = self.next()?;
+        let value_lexp = self.lookup_expression.lookup(value_id)?;
+        let result_type_lookup = self.lookup_type.lookup(result_type_id)?;
+        let kind = match type_arena[result_type_lookup.handle].inner {
+            crate::TypeInner::Scalar { kind, .. } |
+            crate::TypeInner::Vector { kind, .. } => kind,
+            _ => return Err(Error::UnsupportedType(result_type_lookup.handle)),
+        };
+        let expr = crate::Expression::As {
+            expr: value_lexp.handle,
+            kind,
+            convert,
+        };
+        self.lookup_expression.insert(result_id, LookupExpression {
+            handle: fun.expressions.append(expr),
+            type_id: result_type_id,
         });
+        Ok(())
+    }
+
+    fn make_expression_storage(&mut self) -> Arena<crate::Expression> {
+        let mut expressions = Arena::new();
+        assert!(self.lookup_expression.is_empty());
+        // register global variables
+        for (&id, var) in self.lookup_variable.iter() {
+            self.lookup_expression.insert(id, LookupExpression {
+                type_id: var.type_id,
+                handle: expressions.append(crate::Expression::GlobalVariable(var.handle)),
+            });
+        }
+        // register constants
+        for (&id, con) in self.lookup_constant.iter() {
+            self.lookup_expression.insert(id, LookupExpression {
+                type_id: con.type_id,
+                handle: expressions.append(crate::Expression::Constant(con.handle)),
+            });
+        }
+        // register undefined values
+        for (&id, undef) in self.lookup_undef.iter() {
+            self.lookup_expression.insert(id, LookupExpression {
+                type_id: undef.type_id,
+                handle: expressions.append(crate::Expression::Undef(undef.ty)),
+            });
+        }
+        // done
+        expressions
+    }
+
+    fn switch(&mut self, state: ModuleState, op: spirv::Op) -> Result<(), Error> {
+        if state < self.state {
+            Err(Error::UnsupportedInstruction(self.state, op))
+        } else {
+            self.state = state;
+            Ok(())
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<crate::Module, ParseError> {
+        self.parse_module().map_err(|error| ParseError {
+            error,
+            offset: self.inst_offset,
+        })
+    }
+
+    fn parse_header(&mut self) -> Result<crate::Header, Error> {
+        match self.next()? {
+            spirv::MAGIC_NUMBER => {}
+            // the module was produced with the opposite endianness
+            magic if magic == spirv::MAGIC_NUMBER.swap_bytes() => {
+                self.swap_bytes = true;
+            }
+            _ => return Err(Error::InvalidHeader),
+        }
+        let version_raw = self.next()?.to_le_bytes();
+        let generator = self.next()?;
+        let bound = self.next()?;
+        if bound > self.options.max_bound {
+            return Err(Error::LimitExceeded);
+        }
+        let _schema = self.next()?;
+        let (major, minor) = (version_raw[2], version_raw[1]);
+        if major != 1 || minor > 5 {
+            return Err(Error::UnsupportedVersion(major, minor));
+        }
+        // most of the IDs end up being expressions
+        self.lookup_expression.reserve(bound.min(self.options.max_reserve) as usize);
+        Ok(crate::Header {
+            version: (major, minor, version_raw[0]),
+            generator,
+        })
+    }
+
+    fn parse_module(&mut self) -> Result<crate::Module, Error> {
+        let header = self.parse_header()?;
+        let mut module = crate::Module::new(header);
         let mut entry_points = Vec::new();
 
-        while let Ok(inst) = self.next_inst() {
+        loop {
+            let inst = match self.next_inst() {
+                Ok(inst) => inst,
+                // the data is allowed to end between instructions
+                Err(Error::IncompleteData) if self.offset == self.inst_offset => break,
+                Err(e) => return Err(e),
+            };
             use spirv::Op;
             log::debug!("\t{:?} [{}]", inst.op, inst.wc);
             match inst.op {
@@ -694,14 +1689,16 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                 Op::ExtInstImport => self.parse_ext_inst_import(inst),
                 Op::MemoryModel => self.parse_memory_model(inst),
                 Op::EntryPoint => self.parse_entry_point(inst, &mut entry_points),
-                Op::ExecutionMode => self.parse_execution_mode(inst),
+                Op::ExecutionMode => self.parse_execution_mode(inst, &mut entry_points),
                 Op::Source => self.parse_source(inst),
                 Op::SourceExtension => self.parse_source_extension(inst),
+                Op::String => self.parse_string(inst),
                 Op::Name => self.parse_name(inst),
                 Op::MemberName => self.parse_member_name(inst),
                 Op::Decorate => self.parse_decorate(inst),
                 Op::MemberDecorate => self.parse_member_decorate(inst),
                 Op::TypeVoid => self.parse_type_void(inst),
+                Op::TypeBool => self.parse_type_bool(inst, &mut module),
                 Op::TypeInt => self.parse_type_int(inst, &mut module),
                 Op::TypeFloat => self.parse_type_float(inst, &mut module),
                 Op::TypeVector => self.parse_type_vector(inst, &mut module),
@@ -715,9 +1712,15 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                 Op::TypeSampledImage => self.parse_type_sampled_image(inst),
                 Op::TypeSampler => self.parse_type_sampler(inst, &mut module),
                 Op::Constant | Op::SpecConstant => self.parse_constant(inst, &mut module),
-                Op::ConstantComposite => self.parse_composite_constant(inst, &mut module),
+                Op::ConstantComposite | Op::SpecConstantComposite => self.parse_composite_constant(inst, &mut module),
+                Op::ConstantTrue | Op::SpecConstantTrue => self.parse_bool_constant(inst, true, &mut module),
+                Op::ConstantFalse | Op::SpecConstantFalse => self.parse_bool_constant(inst, false, &mut module),
                 Op::Variable => self.parse_variable(inst, &mut module),
+                Op::Undef => self.parse_undef(inst),
                 Op::Function => self.parse_function(inst, &mut module),
+                // only the line information inside of blocks is kept
+                Op::Line => self.parse_line(inst),
+                Op::NoLine => self.parse_no_line(inst),
                 _ => Err(Error::UnsupportedInstruction(self.state, inst.op)), //TODO
             }?;
         }
@@ -731,12 +1734,17 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             self.future_member_decor.clear();
         }
 
+        if let Some(&id) = self.pending_functions.iter().next() {
+            return Err(Error::InvalidId(id));
+        }
+
         module.entry_points.reserve(entry_points.len());
         for raw in entry_points {
             module.entry_points.push(crate::EntryPoint {
-                exec_model: raw.exec_model,
+                stage: raw.stage,
                 name: raw.name,
                 function: *self.lookup_function.lookup(raw.function_id)?,
+                workgroup_size: raw.workgroup_size,
             });
         }
 
@@ -771,7 +1779,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
     fn parse_ext_inst_import(&mut self, inst: Instruction) -> Result<(), Error> {
         self.switch(ModuleState::Extension, inst.op)?;
         inst.expect_at_least(3)?;
-        let _result = self.next()?;
+        let result_id = self.next()?;
         let (name, left) = self.next_string(inst.wc - 2)?;
         if left != 0 {
             return Err(Error::InvalidOperand);
@@ -779,6 +1787,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         if !SUPPORTED_EXT_SETS.contains(&name.as_str()) {
             return Err(Error::UnsupportedExtSet(name.to_owned()));
         }
+        self.ext_glsl_id = Some(result_id);
         Ok(())
     }
 
@@ -797,31 +1806,41 @@ impl<I: Iterator<Item = u32>> Parser<I> {
     ) -> Result<(), Error> {
         self.switch(ModuleState::EntryPoint, inst.op)?;
         inst.expect_at_least(4)?;
-        let exec_model = self.next()?;
-        let exec_model = spirv::ExecutionModel::from_u32(exec_model)
-            .ok_or(Error::UnsupportedExecutionModel(exec_model))?;
+        let stage = map_execution_model(self.next()?)?;
         let function_id = self.next()?;
         let (name, left) = self.next_string(inst.wc - 3)?;
         let ep = EntryPoint {
-            exec_model,
+            stage,
             name: name.to_owned(),
             function_id,
-            variable_ids: self.data
-                .by_ref()
-                .take(left as usize)
-                .collect(),
+            workgroup_size: [0; 3],
+            variable_ids: (0 .. left)
+                .map(|_| self.next())
+                .collect::<Result<_, _>>()?,
         };
         entry_points.push(ep);
         Ok(())
     }
 
-    fn parse_execution_mode(&mut self, inst: Instruction) -> Result<(), Error> {
+    fn parse_execution_mode(
+        &mut self,
+        inst: Instruction,
+        entry_points: &mut [EntryPoint],
+    ) -> Result<(), Error> {
         self.switch(ModuleState::ExecutionMode, inst.op)?;
         inst.expect_at_least(3)?;
-        let _ep_id = self.next()?;
-        let _mode = self.next()?;
-        for _ in 3..inst.wc {
-            let _ = self.next()?; //TODO
+        let ep_id = self.next()?;
+        let mode = self.next()?;
+        if mode == spirv::ExecutionMode::LocalSize as u32 {
+            inst.expect(6)?;
+            let size = [self.next()?, self.next()?, self.next()?];
+            for ep in entry_points.iter_mut().filter(|ep| ep.function_id == ep_id) {
+                ep.workgroup_size = size;
+            }
+        } else {
+            for _ in 3..inst.wc {
+                let _ = self.next()?; //TODO
+            }
         }
         Ok(())
     }
@@ -841,6 +1860,15 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         Ok(())
     }
 
+    fn parse_string(&mut self, inst: Instruction) -> Result<(), Error> {
+        self.switch(ModuleState::Source, inst.op)?;
+        inst.expect_at_least(3)?;
+        let id = self.next()?;
+        let (string, _) = self.next_string(inst.wc - 2)?;
+        self.lookup_string.insert(id, string);
+        Ok(())
+    }
+
     fn parse_name(&mut self, inst: Instruction) -> Result<(), Error> {
         self.switch(ModuleState::Name, inst.op)?;
         inst.expect_at_least(3)?;
@@ -905,6 +1933,30 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         Ok(())
     }
 
+    fn parse_type_bool(
+        &mut self,
+        inst: Instruction,
+        module: &mut crate::Module,
+    ) -> Result<(), Error> {
+        self.switch(ModuleState::Type, inst.op)?;
+        inst.expect(2)?;
+        let id = self.next()?;
+        let inner = crate::TypeInner::Scalar {
+            kind: crate::ScalarKind::Bool,
+            width: 1,
+        };
+        self.lookup_type.insert(id, LookupType {
+            handle: module.types.append(crate::Type {
+                name: self.future_decor
+                    .remove(&id)
+                    .and_then(|dec| dec.name),
+                inner,
+            }),
+            base_id: None,
+        });
+        Ok(())
+    }
+
     fn parse_type_int(
         &mut self,
         inst: Instruction,
@@ -1033,10 +2085,9 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         inst.expect_at_least(3)?;
         let id = self.next()?;
         let return_type_id = self.next()?;
-        let parameter_type_ids = self.data
-            .by_ref()
-            .take(inst.wc as usize - 3)
-            .collect();
+        let parameter_type_ids = (3 .. inst.wc)
+            .map(|_| self.next())
+            .collect::<Result<_, _>>()?;
         self.lookup_function_type.insert(id, LookupFunctionType {
             parameter_type_ids,
             return_type_id,
@@ -1079,7 +2130,16 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         inst.expect(4)?;
         let id = self.next()?;
         let type_id = self.next()?;
-        let length = self.next()?;
+        let length_id = self.next()?;
+        let length_handle = self.lookup_constant.lookup(length_id)?.handle;
+        let length = match module.constants[length_handle].inner {
+            crate::ConstantInner::Uint(value) => value as spirv::Word,
+            crate::ConstantInner::Sint(value) if value > 0 => value as spirv::Word,
+            _ => return Err(Error::InvalidArraySize(length_handle)),
+        };
+        if length > self.options.max_array_length {
+            return Err(Error::LimitExceeded);
+        }
         let inner = crate::TypeInner::Array {
             base: self.lookup_type.lookup(type_id)?.handle,
             size: crate::ArraySize::Static(length),
@@ -1102,7 +2162,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         module: &mut crate::Module,
     ) -> Result<(), Error> {
         self.switch(ModuleState::Type, inst.op)?;
-        inst.expect(4)?;
+        inst.expect(3)?;
         let id = self.next()?;
         let type_id = self.next()?;
         let inner = crate::TypeInner::Array {
@@ -1140,6 +2200,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                 name: decor.name,
                 binding,
                 ty,
+                offset: decor.offset.unwrap_or(0),
             });
         }
         let inner = crate::TypeInner::Struct {
@@ -1232,7 +2293,8 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         let decor = self.future_decor
             .remove(&id)
             .unwrap_or_default();
-        let inner = crate::TypeInner::Sampler;
+        // comparison is a property of the sampling instruction in SPIR-V
+        let inner = crate::TypeInner::Sampler { comparison: false };
         self.lookup_type.insert(id, LookupType {
             handle: module.types.append(crate::Type {
                 name: decor.name,
@@ -1243,6 +2305,16 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         Ok(())
     }
 
+    fn parse_undef(&mut self, inst: Instruction) -> Result<(), Error> {
+        self.switch(ModuleState::Type, inst.op)?;
+        inst.expect(3)?;
+        let type_id = self.next()?;
+        let id = self.next()?;
+        let ty = self.lookup_type.lookup(type_id)?.handle;
+        self.lookup_undef.insert(id, LookupUndef { ty, type_id });
+        Ok(())
+    }
+
     fn parse_constant(
         &mut self,
         inst: Instruction,
@@ -1258,7 +2330,7 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             crate::TypeInner::Scalar { kind: crate::ScalarKind::Uint, width } => {
                 let low = self.next()?;
                 let high = if width > 32 {
-                    inst.expect(4)?;
+                    inst.expect(5)?;
                     self.next()?
                 } else {
                     0
@@ -1267,22 +2339,23 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             }
             crate::TypeInner::Scalar { kind: crate::ScalarKind::Sint, width } => {
                 let low = self.next()?;
-                let high = if width < 32 {
-                    return Err(Error::InvalidTypeWidth(u32::from(width)));
-                } else if width > 32 {
-                    inst.expect(4)?;
-                    self.next()?
-                } else {
-                    !0
+                let value = match width {
+                    32 => i64::from(low as i32),
+                    64 => {
+                        inst.expect(5)?;
+                        let high = self.next()?;
+                        ((u64::from(high) << 32) | u64::from(low)) as i64
+                    }
+                    _ => return Err(Error::InvalidTypeWidth(u32::from(width))),
                 };
-                crate::ConstantInner::Sint(((u64::from(high) << 32) | u64::from(low)) as i64)
+                crate::ConstantInner::Sint(value)
             }
             crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width } => {
                 let low = self.next()?;
                 let extended = match width {
                     32 => f64::from(f32::from_bits(low)),
                     64 => {
-                        inst.expect(4)?;
+                        inst.expect(5)?;
                         let high = self.next()?;
                         f64::from_bits((u64::from(high) << 32) | u64::from(low))
                     }
@@ -1292,12 +2365,13 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             }
             _ => return Err(Error::UnsupportedType(type_lookup.handle))
         };
+        let dec = self.future_decor
+            .remove(&id)
+            .unwrap_or_default();
         self.lookup_constant.insert(id, LookupConstant {
-            handle: module.constants.append(crate::Constant {
-                name: self.future_decor
-                    .remove(&id)
-                    .and_then(|dec| dec.name),
-                specialization: None, //TODO
+            handle: module.constants.fetch_or_append(crate::Constant {
+                name: dec.name,
+                specialization: dec.spec_id,
                 inner,
                 ty,
             }),
@@ -1306,6 +2380,36 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         Ok(())
     }
 
+    fn parse_bool_constant(
+        &mut self,
+        inst: Instruction,
+        value: bool,
+        module: &mut crate::Module,
+    ) -> Result<(), Error> {
+        self.switch(ModuleState::Type, inst.op)?;
+        inst.expect(3)?;
+        let type_id = self.next()?;
+        let id = self.next()?;
+        let type_lookup = self.lookup_type.lookup(type_id)?;
+        match module.types[type_lookup.handle].inner {
+            crate::TypeInner::Scalar { kind: crate::ScalarKind::Bool, .. } => (),
+            _ => return Err(Error::UnsupportedType(type_lookup.handle)),
+        }
+        let dec = self.future_decor
+            .remove(&id)
+            .unwrap_or_default();
+        self.lookup_constant.insert(id, LookupConstant {
+            handle: module.constants.fetch_or_append(crate::Constant {
+                name: dec.name,
+                specialization: dec.spec_id,
+                inner: crate::ConstantInner::Bool(value),
+                ty: type_lookup.handle,
+            }),
+            type_id,
+        });
+        Ok(())
+    }
+
     fn parse_composite_constant(
         &mut self,
         inst: Instruction,
@@ -1327,12 +2431,13 @@ impl<I: Iterator<Item = u32>> Parser<I> {
             constituents.push(constant.handle);
         }
 
+        let dec = self.future_decor
+            .remove(&id)
+            .unwrap_or_default();
         self.lookup_constant.insert(id, LookupConstant {
-            handle: module.constants.append(crate::Constant {
-                name: self.future_decor
-                    .remove(&id)
-                    .and_then(|dec|dec.name),
-                specialization: None,
+            handle: module.constants.fetch_or_append(crate::Constant {
+                name: dec.name,
+                specialization: dec.spec_id,
                 inner: crate::ConstantInner::Composite(constituents),
                 ty
             }),
@@ -1352,17 +2457,28 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         let type_id = self.next()?;
         let id = self.next()?;
         let storage = self.next()?;
-        if inst.wc != 4 {
+        let init = if inst.wc != 4 {
             inst.expect(5)?;
-            let _init = self.next()?; //TODO
-        }
+            let init_id = self.next()?;
+            Some(self.lookup_constant.lookup(init_id)?.handle)
+        } else {
+            None
+        };
         let lookup_type = self.lookup_type.lookup(type_id)?;
+        let class = map_storage_class(storage)?;
         let dec = self.future_decor
             .remove(&id)
-            .ok_or(Error::InvalidBinding(id))?;
-        let binding = match module.types[lookup_type.handle].inner {
-            crate::TypeInner::Pointer { base, class: spirv::StorageClass::Input } |
-            crate::TypeInner::Pointer { base, class: spirv::StorageClass::Output } => {
+            .unwrap_or_default();
+        if dec.built_in.is_some() && (dec.desc_set.is_some() || dec.desc_index.is_some()) {
+            return Err(Error::ConflictingBinding(id));
+        }
+        let binding = match class {
+            crate::StorageClass::Input |
+            crate::StorageClass::Output => {
+                let base = match module.types[lookup_type.handle].inner {
+                    crate::TypeInner::Pointer { base, .. } => base,
+                    _ => lookup_type.handle,
+                };
                 match module.types[base].inner {
                     crate::TypeInner::Struct { ref members } => {
                         // we don't expect binding decoration on I/O structs,
@@ -1380,21 +2496,26 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                             .get_binding()
                             .ok_or(Error::InvalidBinding(id))?
                         )
-                   }
+                    }
                 }
             }
-            _ => {
-                Some(dec
-                    .get_binding()
-                    .ok_or(Error::InvalidBinding(id))?
-                )
-            }
+            crate::StorageClass::Uniform |
+            crate::StorageClass::Storage |
+            crate::StorageClass::Handle => match dec.get_binding() {
+                // resources need both the descriptor set and the binding index
+                Some(binding @ crate::Binding::Resource { .. }) => Some(binding),
+                _ => return Err(Error::InvalidBinding(id)),
+            },
+            _ => dec.get_binding(),
         };
         let var = crate::GlobalVariable {
             name: dec.name,
-            class: map_storage_class(storage)?,
+            class,
             binding,
+            interpolation: dec.interpolation,
+            sampling: dec.sampling,
             ty: lookup_type.handle,
+            init,
         };
         self.lookup_variable.insert(id, LookupVariable {
             handle: module.global_variables.append(var),
@@ -1403,6 +2524,32 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         Ok(())
     }
 
+    /// Returns the handle of the function `id`, reserving a place for it
+    /// in `functions` if it hasn't been seen yet.
+    fn reserve_function(
+        &mut self,
+        functions: &mut Arena<crate::Function>,
+        id: spirv::Word,
+    ) -> Handle<crate::Function> {
+        if let Some(&handle) = self.lookup_function.get(&id) {
+            return handle;
+        }
+        let handle = functions.append(crate::Function {
+            name: None,
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: Arena::new(),
+            expressions: Arena::new(),
+            expression_spans: crate::FastHashMap::default(),
+            body: Vec::new(),
+        });
+        self.lookup_function.insert(id, handle);
+        self.pending_functions.insert(id);
+        handle
+    }
+
     fn parse_function(
         &mut self,
         inst: Instruction,
@@ -1414,6 +2561,11 @@ impl<I: Iterator<Item = u32>> Parser<I> {
         let fun_id = self.next()?;
         let fun_control = self.next()?;
         let fun_type = self.next()?;
+        if self.lookup_function.contains_key(&fun_id) && !self.pending_functions.contains(&fun_id) {
+            return Err(Error::InvalidId(fun_id));
+        }
+        let handle = self.reserve_function(&mut module.functions, fun_id);
+        self.pending_functions.remove(&fun_id);
         let mut fun = {
             let ft = self.lookup_function_type.lookup(fun_type)?;
             if ft.return_type_id != result_type {
@@ -1434,15 +2586,20 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                 global_usage: Vec::new(),
                 local_variables: Arena::new(),
                 expressions: self.make_expression_storage(),
+                expression_spans: crate::FastHashMap::default(),
                 body: Vec::new(),
             }
         };
         // read parameters
-        for i in 0..fun.parameter_types.capacity() {
+        let parameter_count = fun.parameter_types.capacity();
+        while fun.parameter_types.len() < parameter_count {
+            let i = fun.parameter_types.len();
             match self.next_inst()? {
+                inst @ Instruction { op: spirv::Op::Line, .. } => self.parse_line(inst)?,
+                inst @ Instruction { op: spirv::Op::NoLine, .. } => self.parse_no_line(inst)?,
                 Instruction { op: spirv::Op::FunctionParameter, wc: 3 } => {
                     let type_id = self.next()?;
-                    let _id = self.next()?;
+                    let id = self.next()?;
                     //Note: we redo the lookup in order to work around `self` borrowing
                     if type_id != self.lookup_function_type
                         .lookup(fun_type)?
@@ -1452,40 +2609,68 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                     }
                     let ty = self.lookup_type.lookup(type_id)?.handle;
                     fun.parameter_types.push(ty);
+                    let expr = crate::Expression::FunctionParameter(i as u32);
+                    self.lookup_expression.insert(id, LookupExpression {
+                        handle: fun.expressions.append(expr),
+                        type_id,
+                    });
                 }
                 Instruction { op, .. } => return Err(Error::InvalidParameter(op)),
             }
         }
         // read body
+        let mut blocks = FastHashMap::default();
+        let mut entry_block_id = None;
         loop {
             let fun_inst = self.next_inst()?;
             log::debug!("\t\t{:?}", fun_inst.op);
             match fun_inst.op {
                 spirv::Op::Label => {
                     fun_inst.expect(2)?;
-                    let _id = self.next()?;
-                    self.next_block(&mut fun, &module.types, &module.constants)?;
+                    let block_id = self.next()?;
+                    let block = self.next_block(
+                        &mut fun,
+                        &mut module.functions,
+                        &module.types,
+                        &module.constants,
+                    )?;
+                    entry_block_id.get_or_insert(block_id);
+                    blocks.insert(block_id, block);
                 }
                 spirv::Op::FunctionEnd => {
                     fun_inst.expect(1)?;
                     break
                 }
+                spirv::Op::Line => self.parse_line(fun_inst)?,
+                spirv::Op::NoLine => self.parse_no_line(fun_inst)?,
                 _ => return Err(Error::UnsupportedInstruction(self.state, fun_inst.op))
             }
         }
+        // structurize
+        if let Some(block_id) = entry_block_id {
+            fun.body = Self::flow_block(
+                &mut blocks,
+                block_id,
+                &[],
+                FlowExits::default(),
+                self.options.max_nesting_depth,
+            )?.0;
+        }
         // done
         fun.global_usage = crate::GlobalUse::scan(&fun.expressions, &fun.body, &module.global_variables);
-        let handle = module.functions.append(fun);
-        self.lookup_function.insert(fun_id, handle);
+        module.functions[handle] = fun;
         self.lookup_expression.clear();
         self.lookup_sampled_image.clear();
         Ok(())
     }
 }
 
-pub fn parse_u8_slice(data: &[u8]) -> Result<crate::Module, Error> {
+pub fn parse_u8_slice(data: &[u8]) -> Result<crate::Module, ParseError> {
     if data.len() % 4 != 0 {
-        return Err(Error::IncompleteData);
+        return Err(ParseError {
+            error: Error::IncompleteData,
+            offset: data.len() / 4,
+        });
     }
 
     let words = data
@@ -1496,6 +2681,1747 @@ pub fn parse_u8_slice(data: &[u8]) -> Result<crate::Module, Error> {
 
 #[cfg(test)]
 mod test {
+    use spirv::Op;
+
+    fn header() -> Vec<spirv::Word> {
+        vec![spirv::MAGIC_NUMBER, 0x0001_0000, 0, 100, 0]
+    }
+
+    fn push_inst(words: &mut Vec<spirv::Word>, op: Op, operands: &[spirv::Word]) {
+        let wc = operands.len() as u32 + 1;
+        words.push((wc << 16) | op as u32);
+        words.extend_from_slice(operands);
+    }
+
+    /// Declares `%1 = OpTypeVoid` and `%2 = OpTypeFunction %1`,
+    /// for tests that need a function.
+    fn push_void_function_type(words: &mut Vec<spirv::Word>) {
+        push_inst(words, Op::TypeVoid, &[1]);
+        push_inst(words, Op::TypeFunction, &[2, 1]);
+    }
+
+    fn begin_void_function(words: &mut Vec<spirv::Word>, id: spirv::Word) {
+        push_inst(words, Op::Function, &[1, id, 0, 2]);
+        push_inst(words, Op::Label, &[id + 1]);
+    }
+
+    fn end_void_function(words: &mut Vec<spirv::Word>) {
+        push_inst(words, Op::Return, &[]);
+        push_inst(words, Op::FunctionEnd, &[]);
+    }
+
+    fn parse_words(words: Vec<spirv::Word>) -> Result<crate::Module, super::Error> {
+        super::Parser::new(words.into_iter()).parse().map_err(|e| e.error)
+    }
+
+    /// Encodes a nul-terminated string literal operand.
+    fn string_operand(value: &str) -> Vec<spirv::Word> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize((bytes.len() / 4 + 1) * 4, 0);
+        bytes
+            .chunks(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn parse_header() {
+        let mut words = header();
+        words[1] = 0x0001_0300;
+        words[2] = 0x0008_0001;
+        let module = parse_words(words).unwrap();
+        assert_eq!(module.header.version, (1, 3, 0));
+        assert_eq!(module.header.generator, 0x0008_0001);
+
+        let mut words = header();
+        words[0] = 0x0723_0204;
+        assert!(matches!(parse_words(words), Err(super::Error::InvalidHeader)));
+
+        let mut words = header();
+        words[1] = 0x0001_0600;
+        assert!(matches!(parse_words(words), Err(super::Error::UnsupportedVersion(1, 6))));
+    }
+
+    #[test]
+    fn parse_byte_swapped() {
+        let mut words = header();
+        let mut operands = vec![6];
+        operands.extend(string_operand("main"));
+        push_inst(&mut words, Op::Name, &operands);
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypePointer, &[4, spirv::StorageClass::Function as u32, 3]);
+        push_inst(&mut words, Op::Constant, &[3, 5, 1.0f32.to_bits()]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::Variable, &[4, 8, spirv::StorageClass::Function as u32]);
+        push_inst(&mut words, Op::Store, &[8, 5]);
+        end_void_function(&mut words);
+
+        let swapped = words.iter().map(|word| word.swap_bytes()).collect();
+        let module = parse_words(words).unwrap();
+        let module_swapped = parse_words(swapped).unwrap();
+        assert_eq!(format!("{:?}", module), format!("{:?}", module_swapped));
+    }
+
+    #[test]
+    fn parse_type_names() {
+        let mut words = header();
+        let mut operands = vec![1];
+        operands.extend(string_operand("scalar"));
+        push_inst(&mut words, Op::Name, &operands);
+        let mut operands = vec![2];
+        operands.extend(string_operand("Light"));
+        push_inst(&mut words, Op::Name, &operands);
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypeStruct, &[2, 1]);
+        let module = parse_words(words).unwrap();
+
+        let names = module.types
+            .iter()
+            .map(|(_, ty)| ty.name.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![Some("scalar"), Some("Light")]);
+        let (_, light) = module.types.iter().nth(1).unwrap();
+        assert!(matches!(light.inner, crate::TypeInner::Struct { .. }));
+    }
+
+    #[test]
+    fn parse_global_init() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypePointer, &[2, spirv::StorageClass::Private as u32, 1]);
+        push_inst(&mut words, Op::Constant, &[1, 3, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::Variable, &[2, 4, spirv::StorageClass::Private as u32, 3]);
+        push_inst(&mut words, Op::Variable, &[2, 5, spirv::StorageClass::Private as u32]);
+        let module = parse_words(words).unwrap();
+
+        let (constant, _) = module.constants.iter().next().unwrap();
+        let inits = module.global_variables
+            .iter()
+            .map(|(_, var)| var.init)
+            .collect::<Vec<_>>();
+        assert_eq!(inits, vec![Some(constant), None]);
+    }
+
+    #[test]
+    fn parse_storage_classes() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypePointer, &[2, spirv::StorageClass::PushConstant as u32, 1]);
+        push_inst(&mut words, Op::Variable, &[2, 3, spirv::StorageClass::PushConstant as u32]);
+        let module = parse_words(words).unwrap();
+        let (_, var) = module.global_variables.iter().next().unwrap();
+        assert_eq!(var.class, crate::StorageClass::PushConstant);
+        assert_eq!(var.binding, None);
+
+        for &class in &[spirv::StorageClass::Image, spirv::StorageClass::AtomicCounter] {
+            let mut words = header();
+            push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+            push_inst(&mut words, Op::TypePointer, &[2, class as u32, 1]);
+            let result = parse_words(words);
+            assert!(matches!(result, Err(super::Error::UnsupportedStorageClass(c)) if c == class as u32));
+        }
+    }
+
+    #[test]
+    fn parse_type_pointer() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypePointer, &[2, spirv::StorageClass::Function as u32, 1]);
+        let module = parse_words(words).unwrap();
+
+        let (float_ty, _) = module.types.iter().next().unwrap();
+        let (_, pointer) = module.types.iter().nth(1).unwrap();
+        match pointer.inner {
+            crate::TypeInner::Pointer { base, class } => {
+                assert_eq!(base, float_ty);
+                assert_eq!(class, crate::StorageClass::Function);
+            }
+            ref other => panic!("Unexpected type {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_limits() {
+        use super::{Error, ParseError, ParseOptions};
+
+        let mut words = header();
+        words[3] = !0;
+        let result = super::Parser::new(words.into_iter()).parse();
+        assert!(matches!(result, Err(ParseError { error: Error::LimitExceeded, offset: 0 })));
+
+        let mut words = header();
+        push_void_function_type(&mut words);
+        let options = ParseOptions { max_instructions: 1, ..ParseOptions::default() };
+        let result = super::Parser::with_options(words.into_iter(), options).parse();
+        assert!(matches!(result, Err(ParseError { error: Error::LimitExceeded, offset: 7 })));
+
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeBool, &[3]);
+        push_inst(&mut words, Op::ConstantTrue, &[3, 4]);
+        begin_void_function(&mut words, 5);
+        push_inst(&mut words, Op::SelectionMerge, &[8, 0]);
+        push_inst(&mut words, Op::BranchConditional, &[4, 7, 8]);
+        push_inst(&mut words, Op::Label, &[7]);
+        push_inst(&mut words, Op::Branch, &[8]);
+        push_inst(&mut words, Op::Label, &[8]);
+        end_void_function(&mut words);
+        let options = ParseOptions { max_nesting_depth: 2, ..ParseOptions::default() };
+        assert!(super::Parser::with_options(words.clone().into_iter(), options).parse().is_ok());
+        let options = ParseOptions { max_nesting_depth: 1, ..ParseOptions::default() };
+        let result = super::Parser::with_options(words.into_iter(), options).parse();
+        assert!(matches!(result, Err(ParseError { error: Error::LimitExceeded, .. })));
+
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypeInt, &[2, 32, 0]);
+        push_inst(&mut words, Op::Constant, &[2, 3, 16]);
+        push_inst(&mut words, Op::TypeArray, &[4, 1, 3]);
+        let options = ParseOptions { max_array_length: 16, ..ParseOptions::default() };
+        assert!(super::Parser::with_options(words.clone().into_iter(), options).parse().is_ok());
+        let options = ParseOptions { max_array_length: 15, ..ParseOptions::default() };
+        let result = super::Parser::with_options(words.into_iter(), options).parse();
+        assert!(matches!(result, Err(ParseError { error: Error::LimitExceeded, offset: 16 })));
+    }
+
+    #[test]
+    fn parse_reserve() {
+        let max_bound = super::ParseOptions::default().max_bound;
+
+        let mut words = header();
+        words[3] = max_bound;
+        let mut parser = super::Parser::new(words.into_iter());
+        assert!(parser.parse_header().is_ok());
+        assert!(parser.lookup_expression.capacity() < 1 << 16);
+
+        let mut words = header();
+        words[1] = 0x0001_0600;
+        words[3] = max_bound;
+        let mut parser = super::Parser::new(words.into_iter());
+        assert!(matches!(parser.parse_header(), Err(super::Error::UnsupportedVersion(1, 6))));
+        assert_eq!(parser.lookup_expression.capacity(), 0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        use super::{Error, ParseError};
+        fn parse(words: Vec<spirv::Word>) -> Result<crate::Module, ParseError> {
+            super::Parser::new(words.into_iter()).parse()
+        }
+
+        let mut words = header();
+        words[0] = 0xDEAD_BEEF;
+        assert!(matches!(parse(words), Err(ParseError { error: Error::InvalidHeader, offset: 0 })));
+
+        let mut words = header();
+        words[1] = 0x0002_0000;
+        assert!(matches!(parse(words), Err(ParseError {
+            error: Error::UnsupportedVersion(2, 0),
+            offset: 0,
+        })));
+
+        let words = header()[.. 3].to_vec();
+        assert!(matches!(parse(words), Err(ParseError { error: Error::IncompleteData, offset: 0 })));
+
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        words.pop();
+        assert!(matches!(parse(words), Err(ParseError { error: Error::IncompleteData, offset: 5 })));
+
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        words.push(0x0001_FFFF);
+        assert!(matches!(parse(words), Err(ParseError {
+            error: Error::UnknownInstruction(0xFFFF),
+            offset: 8,
+        })));
+
+        let mut words = header();
+        push_inst(&mut words, Op::Capability, &[spirv::Capability::Kernel as u32]);
+        assert!(matches!(parse(words), Err(ParseError {
+            error: Error::UnsupportedCapability(spirv::Capability::Kernel),
+            offset: 5,
+        })));
+
+        let bytes = header()
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .take(18)
+            .collect::<Vec<_>>();
+        assert!(matches!(super::parse_u8_slice(&bytes), Err(ParseError {
+            error: Error::IncompleteData,
+            ..
+        })));
+    }
+
+    #[test]
+    fn parse_capabilities() {
+        let mut words = header();
+        push_inst(&mut words, Op::Capability, &[spirv::Capability::Shader as u32]);
+        push_inst(&mut words, Op::Capability, &[spirv::Capability::Matrix as u32]);
+        assert!(parse_words(words.clone()).is_ok());
+
+        push_inst(&mut words, Op::Capability, &[spirv::Capability::Geometry as u32]);
+        assert!(matches!(
+            super::Parser::new(words.into_iter()).parse(),
+            Err(super::ParseError {
+                error: super::Error::UnsupportedCapability(spirv::Capability::Geometry),
+                offset: 9,
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_function_call() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeFunction, &[4, 3, 3]);
+        push_inst(&mut words, Op::Constant, &[3, 5, 1.0f32.to_bits()]);
+        // the caller comes first, so the call refers forward
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::FunctionCall, &[3, 8, 10, 5]);
+        end_void_function(&mut words);
+        push_inst(&mut words, Op::Function, &[3, 10, 0, 4]);
+        push_inst(&mut words, Op::FunctionParameter, &[3, 11]);
+        push_inst(&mut words, Op::Label, &[12]);
+        push_inst(&mut words, Op::ReturnValue, &[11]);
+        push_inst(&mut words, Op::FunctionEnd, &[]);
+
+        let module = parse_words(words).unwrap();
+        let (_, caller) = module.functions.iter().next().unwrap();
+        let (callee_handle, callee) = module.functions.iter().nth(1).unwrap();
+        assert_eq!(callee.parameter_types.len(), 1);
+        let (origin, arguments) = caller.expressions.iter().find_map(|(_, expr)| match *expr {
+            crate::Expression::Call { ref origin, ref arguments } => Some((origin, arguments)),
+            _ => None,
+        }).unwrap();
+        assert_eq!(*origin, crate::FunctionOrigin::Local(callee_handle));
+        assert_eq!(arguments.len(), 1);
+        assert!(matches!(caller.expressions[arguments[0]], crate::Expression::Constant(_)));
+    }
+
+    #[test]
+    fn parse_undefined_callee() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        begin_void_function(&mut words, 3);
+        push_inst(&mut words, Op::FunctionCall, &[1, 5, 9]);
+        end_void_function(&mut words);
+        assert!(matches!(parse_words(words), Err(super::Error::InvalidId(9))));
+
+        // a call used as a value
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        begin_void_function(&mut words, 4);
+        push_inst(&mut words, Op::FunctionCall, &[3, 6, 9]);
+        end_void_function(&mut words);
+        assert!(matches!(parse_words(words), Err(super::Error::InvalidId(9))));
+    }
+
+    #[test]
+    fn parse_statement_call() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeFunction, &[4, 1, 3, 3]);
+        push_inst(&mut words, Op::Constant, &[3, 5, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::Constant, &[3, 6, 2.0f32.to_bits()]);
+        // the caller comes first, so the callee is not known yet
+        begin_void_function(&mut words, 7);
+        push_inst(&mut words, Op::FunctionCall, &[1, 9, 10, 5, 6]);
+        end_void_function(&mut words);
+        push_inst(&mut words, Op::Function, &[1, 10, 0, 4]);
+        push_inst(&mut words, Op::FunctionParameter, &[3, 11]);
+        push_inst(&mut words, Op::FunctionParameter, &[3, 12]);
+        push_inst(&mut words, Op::Label, &[13]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let mut functions = module.functions.iter();
+        let (_, caller) = functions.next().unwrap();
+        let (callee, _) = functions.next().unwrap();
+        match caller.body[0] {
+            crate::Statement::Call { function, ref arguments } => {
+                assert_eq!(function, callee);
+                let values = arguments
+                    .iter()
+                    .map(|&h| match caller.expressions[h] {
+                        crate::Expression::Constant(c) => &module.constants[c].inner,
+                        ref other => panic!("Unexpected argument {:?}", other),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(values, vec![
+                    &crate::ConstantInner::Float(1.0),
+                    &crate::ConstantInner::Float(2.0),
+                ]);
+            }
+            ref other => panic!("Unexpected statement {:?}", other),
+        }
+        assert!(!caller.expressions
+            .iter()
+            .any(|(_, expr)| matches!(*expr, crate::Expression::Call { .. })));
+    }
+
+    #[test]
+    fn parse_image_sample() {
+        let mut words = header();
+        for &(id, binding) in &[(8, 0), (9, 1)] {
+            push_inst(&mut words, Op::Decorate, &[id, spirv::Decoration::DescriptorSet as u32, 0]);
+            push_inst(&mut words, Op::Decorate, &[id, spirv::Decoration::Binding as u32, binding]);
+        }
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeImage, &[4, 3, spirv::Dim::Dim2D as u32, 0, 0, 0, 1, 0]);
+        push_inst(&mut words, Op::TypeSampler, &[5]);
+        push_inst(&mut words, Op::TypePointer, &[6, spirv::StorageClass::UniformConstant as u32, 4]);
+        push_inst(&mut words, Op::TypePointer, &[7, spirv::StorageClass::UniformConstant as u32, 5]);
+        push_inst(&mut words, Op::Variable, &[6, 8, spirv::StorageClass::UniformConstant as u32]);
+        push_inst(&mut words, Op::Variable, &[7, 9, spirv::StorageClass::UniformConstant as u32]);
+        push_inst(&mut words, Op::TypeSampledImage, &[10, 4]);
+        push_inst(&mut words, Op::TypeVector, &[11, 3, 2]);
+        push_inst(&mut words, Op::TypeVector, &[12, 3, 4]);
+        push_inst(&mut words, Op::Constant, &[3, 13, 0.5f32.to_bits()]);
+        push_inst(&mut words, Op::ConstantComposite, &[11, 14, 13, 13]);
+        begin_void_function(&mut words, 15);
+        push_inst(&mut words, Op::Load, &[4, 17, 8]);
+        push_inst(&mut words, Op::Load, &[5, 18, 9]);
+        push_inst(&mut words, Op::SampledImage, &[10, 19, 17, 18]);
+        push_inst(&mut words, Op::ImageSampleImplicitLod, &[12, 20, 19, 14]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words).unwrap();
+        assert!(module.types.iter().any(|(_, ty)| match ty.inner {
+            crate::TypeInner::Image { dim, flags, .. } => {
+                dim == spirv::Dim::Dim2D && flags == crate::ImageFlags::SAMPLED
+            }
+            _ => false,
+        }));
+        assert!(module.types.iter().any(|(_, ty)| ty.inner == crate::TypeInner::Sampler { comparison: false }));
+
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (image, sampler, coordinate) = fun.expressions.iter().find_map(|(_, expr)| match *expr {
+            crate::Expression::ImageSample { image, sampler, coordinate } => Some((image, sampler, coordinate)),
+            _ => None,
+        }).unwrap();
+        assert!(matches!(fun.expressions[image], crate::Expression::Load { .. }));
+        assert!(matches!(fun.expressions[sampler], crate::Expression::Load { .. }));
+        assert!(matches!(fun.expressions[coordinate], crate::Expression::Constant(_)));
+    }
+
+    #[test]
+    fn parse_type_matrix() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypeVector, &[2, 1, 4]);
+        push_inst(&mut words, Op::TypeMatrix, &[3, 2, 3]);
+        let module = parse_words(words).unwrap();
+        let (_, ty) = module.types.iter().last().unwrap();
+        assert_eq!(ty.inner, crate::TypeInner::Matrix {
+            columns: crate::VectorSize::Tri,
+            rows: crate::VectorSize::Quad,
+            kind: crate::ScalarKind::Float,
+            width: 32,
+        });
+    }
+
+    #[test]
+    fn parse_type_array() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypeVector, &[2, 1, 3]);
+        push_inst(&mut words, Op::TypeInt, &[3, 32, 0]);
+        push_inst(&mut words, Op::Constant, &[3, 4, 16]);
+        push_inst(&mut words, Op::TypeArray, &[5, 2, 4]);
+        let module = parse_words(words).unwrap();
+        let (vec_handle, _) = module.types.iter().nth(1).unwrap();
+        let (_, ty) = module.types.iter().last().unwrap();
+        assert_eq!(ty.inner, crate::TypeInner::Array {
+            base: vec_handle,
+            size: crate::ArraySize::Static(16),
+        });
+    }
+
+    #[test]
+    fn parse_type_runtime_array() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypeInt, &[2, 32, 0]);
+        push_inst(&mut words, Op::Constant, &[2, 3, 4]);
+        push_inst(&mut words, Op::TypeArray, &[4, 1, 3]);
+        push_inst(&mut words, Op::TypeRuntimeArray, &[5, 1]);
+        let module = parse_words(words).unwrap();
+        let sizes = module.types
+            .iter()
+            .filter_map(|(_, ty)| match ty.inner {
+                crate::TypeInner::Array { size, .. } => Some(size),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![
+            crate::ArraySize::Static(4),
+            crate::ArraySize::Dynamic,
+        ]);
+    }
+
+    fn scalar_constants() -> Vec<spirv::Word> {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeInt, &[1, 32, 1]);
+        push_inst(&mut words, Op::TypeInt, &[2, 32, 0]);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeBool, &[4]);
+        push_inst(&mut words, Op::Constant, &[1, 5, -3i32 as u32]);
+        push_inst(&mut words, Op::Constant, &[1, 6, 7]);
+        push_inst(&mut words, Op::Constant, &[2, 7, 8]);
+        push_inst(&mut words, Op::Constant, &[3, 8, 1.5f32.to_bits()]);
+        push_inst(&mut words, Op::ConstantTrue, &[4, 9]);
+        push_inst(&mut words, Op::ConstantFalse, &[4, 10]);
+        words
+    }
+
+    #[test]
+    fn parse_scalar_constants() {
+        let module = parse_words(scalar_constants()).unwrap();
+        let values = module.constants
+            .iter()
+            .map(|(_, c)| &c.inner)
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![
+            &crate::ConstantInner::Sint(-3),
+            &crate::ConstantInner::Sint(7),
+            &crate::ConstantInner::Uint(8),
+            &crate::ConstantInner::Float(1.5),
+            &crate::ConstantInner::Bool(true),
+            &crate::ConstantInner::Bool(false),
+        ]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "serialize", feature = "deserialize"))]
+    fn scalar_constants_serde_round_trip() {
+        let module = parse_words(scalar_constants()).unwrap();
+        let ser = ron::ser::to_string(&module.constants).unwrap();
+        let de: crate::arena::Arena<crate::Constant> = ron::de::from_str(&ser).unwrap();
+        assert_eq!(de.len(), module.constants.len());
+        for ((_, a), (_, b)) in module.constants.iter().zip(de.iter()) {
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn parse_composite_constant() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[1, 32]);
+        push_inst(&mut words, Op::TypeVector, &[2, 1, 2]);
+        push_inst(&mut words, Op::Constant, &[1, 3, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::Constant, &[1, 4, 0.0f32.to_bits()]);
+        push_inst(&mut words, Op::ConstantComposite, &[2, 5, 3, 4]);
+        let module = parse_words(words).unwrap();
+        let (vec_handle, _) = module.types.iter().nth(1).unwrap();
+        let handles = module.constants
+            .iter()
+            .map(|(h, _)| h)
+            .collect::<Vec<_>>();
+        let composite = &module.constants[handles[2]];
+        assert_eq!(composite.ty, vec_handle);
+        assert_eq!(composite.inner, crate::ConstantInner::Composite(vec![handles[0], handles[1]]));
+        assert_eq!(module.constants[handles[0]].inner, crate::ConstantInner::Float(1.0));
+        assert_eq!(module.constants[handles[1]].inner, crate::ConstantInner::Float(0.0));
+    }
+
+    #[test]
+    fn parse_spec_constant() {
+        let mut words = header();
+        push_inst(&mut words, Op::Decorate, &[2, spirv::Decoration::SpecId as u32, 3]);
+        push_inst(&mut words, Op::TypeInt, &[1, 32, 0]);
+        push_inst(&mut words, Op::SpecConstant, &[1, 2, 16]);
+        push_inst(&mut words, Op::Constant, &[1, 3, 4]);
+        let module = parse_words(words).unwrap();
+        let specializations = module.constants
+            .iter()
+            .map(|(_, c)| (c.specialization, &c.inner))
+            .collect::<Vec<_>>();
+        assert_eq!(specializations, vec![
+            (Some(3), &crate::ConstantInner::Uint(16)),
+            (None, &crate::ConstantInner::Uint(4)),
+        ]);
+    }
+
+    #[test]
+    fn parse_constant_expression() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::Constant, &[3, 4, 1.0f32.to_bits()]);
+        begin_void_function(&mut words, 5);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (const_handle, _) = module.constants.iter().next().unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let referenced = fun.expressions
+            .iter()
+            .filter_map(|(_, expr)| match *expr {
+                crate::Expression::Constant(handle) => Some(handle),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(referenced, vec![const_handle]);
+    }
+
+    #[test]
+    fn parse_unary_expressions() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeBool, &[4]);
+        push_inst(&mut words, Op::Constant, &[3, 5, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::ConstantTrue, &[4, 6]);
+        begin_void_function(&mut words, 7);
+        push_inst(&mut words, Op::FNegate, &[3, 9, 5]);
+        push_inst(&mut words, Op::LogicalNot, &[4, 10, 6]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let unary = fun.expressions
+            .iter()
+            .filter_map(|(_, expr)| match *expr {
+                crate::Expression::Unary { op, expr } => match fun.expressions[expr] {
+                    crate::Expression::Constant(c) => Some((op, &module.constants[c].inner)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(unary, vec![
+            (crate::UnaryOperator::Negate, &crate::ConstantInner::Float(1.0)),
+            (crate::UnaryOperator::Not, &crate::ConstantInner::Bool(true)),
+        ]);
+    }
+
+    #[test]
+    fn parse_resource_binding() {
+        let build = |decorations: &[spirv::Decoration]| {
+            let mut words = header();
+            for &decoration in decorations {
+                let value = if decoration == spirv::Decoration::Binding { 2 } else { 0 };
+                push_inst(&mut words, Op::Decorate, &[5, decoration as u32, value]);
+            }
+            push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+            push_inst(&mut words, Op::TypePointer, &[4, spirv::StorageClass::Uniform as u32, 3]);
+            push_inst(&mut words, Op::Variable, &[4, 5, spirv::StorageClass::Uniform as u32]);
+            parse_words(words)
+        };
+
+        let module = build(&[spirv::Decoration::DescriptorSet, spirv::Decoration::Binding]).unwrap();
+        let (_, var) = module.global_variables.iter().next().unwrap();
+        assert_eq!(var.class, crate::StorageClass::Uniform);
+        assert_eq!(var.binding, Some(crate::Binding::Resource { group: 0, binding: 2 }));
+
+        match build(&[spirv::Decoration::Binding]) {
+            Err(super::Error::InvalidBinding(5)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_built_in() {
+        let build = |decorations: &[(spirv::Decoration, u32)]| {
+            let mut words = header();
+            for &(decoration, value) in decorations {
+                push_inst(&mut words, Op::Decorate, &[6, decoration as u32, value]);
+            }
+            push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+            push_inst(&mut words, Op::TypeVector, &[4, 3, 4]);
+            push_inst(&mut words, Op::TypePointer, &[5, spirv::StorageClass::Output as u32, 4]);
+            push_inst(&mut words, Op::Variable, &[5, 6, spirv::StorageClass::Output as u32]);
+            parse_words(words)
+        };
+        let position = (spirv::Decoration::BuiltIn, spirv::BuiltIn::Position as u32);
+
+        let module = build(&[position]).unwrap();
+        let (_, var) = module.global_variables.iter().next().unwrap();
+        assert_eq!(var.class, crate::StorageClass::Output);
+        assert_eq!(var.binding, Some(crate::Binding::BuiltIn(spirv::BuiltIn::Position)));
+
+        match build(&[position, (spirv::Decoration::DescriptorSet, 0)]) {
+            Err(super::Error::ConflictingBinding(6)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_interpolation() {
+        let mut words = header();
+        push_inst(&mut words, Op::Decorate, &[4, spirv::Decoration::Location as u32, 0]);
+        push_inst(&mut words, Op::Decorate, &[4, spirv::Decoration::Flat as u32]);
+        push_inst(&mut words, Op::Decorate, &[5, spirv::Decoration::Location as u32, 1]);
+        push_inst(&mut words, Op::Decorate, &[5, spirv::Decoration::NoPerspective as u32]);
+        push_inst(&mut words, Op::Decorate, &[5, spirv::Decoration::Centroid as u32]);
+        push_inst(&mut words, Op::TypeInt, &[1, 32, 1]);
+        push_inst(&mut words, Op::TypePointer, &[2, spirv::StorageClass::Input as u32, 1]);
+        push_inst(&mut words, Op::Variable, &[2, 4, spirv::StorageClass::Input as u32]);
+        push_inst(&mut words, Op::Variable, &[2, 5, spirv::StorageClass::Input as u32]);
+        let module = parse_words(words).unwrap();
+        let mut globals = module.global_variables.iter();
+        let (_, var) = globals.next().unwrap();
+        assert_eq!(var.binding, Some(crate::Binding::Location(0)));
+        assert_eq!(var.interpolation, Some(crate::Interpolation::Flat));
+        assert_eq!(var.sampling, None);
+        let (_, var) = globals.next().unwrap();
+        assert_eq!(var.binding, Some(crate::Binding::Location(1)));
+        assert_eq!(var.interpolation, Some(crate::Interpolation::Linear));
+        assert_eq!(var.sampling, Some(crate::Sampling::Centroid));
+    }
+
+    #[test]
+    fn parse_private_global() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypePointer, &[4, spirv::StorageClass::Private as u32, 3]);
+        push_inst(&mut words, Op::Variable, &[4, 5, spirv::StorageClass::Private as u32]);
+        let module = parse_words(words).unwrap();
+        let (_, var) = module.global_variables.iter().next().unwrap();
+        assert_eq!(var.class, crate::StorageClass::Private);
+        assert_eq!(var.binding, None);
+    }
+
+    #[test]
+    fn parse_global_store() {
+        let mut words = header();
+        let mut operands = vec![5];
+        operands.extend(string_operand("color"));
+        push_inst(&mut words, Op::Name, &operands);
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypePointer, &[4, spirv::StorageClass::Private as u32, 3]);
+        push_inst(&mut words, Op::Variable, &[4, 5, spirv::StorageClass::Private as u32]);
+        push_inst(&mut words, Op::Constant, &[3, 6, 1.0f32.to_bits()]);
+        begin_void_function(&mut words, 7);
+        push_inst(&mut words, Op::Store, &[5, 6]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let pointer = match fun.body[0] {
+            crate::Statement::Store { pointer, .. } => pointer,
+            ref other => panic!("Unexpected statement {:?}", other),
+        };
+        match fun.expressions[pointer] {
+            crate::Expression::GlobalVariable(var) => {
+                assert_eq!(module.global_variables[var].name.as_deref(), Some("color"));
+            }
+            ref other => panic!("Unexpected pointer {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_access_expressions() {
+        let mut words = header();
+        push_inst(&mut words, Op::Decorate, &[10, spirv::Decoration::DescriptorSet as u32, 0]);
+        push_inst(&mut words, Op::Decorate, &[10, spirv::Decoration::Binding as u32, 0]);
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeInt, &[4, 32, 0]);
+        push_inst(&mut words, Op::TypeVector, &[5, 3, 2]);
+        push_inst(&mut words, Op::Constant, &[4, 6, 4]);
+        push_inst(&mut words, Op::TypeArray, &[7, 3, 6]);
+        push_inst(&mut words, Op::TypePointer, &[8, spirv::StorageClass::Uniform as u32, 7]);
+        push_inst(&mut words, Op::TypePointer, &[9, spirv::StorageClass::Uniform as u32, 3]);
+        push_inst(&mut words, Op::Variable, &[8, 10, spirv::StorageClass::Uniform as u32]);
+        push_inst(&mut words, Op::Constant, &[3, 11, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::ConstantComposite, &[5, 12, 11, 11]);
+        begin_void_function(&mut words, 13);
+        push_inst(&mut words, Op::AccessChain, &[9, 15, 10, 6]);
+        push_inst(&mut words, Op::CompositeExtract, &[3, 16, 12, 1]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let mut accesses = fun.expressions.iter().skip_while(|&(_, expr)| matches!(*expr,
+            crate::Expression::GlobalVariable(_) | crate::Expression::Constant(_)
+        ));
+        match *accesses.next().unwrap().1 {
+            crate::Expression::Access { base, index } => {
+                match fun.expressions[base] {
+                    crate::Expression::GlobalVariable(_) => (),
+                    ref other => panic!("Unexpected base {:?}", other),
+                }
+                match fun.expressions[index] {
+                    crate::Expression::Constant(c) => {
+                        assert_eq!(module.constants[c].inner, crate::ConstantInner::Uint(4));
+                    }
+                    ref other => panic!("Unexpected index {:?}", other),
+                }
+            }
+            ref other => panic!("Unexpected expression {:?}", other),
+        }
+        match *accesses.next().unwrap().1 {
+            crate::Expression::AccessIndex { base, index } => {
+                assert_eq!(index, 1);
+                match fun.expressions[base] {
+                    crate::Expression::Constant(c) => match module.constants[c].inner {
+                        crate::ConstantInner::Composite(_) => (),
+                        ref other => panic!("Unexpected base constant {:?}", other),
+                    },
+                    ref other => panic!("Unexpected base {:?}", other),
+                }
+            }
+            ref other => panic!("Unexpected expression {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_compose_expression() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeVector, &[4, 3, 2]);
+        push_inst(&mut words, Op::Constant, &[3, 5, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::Constant, &[3, 6, 2.0f32.to_bits()]);
+        begin_void_function(&mut words, 7);
+        push_inst(&mut words, Op::CompositeConstruct, &[4, 9, 5, 6]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (vec_handle, _) = module.types.iter().last().unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        match *fun.expressions.iter().last().unwrap().1 {
+            crate::Expression::Compose { ty, ref components } => {
+                assert_eq!(ty, vec_handle);
+                let values = components
+                    .iter()
+                    .map(|&h| match fun.expressions[h] {
+                        crate::Expression::Constant(c) => &module.constants[c].inner,
+                        ref other => panic!("Unexpected component {:?}", other),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(values, vec![
+                    &crate::ConstantInner::Float(1.0),
+                    &crate::ConstantInner::Float(2.0),
+                ]);
+            }
+            ref other => panic!("Unexpected expression {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_local_variable() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypePointer, &[4, spirv::StorageClass::Function as u32, 3]);
+        push_inst(&mut words, Op::Constant, &[3, 5, 1.0f32.to_bits()]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::Variable, &[4, 8, spirv::StorageClass::Function as u32]);
+        push_inst(&mut words, Op::Store, &[8, 5]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (float_handle, _) = module.types.iter().next().unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (var_handle, var) = fun.local_variables.iter().next().unwrap();
+        assert_eq!(var.ty, float_handle);
+        assert_eq!(var.init, None);
+        match fun.body[0] {
+            crate::Statement::Store { pointer, .. } => match fun.expressions[pointer] {
+                crate::Expression::LocalVariable(handle) => assert_eq!(handle, var_handle),
+                ref other => panic!("Unexpected pointer {:?}", other),
+            },
+            ref other => panic!("Unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_load() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypePointer, &[4, spirv::StorageClass::Function as u32, 3]);
+        push_inst(&mut words, Op::TypeInt, &[5, 32, 1]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::Variable, &[4, 8, spirv::StorageClass::Function as u32]);
+        let load_offset = words.len();
+        push_inst(&mut words, Op::Load, &[3, 9, 8]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words.clone()).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (var_handle, _) = fun.local_variables.iter().next().unwrap();
+        let pointer = fun.expressions
+            .iter()
+            .find_map(|(_, expr)| match *expr {
+                crate::Expression::Load { pointer } => Some(pointer),
+                _ => None,
+            })
+            .unwrap();
+        match fun.expressions[pointer] {
+            crate::Expression::LocalVariable(handle) => assert_eq!(handle, var_handle),
+            ref other => panic!("Unexpected pointer {:?}", other),
+        }
+
+        // Loading an integer from a float variable is rejected.
+        words[load_offset + 1] = 5;
+        match parse_words(words) {
+            Err(super::Error::InvalidLoadType(5)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_store() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypePointer, &[4, spirv::StorageClass::Function as u32, 3]);
+        push_inst(&mut words, Op::Constant, &[3, 5, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::TypeInt, &[9, 32, 1]);
+        push_inst(&mut words, Op::Constant, &[9, 10, 1]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::Variable, &[4, 8, spirv::StorageClass::Function as u32]);
+        let store_offset = words.len();
+        push_inst(&mut words, Op::Store, &[8, 5]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words.clone()).unwrap();
+        let (constant_handle, _) = module.constants.iter().next().unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (var_handle, _) = fun.local_variables.iter().next().unwrap();
+        match fun.body[0] {
+            crate::Statement::Store { pointer, value } => {
+                match fun.expressions[pointer] {
+                    crate::Expression::LocalVariable(handle) => assert_eq!(handle, var_handle),
+                    ref other => panic!("Unexpected pointer {:?}", other),
+                }
+                match fun.expressions[value] {
+                    crate::Expression::Constant(handle) => assert_eq!(handle, constant_handle),
+                    ref other => panic!("Unexpected value {:?}", other),
+                }
+            }
+            ref other => panic!("Unexpected statement {:?}", other),
+        }
+
+        // Storing an integer into a float variable is rejected.
+        words[store_offset + 2] = 10;
+        match parse_words(words) {
+            Err(super::Error::InvalidStoreType(9)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_strings() {
+        let mut words = string_operand("ab");
+        assert_eq!(words.len(), 1);
+        let long = string_operand("fragColor");
+        assert_eq!(long.len(), 3);
+        words.extend(long);
+        words.push(7);
+        let mut parser = super::Parser::new(words.into_iter());
+        assert_eq!(parser.next_string(1).unwrap(), ("ab".to_owned(), 0));
+        assert_eq!(parser.next_string(4).unwrap(), ("fragColor".to_owned(), 1));
+        assert_eq!(parser.next().unwrap(), 7);
+
+        // missing terminator
+        let mut parser = super::Parser::new(vec![u32::from_le_bytes(*b"main")].into_iter());
+        assert!(matches!(parser.next_string(1), Err(super::Error::BadString)));
+        // invalid UTF-8
+        let mut parser = super::Parser::new(vec![0x0000_00FF].into_iter());
+        assert!(matches!(parser.next_string(1), Err(super::Error::BadString)));
+    }
+
+    #[test]
+    fn parse_names() {
+        let mut words = header();
+        let mut operands = vec![6];
+        operands.extend(string_operand("main"));
+        push_inst(&mut words, Op::Name, &operands);
+        let mut operands = vec![4];
+        operands.extend(string_operand("Data"));
+        push_inst(&mut words, Op::Name, &operands);
+        let mut operands = vec![4, 0];
+        operands.extend(string_operand("value"));
+        push_inst(&mut words, Op::MemberName, &operands);
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeStruct, &[4, 3]);
+        begin_void_function(&mut words, 6);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+
+        let (_, fun) = module.functions.iter().next().unwrap();
+        assert_eq!(fun.name.as_deref(), Some("main"));
+        let (_, ty) = module.types.iter().nth(1).unwrap();
+        assert_eq!(ty.name.as_deref(), Some("Data"));
+        match ty.inner {
+            crate::TypeInner::Struct { ref members } => {
+                assert_eq!(members[0].name.as_deref(), Some("value"));
+            }
+            ref other => panic!("Unexpected type {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_workgroup_size() {
+        let mut words = header();
+        let mut operands = vec![spirv::ExecutionModel::GLCompute as u32, 3];
+        operands.extend(string_operand("main"));
+        push_inst(&mut words, Op::EntryPoint, &operands);
+        push_inst(&mut words, Op::ExecutionMode, &[3, spirv::ExecutionMode::LocalSize as u32, 8, 8, 1]);
+        push_void_function_type(&mut words);
+        begin_void_function(&mut words, 3);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+
+        assert_eq!(module.entry_points.len(), 1);
+        assert_eq!(module.entry_points[0].stage, crate::ShaderStage::Compute);
+        assert_eq!(module.entry_points[0].workgroup_size, [8, 8, 1]);
+    }
+
+    #[test]
+    fn parse_shared_helper() {
+        let mut words = header();
+        for &(model, id, name) in &[
+            (spirv::ExecutionModel::Vertex, 10, "vs_main"),
+            (spirv::ExecutionModel::Fragment, 20, "fs_main"),
+        ] {
+            let mut operands = vec![model as u32, id];
+            operands.extend(string_operand(name));
+            push_inst(&mut words, Op::EntryPoint, &operands);
+        }
+        push_void_function_type(&mut words);
+        // both entry points call the helper, which is defined last
+        begin_void_function(&mut words, 10);
+        push_inst(&mut words, Op::FunctionCall, &[1, 12, 30]);
+        end_void_function(&mut words);
+        begin_void_function(&mut words, 20);
+        push_inst(&mut words, Op::FunctionCall, &[1, 22, 30]);
+        end_void_function(&mut words);
+        begin_void_function(&mut words, 30);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+
+        assert_eq!(module.functions.len(), 3);
+        let stages = module.entry_points
+            .iter()
+            .map(|ep| (ep.stage, ep.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(stages, vec![
+            (crate::ShaderStage::Vertex, "vs_main"),
+            (crate::ShaderStage::Fragment, "fs_main"),
+        ]);
+        let callees = module.entry_points
+            .iter()
+            .map(|ep| match module.functions[ep.function].body[0] {
+                crate::Statement::Call { function, .. } => function,
+                ref other => panic!("Unexpected statement {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        // the helper is reserved when the vertex entry point calls it
+        let helper = module.functions.iter().nth(1).unwrap().0;
+        assert_eq!(callees, vec![helper, helper]);
+        assert!(module.entry_points.iter().all(|ep| ep.function != helper));
+        assert_ne!(module.entry_points[0].function, module.entry_points[1].function);
+    }
+
+    #[test]
+    fn parse_struct_members() {
+        let mut words = header();
+        push_inst(&mut words, Op::MemberDecorate, &[5, 0, spirv::Decoration::Offset as u32, 0]);
+        push_inst(&mut words, Op::MemberDecorate, &[5, 1, spirv::Decoration::Offset as u32, 16]);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeVector, &[4, 3, 4]);
+        push_inst(&mut words, Op::TypeStruct, &[5, 4, 3]);
+        let module = parse_words(words).unwrap();
+
+        let float_ty = module.types.iter().next().unwrap().0;
+        let (_, ty) = module.types.iter().nth(2).unwrap();
+        match ty.inner {
+            crate::TypeInner::Struct { ref members } => {
+                assert_eq!(members.len(), 2);
+                assert_eq!(members[0].offset, 0);
+                assert_eq!(members[1].offset, 16);
+                assert_eq!(members[1].ty, float_ty);
+                match module.types[members[0].ty].inner {
+                    crate::TypeInner::Vector { size: crate::VectorSize::Quad, .. } => (),
+                    ref other => panic!("Unexpected member type {:?}", other),
+                }
+            }
+            ref other => panic!("Unexpected type {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_selection() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeBool, &[3]);
+        push_inst(&mut words, Op::ConstantTrue, &[3, 4]);
+        push_inst(&mut words, Op::TypeFloat, &[5, 32]);
+        push_inst(&mut words, Op::TypePointer, &[6, spirv::StorageClass::Function as u32, 5]);
+        push_inst(&mut words, Op::Constant, &[5, 7, 1.0f32.to_bits()]);
+        begin_void_function(&mut words, 8);
+        push_inst(&mut words, Op::Variable, &[6, 10, spirv::StorageClass::Function as u32]);
+        push_inst(&mut words, Op::SelectionMerge, &[13, 0]);
+        let branch_offset = words.len();
+        push_inst(&mut words, Op::BranchConditional, &[4, 11, 12]);
+        push_inst(&mut words, Op::Label, &[11]);
+        push_inst(&mut words, Op::Store, &[10, 7]);
+        push_inst(&mut words, Op::Branch, &[13]);
+        push_inst(&mut words, Op::Label, &[12]);
+        push_inst(&mut words, Op::Kill, &[]);
+        push_inst(&mut words, Op::Label, &[13]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words.clone()).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        match fun.body[..] {
+            [crate::Statement::If { ref accept, ref reject, .. }, crate::Statement::Return { value: None }] => {
+                assert!(matches!(accept[..], [crate::Statement::Store { .. }]));
+                assert!(matches!(reject[..], [crate::Statement::Kill]));
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        }
+
+        // Branching straight to the merge block produces an empty `reject`.
+        words[branch_offset + 3] = 13;
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        match fun.body[..] {
+            [crate::Statement::If { ref accept, ref reject, .. }, crate::Statement::Return { value: None }] => {
+                assert_eq!(accept.len(), 1);
+                assert!(reject.is_empty());
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_switch() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeInt, &[3, 32, 1]);
+        push_inst(&mut words, Op::Constant, &[3, 4, 2]);
+        push_inst(&mut words, Op::TypePointer, &[5, spirv::StorageClass::Function as u32, 3]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::Variable, &[5, 8, spirv::StorageClass::Function as u32]);
+        push_inst(&mut words, Op::SelectionMerge, &[14, 0]);
+        push_inst(&mut words, Op::Switch, &[4, 13, 1, 10, 2, 11, 3, 12]);
+        // case 1: store and break
+        push_inst(&mut words, Op::Label, &[10]);
+        push_inst(&mut words, Op::Store, &[8, 4]);
+        push_inst(&mut words, Op::Branch, &[14]);
+        // case 2: store and fall through into case 3
+        push_inst(&mut words, Op::Label, &[11]);
+        push_inst(&mut words, Op::Store, &[8, 4]);
+        push_inst(&mut words, Op::Branch, &[12]);
+        // case 3: break
+        push_inst(&mut words, Op::Label, &[12]);
+        push_inst(&mut words, Op::Branch, &[14]);
+        // default: store
+        push_inst(&mut words, Op::Label, &[13]);
+        push_inst(&mut words, Op::Store, &[8, 4]);
+        push_inst(&mut words, Op::Branch, &[14]);
+        push_inst(&mut words, Op::Label, &[14]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (cases, default) = match fun.body[..] {
+            [crate::Statement::Switch { ref cases, ref default, .. }, crate::Statement::Return { value: None }] => {
+                (cases, default)
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        };
+        assert_eq!(cases.len(), 3);
+        assert!(matches!(cases[&1], (ref body, None) if matches!(body[..], [
+            crate::Statement::Store { .. },
+            crate::Statement::Break,
+        ])));
+        assert!(matches!(cases[&2], (ref body, Some(_)) if matches!(body[..], [
+            crate::Statement::Store { .. },
+        ])));
+        assert!(matches!(cases[&3], (ref body, None) if matches!(body[..], [
+            crate::Statement::Break,
+        ])));
+        assert!(matches!(default[..], [crate::Statement::Store { .. }]));
+    }
+
+    #[test]
+    fn parse_switch_shared_targets() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeInt, &[3, 32, 1]);
+        push_inst(&mut words, Op::Constant, &[3, 4, 2]);
+        push_inst(&mut words, Op::TypePointer, &[5, spirv::StorageClass::Function as u32, 3]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::Variable, &[5, 8, spirv::StorageClass::Function as u32]);
+        push_inst(&mut words, Op::SelectionMerge, &[14, 0]);
+        // case 3, case 1 and case 4 share a block, case 2 is empty,
+        // and case 5 goes to the default block
+        push_inst(&mut words, Op::Switch, &[4, 13, 3, 10, 1, 10, 2, 14, 5, 13, 4, 10]);
+        push_inst(&mut words, Op::Label, &[10]);
+        push_inst(&mut words, Op::Store, &[8, 4]);
+        push_inst(&mut words, Op::Branch, &[14]);
+        // default: store
+        push_inst(&mut words, Op::Label, &[13]);
+        push_inst(&mut words, Op::Store, &[8, 4]);
+        push_inst(&mut words, Op::Branch, &[14]);
+        push_inst(&mut words, Op::Label, &[14]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (cases, default) = match fun.body[0] {
+            crate::Statement::Switch { ref cases, ref default, .. } => (cases, default),
+            ref other => panic!("Unexpected statement {:?}", other),
+        };
+        let mut literals = cases.keys().cloned().collect::<Vec<_>>();
+        literals.sort();
+        assert_eq!(literals, vec![1, 2, 3, 4]);
+        // case 1 is not next to the others, so it gets its own copy
+        for literal in &[1, 4] {
+            assert!(matches!(cases[literal], (ref body, None) if matches!(body[..], [
+                crate::Statement::Store { .. },
+                crate::Statement::Break,
+            ])));
+        }
+        assert!(matches!(cases[&2], (ref body, None) if matches!(body[..], [crate::Statement::Break])));
+        assert!(matches!(cases[&3], (ref body, Some(_)) if body.is_empty()));
+        assert!(matches!(default[..], [crate::Statement::Store { .. }]));
+    }
+
+    #[test]
+    fn parse_function_parameter() {
+        let mut words = header();
+        push_inst(&mut words, Op::TypeVoid, &[1]);
+        push_inst(&mut words, Op::TypeFloat, &[2, 32]);
+        push_inst(&mut words, Op::TypeFunction, &[3, 1, 2, 2]);
+        push_inst(&mut words, Op::Function, &[1, 4, 0, 3]);
+        push_inst(&mut words, Op::FunctionParameter, &[2, 5]);
+        push_inst(&mut words, Op::FunctionParameter, &[2, 6]);
+        push_inst(&mut words, Op::Label, &[7]);
+        push_inst(&mut words, Op::FNegate, &[2, 8, 6]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        assert_eq!(fun.parameter_types.len(), 2);
+        match *fun.expressions.iter().last().unwrap().1 {
+            crate::Expression::Unary { expr, .. } => match fun.expressions[expr] {
+                crate::Expression::FunctionParameter(index) => assert_eq!(index, 1),
+                ref other => panic!("Unexpected operand {:?}", other),
+            },
+            ref other => panic!("Unexpected expression {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_binary_expression() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::Constant, &[3, 4, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::Constant, &[3, 5, 2.0f32.to_bits()]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::FAdd, &[3, 8, 4, 5]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (_, expr) = fun.expressions.iter().last().unwrap();
+        match *expr {
+            crate::Expression::Binary { op, left, right } => {
+                assert_eq!(op, crate::BinaryOperator::Add);
+                let values = [left, right]
+                    .iter()
+                    .map(|&h| match fun.expressions[h] {
+                        crate::Expression::Constant(c) => &module.constants[c].inner,
+                        ref other => panic!("Unexpected operand {:?}", other),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(values, vec![
+                    &crate::ConstantInner::Float(1.0),
+                    &crate::ConstantInner::Float(2.0),
+                ]);
+            }
+            ref other => panic!("Unexpected expression {:?}", other),
+        }
+    }
+
+    /// Declares `%3 = OpExtInstImport "GLSL.std.450"` and a float type `%4`
+    /// with constants `%5`, `%6` and `%7`, then calls the extended
+    /// instruction `inst` with the given arguments in a void function.
+    fn parse_ext_inst(inst: spirv::GLOp, arguments: &[spirv::Word]) -> Result<crate::Module, super::Error> {
+        let mut words = header();
+        let mut operands = vec![3];
+        operands.extend(string_operand("GLSL.std.450"));
+        push_inst(&mut words, Op::ExtInstImport, &operands);
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[4, 32]);
+        push_inst(&mut words, Op::Constant, &[4, 5, 0.0f32.to_bits()]);
+        push_inst(&mut words, Op::Constant, &[4, 6, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::Constant, &[4, 7, 2.0f32.to_bits()]);
+        begin_void_function(&mut words, 8);
+        let mut operands = vec![4, 10, 3, inst as spirv::Word];
+        operands.extend_from_slice(arguments);
+        push_inst(&mut words, Op::ExtInst, &operands);
+        end_void_function(&mut words);
+        parse_words(words)
+    }
+
+    #[test]
+    fn parse_math_functions() {
+        for &(inst, ref arguments, fun) in &[
+            (spirv::GLOp::Sqrt, vec![7], crate::MathFunction::Sqrt),
+            (spirv::GLOp::FClamp, vec![7, 5, 6], crate::MathFunction::Clamp),
+        ] {
+            let module = parse_ext_inst(inst, arguments).unwrap();
+            let (_, function) = module.functions.iter().next().unwrap();
+            let (_, expr) = function.expressions.iter().last().unwrap();
+            match *expr {
+                crate::Expression::Math { fun: parsed, arguments: ref parsed_args } => {
+                    assert_eq!(parsed, fun);
+                    assert_eq!(parsed_args.len(), arguments.len());
+                }
+                ref other => panic!("Unexpected expression {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_cross_product() {
+        let module = parse_ext_inst(spirv::GLOp::Cross, &[7, 7]).unwrap();
+        let (_, function) = module.functions.iter().next().unwrap();
+        let (_, expr) = function.expressions.iter().last().unwrap();
+        assert!(matches!(*expr, crate::Expression::CrossProduct(..)));
+
+        let error = parse_ext_inst(spirv::GLOp::Cross, &[7]).unwrap_err();
+        assert!(matches!(error, super::Error::InvalidOperandCount(Op::ExtInst, 6)));
+    }
+
+    #[test]
+    fn parse_unsupported_ext_inst() {
+        let error = parse_ext_inst(spirv::GLOp::Modf, &[7, 5]).unwrap_err();
+        assert!(matches!(error, super::Error::UnsupportedExtInst(35)));
+    }
+
+    #[test]
+    fn parse_loop() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeInt, &[3, 32, 1]);
+        push_inst(&mut words, Op::TypeBool, &[4]);
+        push_inst(&mut words, Op::TypePointer, &[5, spirv::StorageClass::Function as u32, 3]);
+        push_inst(&mut words, Op::Constant, &[3, 6, 0]);
+        push_inst(&mut words, Op::Constant, &[3, 7, 10]);
+        push_inst(&mut words, Op::Constant, &[3, 8, 1]);
+        begin_void_function(&mut words, 9);
+        push_inst(&mut words, Op::Variable, &[5, 11, spirv::StorageClass::Function as u32]);
+        push_inst(&mut words, Op::Store, &[11, 6]);
+        push_inst(&mut words, Op::Branch, &[12]);
+        // header
+        push_inst(&mut words, Op::Label, &[12]);
+        push_inst(&mut words, Op::Load, &[3, 16, 11]);
+        push_inst(&mut words, Op::SLessThan, &[4, 17, 16, 7]);
+        let merge_offset = words.len();
+        push_inst(&mut words, Op::LoopMerge, &[15, 14, 0]);
+        push_inst(&mut words, Op::BranchConditional, &[17, 13, 15]);
+        // body
+        push_inst(&mut words, Op::Label, &[13]);
+        push_inst(&mut words, Op::Branch, &[14]);
+        // continue target
+        push_inst(&mut words, Op::Label, &[14]);
+        push_inst(&mut words, Op::Load, &[3, 18, 11]);
+        push_inst(&mut words, Op::IAdd, &[3, 19, 18, 8]);
+        push_inst(&mut words, Op::Store, &[11, 19]);
+        push_inst(&mut words, Op::Branch, &[12]);
+        // merge
+        push_inst(&mut words, Op::Label, &[15]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words.clone()).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        match fun.body[..] {
+            [
+                crate::Statement::Store { .. },
+                crate::Statement::Loop { ref body, ref continuing },
+                crate::Statement::Return { value: None },
+            ] => {
+                match body[..] {
+                    [crate::Statement::If { ref accept, ref reject, .. }] => {
+                        assert!(accept.is_empty());
+                        assert!(matches!(reject[..], [crate::Statement::Break]));
+                    }
+                    ref other => panic!("Unexpected loop body {:?}", other),
+                }
+                assert!(matches!(continuing[..], [crate::Statement::Store { .. }]));
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        }
+
+        // With the header as the continue target, the continuing block is empty.
+        words[merge_offset + 2] = 12;
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        match fun.body[1] {
+            crate::Statement::Loop { ref body, ref continuing } => {
+                assert!(matches!(body[..], [crate::Statement::If { .. }, crate::Statement::Store { .. }]));
+                assert!(continuing.is_empty());
+            }
+            ref other => panic!("Unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_loop_break_continue() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeBool, &[3]);
+        push_inst(&mut words, Op::ConstantTrue, &[3, 4]);
+        begin_void_function(&mut words, 5);
+        push_inst(&mut words, Op::Branch, &[7]);
+        // header
+        push_inst(&mut words, Op::Label, &[7]);
+        push_inst(&mut words, Op::LoopMerge, &[9, 8, 0]);
+        push_inst(&mut words, Op::Branch, &[10]);
+        // if (true) { break; }
+        push_inst(&mut words, Op::Label, &[10]);
+        push_inst(&mut words, Op::SelectionMerge, &[12, 0]);
+        push_inst(&mut words, Op::BranchConditional, &[4, 11, 12]);
+        push_inst(&mut words, Op::Label, &[11]);
+        push_inst(&mut words, Op::Branch, &[9]);
+        // if (true) { continue; }
+        push_inst(&mut words, Op::Label, &[12]);
+        push_inst(&mut words, Op::SelectionMerge, &[14, 0]);
+        push_inst(&mut words, Op::BranchConditional, &[4, 13, 14]);
+        push_inst(&mut words, Op::Label, &[13]);
+        push_inst(&mut words, Op::Branch, &[8]);
+        push_inst(&mut words, Op::Label, &[14]);
+        push_inst(&mut words, Op::Branch, &[8]);
+        // continue target
+        push_inst(&mut words, Op::Label, &[8]);
+        push_inst(&mut words, Op::Branch, &[7]);
+        // merge
+        push_inst(&mut words, Op::Label, &[9]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (body, continuing) = match fun.body[..] {
+            [crate::Statement::Loop { ref body, ref continuing }, crate::Statement::Return { value: None }] => {
+                (body, continuing)
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        };
+        match body[..] {
+            [
+                crate::Statement::If { accept: ref break_accept, reject: ref break_reject, .. },
+                crate::Statement::If { accept: ref continue_accept, reject: ref continue_reject, .. },
+            ] => {
+                assert!(matches!(break_accept[..], [crate::Statement::Break]));
+                assert!(break_reject.is_empty());
+                assert!(matches!(continue_accept[..], [crate::Statement::Continue]));
+                assert!(continue_reject.is_empty());
+            }
+            ref other => panic!("Unexpected loop body {:?}", other),
+        }
+        assert!(continuing.is_empty());
+    }
+
+    #[test]
+    fn parse_do_while() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeBool, &[3]);
+        push_inst(&mut words, Op::ConstantTrue, &[3, 4]);
+        begin_void_function(&mut words, 5);
+        push_inst(&mut words, Op::Branch, &[7]);
+        // header
+        push_inst(&mut words, Op::Label, &[7]);
+        push_inst(&mut words, Op::LoopMerge, &[9, 8, 0]);
+        push_inst(&mut words, Op::Branch, &[10]);
+        // body
+        push_inst(&mut words, Op::Label, &[10]);
+        push_inst(&mut words, Op::Branch, &[8]);
+        // continue target, with a conditional back-edge
+        push_inst(&mut words, Op::Label, &[8]);
+        push_inst(&mut words, Op::BranchConditional, &[4, 7, 9]);
+        // merge
+        push_inst(&mut words, Op::Label, &[9]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        match fun.body[0] {
+            crate::Statement::Loop { ref body, ref continuing } => {
+                assert!(body.is_empty());
+                match continuing[..] {
+                    [crate::Statement::If { ref accept, ref reject, .. }] => {
+                        assert!(accept.is_empty());
+                        assert!(matches!(reject[..], [crate::Statement::Break]));
+                    }
+                    ref other => panic!("Unexpected continuing block {:?}", other),
+                }
+            }
+            ref other => panic!("Unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_vector_shuffle() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeVector, &[4, 3, 4]);
+        push_inst(&mut words, Op::TypeVector, &[5, 3, 2]);
+        push_inst(&mut words, Op::Constant, &[3, 6, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::ConstantComposite, &[4, 7, 6, 6, 6, 6]);
+        push_inst(&mut words, Op::ConstantComposite, &[4, 8, 6, 6, 6, 6]);
+        begin_void_function(&mut words, 9);
+        let shuffle_offset = words.len();
+        push_inst(&mut words, Op::VectorShuffle, &[5, 11, 7, 7, 0, 6]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words.clone()).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (_, swizzle) = fun.expressions.iter().last().unwrap();
+        match *swizzle {
+            crate::Expression::Swizzle { size, vector, pattern } => {
+                assert_eq!(size, crate::VectorSize::Bi);
+                assert_eq!(pattern[.. 2], [crate::SwizzleComponent::X, crate::SwizzleComponent::Z]);
+                assert!(matches!(fun.expressions[vector], crate::Expression::Constant(_)));
+            }
+            ref other => panic!("Unexpected expression {:?}", other),
+        }
+
+        // Picking components from another vector isn't a swizzle.
+        words[shuffle_offset + 4] = 8;
+        match parse_words(words) {
+            Err(super::Error::UnsupportedVectorShuffle(6)) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_undef() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypePointer, &[4, spirv::StorageClass::Function as u32, 3]);
+        push_inst(&mut words, Op::Undef, &[3, 5]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::Variable, &[4, 8, spirv::StorageClass::Function as u32]);
+        push_inst(&mut words, Op::Undef, &[3, 9]);
+        push_inst(&mut words, Op::Store, &[8, 5]);
+        push_inst(&mut words, Op::Store, &[8, 9]);
+        end_void_function(&mut words);
+
+        let module = parse_words(words).unwrap();
+        let (float_ty, _) = module.types.iter().next().unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let values = fun.body
+            .iter()
+            .filter_map(|statement| match *statement {
+                crate::Statement::Store { value, .. } => Some(value),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values.len(), 2);
+        for value in values {
+            match fun.expressions[value] {
+                crate::Expression::Undef(ty) => assert_eq!(ty, float_ty),
+                ref other => panic!("Unexpected value {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_select() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::TypeBool, &[4]);
+        push_inst(&mut words, Op::ConstantTrue, &[4, 5]);
+        push_inst(&mut words, Op::Constant, &[3, 6, 1.0f32.to_bits()]);
+        push_inst(&mut words, Op::Constant, &[3, 7, 2.0f32.to_bits()]);
+        begin_void_function(&mut words, 8);
+        push_inst(&mut words, Op::Select, &[3, 10, 5, 6, 7]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (_, expr) = fun.expressions.iter().last().unwrap();
+        match *expr {
+            crate::Expression::Select { condition, accept, reject } => {
+                let values = [condition, accept, reject]
+                    .iter()
+                    .map(|&h| match fun.expressions[h] {
+                        crate::Expression::Constant(c) => &module.constants[c].inner,
+                        ref other => panic!("Unexpected operand {:?}", other),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(values, vec![
+                    &crate::ConstantInner::Bool(true),
+                    &crate::ConstantInner::Float(1.0),
+                    &crate::ConstantInner::Float(2.0),
+                ]);
+            }
+            ref other => panic!("Unexpected expression {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_derivatives() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[3, 32]);
+        push_inst(&mut words, Op::Constant, &[3, 4, 1.0f32.to_bits()]);
+        begin_void_function(&mut words, 5);
+        push_inst(&mut words, Op::DPdx, &[3, 7, 4]);
+        push_inst(&mut words, Op::DPdyFine, &[3, 8, 4]);
+        push_inst(&mut words, Op::Fwidth, &[3, 9, 4]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let axes = fun.expressions
+            .iter()
+            .filter_map(|(_, expr)| match *expr {
+                crate::Expression::Derivative { axis, expr } => match fun.expressions[expr] {
+                    crate::Expression::Constant(_) => Some(axis),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(axes, vec![
+            crate::DerivativeAxis::X,
+            crate::DerivativeAxis::Y,
+            crate::DerivativeAxis::Width,
+        ]);
+    }
+
+    #[test]
+    fn parse_intrinsics() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeBool, &[3]);
+        push_inst(&mut words, Op::TypeVector, &[4, 3, 2]);
+        push_inst(&mut words, Op::ConstantTrue, &[3, 5]);
+        push_inst(&mut words, Op::ConstantComposite, &[4, 6, 5, 5]);
+        begin_void_function(&mut words, 7);
+        push_inst(&mut words, Op::All, &[3, 9, 6]);
+        push_inst(&mut words, Op::Any, &[3, 10, 6]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let funs = fun.expressions
+            .iter()
+            .filter_map(|(_, expr)| match *expr {
+                crate::Expression::Intrinsic { fun: intrinsic, argument } => match fun.expressions[argument] {
+                    crate::Expression::Constant(_) => Some(intrinsic),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(funs, vec![crate::IntrinsicFunction::All, crate::IntrinsicFunction::Any]);
+    }
+
+    #[test]
+    fn parse_line() {
+        let mut words = header();
+        let mut operands = vec![3];
+        operands.extend(string_operand("shader.vert"));
+        push_inst(&mut words, Op::String, &operands);
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeFloat, &[4, 32]);
+        push_inst(&mut words, Op::Constant, &[4, 5, 1.0f32.to_bits()]);
+        begin_void_function(&mut words, 6);
+        push_inst(&mut words, Op::FNegate, &[4, 8, 5]);
+        push_inst(&mut words, Op::Line, &[3, 12, 7]);
+        push_inst(&mut words, Op::FNegate, &[4, 9, 8]);
+        push_inst(&mut words, Op::NoLine, &[]);
+        push_inst(&mut words, Op::FNegate, &[4, 10, 9]);
+        push_inst(&mut words, Op::Line, &[3, 14, 1]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let span = |line, column| crate::Span {
+            file: Some("shader.vert".to_owned()),
+            line,
+            column,
+        };
+        let spans = fun.expressions
+            .iter()
+            .filter(|&(_, expr)| matches!(*expr, crate::Expression::Unary { .. }))
+            .map(|(handle, _)| fun.expression_spans.get(&handle).cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec![None, Some(span(12, 7)), None]);
+        match fun.body[..] {
+            [crate::Statement::Line(Some(ref line)), crate::Statement::Return { value: None }] => {
+                assert_eq!(*line, span(14, 1));
+            }
+            ref other => panic!("Unexpected body {:?}", other),
+        }
+
+        // the file has to be declared
+        let mut words = header();
+        push_void_function_type(&mut words);
+        begin_void_function(&mut words, 3);
+        push_inst(&mut words, Op::Line, &[7, 1, 1]);
+        end_void_function(&mut words);
+        assert!(matches!(parse_words(words), Err(super::Error::InvalidId(7))));
+
+        // line information outside of blocks is accepted
+        let mut words = header();
+        let mut operands = vec![3];
+        operands.extend(string_operand("shader.vert"));
+        push_inst(&mut words, Op::String, &operands);
+        push_inst(&mut words, Op::TypeVoid, &[1]);
+        push_inst(&mut words, Op::TypeFloat, &[4, 32]);
+        push_inst(&mut words, Op::TypeFunction, &[2, 1, 4]);
+        push_inst(&mut words, Op::TypePointer, &[5, spirv::StorageClass::Private as u32, 4]);
+        push_inst(&mut words, Op::Line, &[3, 1, 1]);
+        push_inst(&mut words, Op::Variable, &[5, 6, spirv::StorageClass::Private as u32]);
+        push_inst(&mut words, Op::NoLine, &[]);
+        push_inst(&mut words, Op::Line, &[3, 2, 1]);
+        push_inst(&mut words, Op::Function, &[1, 7, 0, 2]);
+        push_inst(&mut words, Op::Line, &[3, 2, 10]);
+        push_inst(&mut words, Op::FunctionParameter, &[4, 8]);
+        push_inst(&mut words, Op::Line, &[3, 3, 1]);
+        push_inst(&mut words, Op::Label, &[9]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        assert_eq!(module.global_variables.len(), 1);
+        let (_, fun) = module.functions.iter().next().unwrap();
+        assert_eq!(fun.parameter_types.len(), 1);
+    }
+
+    #[test]
+    fn parse_conversions() {
+        let mut words = header();
+        push_void_function_type(&mut words);
+        push_inst(&mut words, Op::TypeInt, &[3, 32, 1]);
+        push_inst(&mut words, Op::TypeFloat, &[4, 32]);
+        push_inst(&mut words, Op::TypeInt, &[5, 32, 0]);
+        push_inst(&mut words, Op::Constant, &[3, 6, 7]);
+        begin_void_function(&mut words, 7);
+        push_inst(&mut words, Op::ConvertSToF, &[4, 9, 6]);
+        push_inst(&mut words, Op::Bitcast, &[5, 10, 6]);
+        end_void_function(&mut words);
+        let module = parse_words(words).unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let casts = fun.expressions
+            .iter()
+            .filter_map(|(_, expr)| match *expr {
+                crate::Expression::As { expr, kind, convert } => match fun.expressions[expr] {
+                    crate::Expression::Constant(_) => Some((kind, convert)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(casts, vec![
+            (crate::ScalarKind::Float, true),
+            (crate::ScalarKind::Uint, false),
+        ]);
+    }
+
     #[test]
     fn parse() {
         let bin = vec![
@@ -56,7 +56,7 @@ mod lex {
                     (Token::Separator(cur), input)
                 }
             }
-            ';' | ',' | '.' => {
+            ';' | ',' | '.' | '@' => {
                 (Token::Separator(cur), chars.as_str())
             }
             '(' | ')' | '{' | '}' => {
@@ -150,6 +150,9 @@ pub enum Error<'a> {
     UnknownStorageClass(&'a str),
     UnknownDecoration(&'a str),
     UnknownBuiltin(&'a str),
+    UnknownBuiltinClass(spirv::BuiltIn),
+    UnknownInterpolation(&'a str),
+    UnknownSampling(&'a str),
     UnknownPipelineStage(&'a str),
     UnknownIdent(&'a str),
     UnknownType(&'a str),
@@ -233,6 +236,7 @@ impl<'a> Lexer<'a> {
             Token::Word("f32") => (crate::ScalarKind::Float, 32),
             Token::Word("i32") => (crate::ScalarKind::Sint, 32),
             Token::Word("u32") => (crate::ScalarKind::Uint, 32),
+            Token::Word("bool") => (crate::ScalarKind::Bool, 1),
             other => return Err(Error::Unexpected(other)),
         };
         self.expect(Token::Paren('>'))?;
@@ -261,6 +265,8 @@ struct StatementContext<'input, 'temp, 'out> {
     types: &'out mut Arena<crate::Type>,
     constants: &'out mut Arena<crate::Constant>,
     global_vars: &'out Arena<crate::GlobalVariable>,
+    functions: &'out Arena<crate::Function>,
+    parameter_types: &'out [Handle<crate::Type>],
 }
 
 impl<'a> StatementContext<'a, '_, '_> {
@@ -273,6 +279,8 @@ impl<'a> StatementContext<'a, '_, '_> {
             types: self.types,
             constants: self.constants,
             global_vars: self.global_vars,
+            functions: self.functions,
+            parameter_types: self.parameter_types,
         }
     }
 
@@ -285,6 +293,8 @@ impl<'a> StatementContext<'a, '_, '_> {
             constants: self.constants,
             global_vars: self.global_vars,
             local_vars: self.variables,
+            functions: self.functions,
+            parameter_types: self.parameter_types,
         }
     }
 }
@@ -297,6 +307,8 @@ struct ExpressionContext<'input, 'temp, 'out> {
     constants: &'out mut Arena<crate::Constant>,
     global_vars: &'out Arena<crate::GlobalVariable>,
     local_vars: &'out Arena<crate::LocalVariable>,
+    functions: &'out Arena<crate::Function>,
+    parameter_types: &'out [Handle<crate::Type>],
 }
 
 impl<'a> ExpressionContext<'a, '_, '_> {
@@ -309,12 +321,23 @@ impl<'a> ExpressionContext<'a, '_, '_> {
             constants: self.constants,
             global_vars: self.global_vars,
             local_vars: self.local_vars,
+            functions: self.functions,
+            parameter_types: self.parameter_types,
         }
     }
 
     fn resolve_type(&mut self, handle: Handle<crate::Expression>) -> Result<Handle<crate::Type>, Error<'a>> {
         self.typifier
-            .resolve(handle, self.expressions, self.types, self.constants, self.global_vars, self.local_vars)
+            .resolve(
+                handle,
+                self.expressions,
+                self.types,
+                self.constants,
+                self.global_vars,
+                self.local_vars,
+                self.functions,
+                self.parameter_types,
+            )
             .map_err(Error::InvalidResolve)
     }
 
@@ -354,6 +377,56 @@ pub enum Scope {
     GeneralExpr,
 }
 
+/// Decorations collected in front of a global declaration.
+#[derive(Default)]
+struct GlobalDecorations {
+    binding: Option<crate::Binding>,
+    bind_index: Option<u32>,
+    bind_set: Option<u32>,
+    interpolation: Option<crate::Interpolation>,
+    sampling: Option<crate::Sampling>,
+}
+
+impl GlobalDecorations {
+    fn parse<'a>(&mut self, name: &'a str, lexer: &mut Lexer<'a>) -> Result<(), Error<'a>> {
+        match name {
+            "location" => {
+                let loc = lexer.next_uint_literal()?;
+                self.binding = Some(crate::Binding::Location(loc));
+            }
+            "builtin" => {
+                let builtin = Parser::get_built_in(lexer.next_ident()?)?;
+                self.binding = Some(crate::Binding::BuiltIn(builtin));
+            }
+            "binding" => {
+                self.bind_index = Some(lexer.next_uint_literal()?);
+            }
+            "set" | "group" => {
+                self.bind_set = Some(lexer.next_uint_literal()?);
+            }
+            "interpolate" => {
+                self.interpolation = Parser::get_interpolation(lexer.next_ident()?)?;
+                if lexer.skip(Token::Separator(',')) {
+                    self.sampling = Some(Parser::get_sampling(lexer.next_ident()?)?);
+                }
+            }
+            other => return Err(Error::UnknownDecoration(other)),
+        }
+        Ok(())
+    }
+
+    fn binding<'a>(&self) -> Result<Option<crate::Binding>, Error<'a>> {
+        match (self.bind_set, self.bind_index) {
+            (Some(set), Some(index)) if self.binding.is_none() => {
+                Ok(Some(crate::Binding::Resource { group: set, binding: index }))
+            }
+            (None, None) => Ok(self.binding.clone()),
+            _ if self.binding.is_none() => Err(Error::Other),
+            _ => Ok(self.binding.clone()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ParseError<'a> {
     pub error: Error<'a>,
@@ -376,12 +449,12 @@ impl Parser {
         }
     }
 
-    fn get_storage_class(word: &str) -> Result<spirv::StorageClass, Error<'_>> {
+    fn get_storage_class(word: &str) -> Result<crate::StorageClass, Error<'_>> {
         match word {
-            "in" => Ok(spirv::StorageClass::Input),
-            "out" => Ok(spirv::StorageClass::Output),
-            "uniform" => Ok(spirv::StorageClass::Uniform),
-            "storage_buffer" => Ok(spirv::StorageClass::StorageBuffer),
+            "in" => Ok(crate::StorageClass::Input),
+            "out" => Ok(crate::StorageClass::Output),
+            "uniform" => Ok(crate::StorageClass::Uniform),
+            "storage_buffer" => Ok(crate::StorageClass::Storage),
             _ => Err(Error::UnknownStorageClass(word)),
         }
     }
@@ -390,16 +463,57 @@ impl Parser {
         match word {
             "position" => Ok(spirv::BuiltIn::Position),
             "vertex_idx" => Ok(spirv::BuiltIn::VertexId),
+            "instance_idx" => Ok(spirv::BuiltIn::InstanceIndex),
+            "front_facing" => Ok(spirv::BuiltIn::FrontFacing),
+            "frag_coord" => Ok(spirv::BuiltIn::FragCoord),
+            "frag_depth" => Ok(spirv::BuiltIn::FragDepth),
+            "local_invocation_id" => Ok(spirv::BuiltIn::LocalInvocationId),
+            "local_invocation_idx" => Ok(spirv::BuiltIn::LocalInvocationIndex),
             "global_invocation_id" => Ok(spirv::BuiltIn::GlobalInvocationId),
+            "workgroup_id" => Ok(spirv::BuiltIn::WorkgroupId),
             _ => Err(Error::UnknownBuiltin(word)),
         }
     }
 
-    fn get_execution_model(word: &str) -> Result<spirv::ExecutionModel, Error<'_>> {
+    /// Storage class of a global bound to `built_in` without an explicit class.
+    fn get_built_in_class<'a>(built_in: spirv::BuiltIn) -> Result<crate::StorageClass, Error<'a>> {
+        match built_in {
+            spirv::BuiltIn::VertexId |
+            spirv::BuiltIn::InstanceIndex |
+            spirv::BuiltIn::FrontFacing |
+            spirv::BuiltIn::FragCoord |
+            spirv::BuiltIn::LocalInvocationId |
+            spirv::BuiltIn::LocalInvocationIndex |
+            spirv::BuiltIn::GlobalInvocationId |
+            spirv::BuiltIn::WorkgroupId => Ok(crate::StorageClass::Input),
+            spirv::BuiltIn::Position |
+            spirv::BuiltIn::FragDepth => Ok(crate::StorageClass::Output),
+            _ => Err(Error::UnknownBuiltinClass(built_in)),
+        }
+    }
+
+    fn get_interpolation(word: &str) -> Result<Option<crate::Interpolation>, Error<'_>> {
         match word {
-            "vertex" => Ok(spirv::ExecutionModel::Vertex),
-            "fragment" => Ok(spirv::ExecutionModel::Fragment),
-            "compute" => Ok(spirv::ExecutionModel::GLCompute),
+            "perspective" => Ok(None),
+            "flat" => Ok(Some(crate::Interpolation::Flat)),
+            "linear" => Ok(Some(crate::Interpolation::Linear)),
+            _ => Err(Error::UnknownInterpolation(word)),
+        }
+    }
+
+    fn get_sampling(word: &str) -> Result<crate::Sampling, Error<'_>> {
+        match word {
+            "centroid" => Ok(crate::Sampling::Centroid),
+            "sample" => Ok(crate::Sampling::Sample),
+            _ => Err(Error::UnknownSampling(word)),
+        }
+    }
+
+    fn get_shader_stage(word: &str) -> Result<crate::ShaderStage, Error<'_>> {
+        match word {
+            "vertex" => Ok(crate::ShaderStage::Vertex),
+            "fragment" => Ok(crate::ShaderStage::Fragment),
+            "compute" => Ok(crate::ShaderStage::Compute),
             _ => Err(Error::UnknownPipelineStage(word)),
         }
     }
@@ -527,7 +641,7 @@ impl Parser {
                         arguments.push(arg);
                     }
                     crate::Expression::Call {
-                        name: name.to_owned(),
+                        origin: crate::FunctionOrigin::External(name.to_owned()),
                         arguments,
                     }
                 } else {
@@ -849,7 +963,7 @@ impl Parser {
         lexer: &mut Lexer<'a>,
         type_arena: &mut Arena<crate::Type>,
         const_arena: &mut Arena<crate::Constant>,
-    ) -> Result<(&'a str, Option<spirv::StorageClass>, Handle<crate::Type>), Error<'a>> {
+    ) -> Result<(&'a str, Option<crate::StorageClass>, Handle<crate::Type>), Error<'a>> {
         self.scopes.push(Scope::VariableDecl);
         let mut class = None;
         if lexer.skip(Token::Paren('<')) {
@@ -877,6 +991,7 @@ impl Parser {
         let mut members = Vec::new();
         lexer.expect(Token::Paren('{'))?;
         loop {
+            let mut offset = 0;
             if lexer.skip(Token::DoubleParen('[')) {
                 self.scopes.push(Scope::Decoration);
                 let mut ready = true;
@@ -889,7 +1004,7 @@ impl Parser {
                             ready = true;
                         }
                         Token::Word("offset") if ready => {
-                            let _offset = lexer.next_uint_literal()?; //TODO
+                            offset = lexer.next_uint_literal()?;
                             ready = false;
                         }
                         other => return Err(Error::Unexpected(other)),
@@ -909,6 +1024,7 @@ impl Parser {
                 name: Some(name.to_owned()),
                 binding: None,
                 ty,
+                offset,
             });
         }
     }
@@ -938,6 +1054,12 @@ impl Parser {
                     width: 32,
                 }
             }
+            Token::Word("bool") => {
+                crate::TypeInner::Scalar {
+                    kind: crate::ScalarKind::Bool,
+                    width: 1,
+                }
+            }
             Token::Word("vec2") => {
                 let (kind, width) = lexer.next_scalar_generic()?;
                 crate::TypeInner::Vector {
@@ -1119,8 +1241,14 @@ impl Parser {
                             continuing
                         }
                     }
-                    "break" => crate::Statement::Break,
-                    "continue" => crate::Statement::Continue,
+                    "break" => {
+                        lexer.expect(Token::Separator(';'))?;
+                        crate::Statement::Break
+                    }
+                    "continue" => {
+                        lexer.expect(Token::Separator(';'))?;
+                        crate::Statement::Continue
+                    }
                     ident => {
                         // assignment
                         let var_expr = context.lookup_ident.lookup(ident)?;
@@ -1203,6 +1331,8 @@ impl Parser {
             types: &mut module.types,
             constants: &mut module.constants,
             global_vars: &module.global_variables,
+            functions: &module.functions,
+            parameter_types: &parameter_types,
         })?;
         // done
         let global_usage = crate::GlobalUse::scan(&expressions, &body, &module.global_variables);
@@ -1216,6 +1346,7 @@ impl Parser {
             global_usage,
             local_variables,
             expressions,
+            expression_spans: crate::FastHashMap::default(),
             body,
         };
         Ok(module.functions.append(fun))
@@ -1227,29 +1358,13 @@ impl Parser {
         module: &mut crate::Module,
         lookup_global_expression: &mut FastHashMap<&'a str, crate::Expression>,
     ) -> Result<bool, Error<'a>> {
-        // read decorations
-        let mut binding = None;
+        // read decorations, either as `[[name value, ...]]` or as `@name(value) ...`
+        let mut decorations = GlobalDecorations::default();
         if lexer.skip(Token::DoubleParen('[')) {
-            let (mut bind_index, mut bind_set) = (None, None);
             self.scopes.push(Scope::Decoration);
             loop {
-                match lexer.next_ident()? {
-                    "location" => {
-                        let loc = lexer.next_uint_literal()?;
-                        binding = Some(crate::Binding::Location(loc));
-                    }
-                    "builtin" => {
-                        let builtin = Self::get_built_in(lexer.next_ident()?)?;
-                        binding = Some(crate::Binding::BuiltIn(builtin));
-                    }
-                    "binding" => {
-                        bind_index = Some(lexer.next_uint_literal()?);
-                    }
-                    "set" => {
-                        bind_set = Some(lexer.next_uint_literal()?);
-                    }
-                    other => return Err(Error::UnknownDecoration(other)),
-                }
+                let name = lexer.next_ident()?;
+                decorations.parse(name, lexer)?;
                 match lexer.next() {
                     Token::DoubleParen(']') => {
                         break;
@@ -1259,15 +1374,19 @@ impl Parser {
                     other => return Err(Error::Unexpected(other)),
                 }
             }
-            match (bind_set, bind_index) {
-                (Some(set), Some(index)) if binding.is_none() => {
-                    binding = Some(crate::Binding::Descriptor { set, binding: index });
-                }
-                _ if binding.is_none() => return Err(Error::Other),
-                _ => {}
-            }
             self.scopes.pop();
         }
+        while lexer.skip(Token::Separator('@')) {
+            self.scopes.push(Scope::Decoration);
+            let name = lexer.next_ident()?;
+            lexer.expect(Token::Paren('('))?;
+            decorations.parse(name, lexer)?;
+            lexer.expect(Token::Paren(')'))?;
+            self.scopes.pop();
+        }
+        let mut binding = decorations.binding()?;
+        let interpolation = decorations.interpolation;
+        let sampling = decorations.sampling;
         // read items
         match lexer.next() {
             Token::Separator(';') => {},
@@ -1310,21 +1429,21 @@ impl Parser {
             }
             Token::Word("var") => {
                 let (name, class, ty) = self.parse_variable_decl(lexer, &mut module.types, &mut module.constants)?;
+                let class = match class {
+                    Some(c) => c,
+                    None => match binding {
+                        Some(crate::Binding::BuiltIn(built_in)) => Self::get_built_in_class(built_in)?,
+                        _ => crate::StorageClass::Private,
+                    },
+                };
                 let var_handle = module.global_variables.append(crate::GlobalVariable {
                     name: Some(name.to_owned()),
-                    class: match class {
-                        Some(c) => c,
-                        None => match binding {
-                            Some(crate::Binding::BuiltIn(builtin)) => match builtin {
-                                spirv::BuiltIn::GlobalInvocationId => spirv::StorageClass::Input,
-                                spirv::BuiltIn::Position => spirv::StorageClass::Output,
-                                _ => unimplemented!(),
-                            },
-                            _ => spirv::StorageClass::Private,
-                        },
-                    },
+                    class,
                     binding: binding.take(),
+                    interpolation,
+                    sampling,
                     ty,
+                    init: None,
                 });
                 lookup_global_expression.insert(name, crate::Expression::GlobalVariable(var_handle));
             }
@@ -1332,7 +1451,7 @@ impl Parser {
                 self.parse_function_decl(lexer, module, &lookup_global_expression)?;
             }
             Token::Word("entry_point") => {
-                let exec_model = Self::get_execution_model(lexer.next_ident()?)?;
+                let stage = Self::get_shader_stage(lexer.next_ident()?)?;
                 let export_name = if lexer.skip(Token::Word("as")) {
                     match lexer.next() {
                         Token::String(name) => Some(name),
@@ -1349,9 +1468,10 @@ impl Parser {
                     .find(|(_, fun)| fun.name.as_ref().map(|s| s.as_str()) == Some(fun_ident))
                     .ok_or(Error::UnknownFunction(fun_ident))?;
                 module.entry_points.push(crate::EntryPoint {
-                    exec_model,
+                    stage,
                     name: export_name.unwrap_or(fun_ident).to_owned(),
                     function: fun_handle,
+                    workgroup_size: [0; 3],
                 });
             }
             Token::End => return Ok(false),
@@ -1400,3 +1520,97 @@ impl Parser {
 pub fn parse_str(source: &str) -> Result<crate::Module, ParseError> {
     Parser::new().parse(source)
 }
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse_loop() {
+        let module = super::parse_str("
+            fn main() -> void {
+                var i : i32 = 0;
+                loop {
+                    if (i >= 4) {
+                        break;
+                    }
+                    continuing {
+                        i = i + 1;
+                    }
+                }
+                return;
+            }
+            entry_point vertex as \"main\" = main;
+        ").unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let (body, continuing) = match fun.body[1] {
+            crate::Statement::Loop { ref body, ref continuing } => (body, continuing),
+            ref other => panic!("Unexpected statement {:?}", other),
+        };
+        match body[..] {
+            [crate::Statement::If { ref accept, ref reject, .. }] => {
+                assert!(matches!(accept[..], [crate::Statement::Break]));
+                assert!(reject.is_empty());
+            }
+            ref other => panic!("Unexpected loop body {:?}", other),
+        }
+        match continuing[..] {
+            [crate::Statement::Store { value, .. }] => match fun.expressions[value] {
+                crate::Expression::Binary { op: crate::BinaryOperator::Add, .. } => (),
+                ref other => panic!("Unexpected value {:?}", other),
+            },
+            ref other => panic!("Unexpected continuing block {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_bool() {
+        let module = super::parse_str("
+            fn is_less(a : f32, b : f32) -> bool {
+                return a < b;
+            }
+        ").unwrap();
+        let (_, fun) = module.functions.iter().next().unwrap();
+        let return_type = fun.return_type.unwrap();
+        assert_eq!(module.types[return_type].inner, crate::TypeInner::Scalar {
+            kind: crate::ScalarKind::Bool,
+            width: 1,
+        });
+        crate::proc::Validator::new().validate(&module).unwrap();
+    }
+
+    #[test]
+    fn parse_struct_offsets() {
+        let module = super::parse_str("
+            type Particle = struct {
+              [[offset 0]] pos : vec2<f32>;
+              [[offset 8]] vel : vec2<f32>;
+            };
+        ").unwrap();
+        let members = module.types
+            .iter()
+            .find_map(|(_, ty)| match ty.inner {
+                crate::TypeInner::Struct { ref members } => Some(members),
+                _ => None,
+            })
+            .unwrap();
+        let offsets = members.iter().map(|m| m.offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 8]);
+    }
+
+    #[test]
+    fn infer_built_in_class() {
+        let module = super::parse_str("
+            @builtin(frag_coord) var coord : vec4<f32>;
+            [[builtin front_facing]] var ff : bool;
+            [[builtin frag_depth]] var depth : f32;
+        ").unwrap();
+        let classes = module.global_variables
+            .iter()
+            .map(|(_, var)| var.class)
+            .collect::<Vec<_>>();
+        assert_eq!(classes, [
+            crate::StorageClass::Input,
+            crate::StorageClass::Input,
+            crate::StorageClass::Output,
+        ]);
+    }
+}
@@ -1,15 +1,31 @@
 #![allow(clippy::new_without_default)]
+// Lints added to newer toolchains than the supported one.
+#![allow(
+    unused_parens,
+    mismatched_lifetime_syntaxes,
+    clippy::manual_range_contains,
+    clippy::map_collect_result_unit,
+    clippy::mem_replace_with_default,
+    clippy::needless_borrow,
+    clippy::needless_borrowed_reference,
+    clippy::needless_lifetimes,
+    clippy::only_used_in_recursion,
+    clippy::option_as_ref_deref,
+    clippy::unnecessary_lazy_evaluations,
+)]
 
 mod arena;
 pub mod back;
 pub mod front;
 pub mod proc;
 
-use crate::arena::{Arena, Handle};
+pub use crate::arena::{Arena, Handle};
 
 use std::{
     collections::{HashMap, HashSet},
-    hash::BuildHasherDefault,
+    fmt,
+    hash::{self, BuildHasherDefault},
+    mem,
 };
 
 
@@ -24,10 +40,27 @@ pub struct Header {
     pub generator: u32,
 }
 
+impl Default for Header {
+    fn default() -> Self {
+        Header {
+            version: (1, 0, 0),
+            generator: 0,
+        }
+    }
+}
+
+impl Header {
+    /// Check if the version is equal to or newer than `major.minor`.
+    pub fn version_at_least(&self, major: u8, minor: u8) -> bool {
+        let (self_major, self_minor, _) = self.version;
+        (self_major, self_minor) >= (major, minor)
+    }
+}
+
 pub type Bytes = u8;
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub enum VectorSize {
@@ -37,7 +70,7 @@ pub enum VectorSize {
 }
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub enum ScalarKind {
@@ -48,7 +81,7 @@ pub enum ScalarKind {
 }
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub enum ArraySize {
@@ -56,13 +89,40 @@ pub enum ArraySize {
     Dynamic,
 }
 
-#[derive(Debug, PartialEq)]
+/// Address space of a variable or pointer.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum StorageClass {
+    /// Function-local variables.
+    Function,
+    /// Uniform buffers.
+    Uniform,
+    /// Read-write storage buffers.
+    Storage,
+    /// Pipeline inputs.
+    Input,
+    /// Pipeline outputs.
+    Output,
+    /// Module-scope variables private to an invocation.
+    Private,
+    /// Variables shared across a compute workgroup.
+    WorkGroup,
+    /// Small blocks of uniform data set directly by the API.
+    PushConstant,
+    /// Opaque handles, such as images and samplers.
+    Handle,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub struct StructMember {
     pub name: Option<String>,
     pub binding: Option<Binding>,
     pub ty: Handle<Type>,
+    /// Byte offset of the member from the start of the struct.
+    pub offset: u32,
 }
 
 bitflags::bitflags! {
@@ -77,7 +137,7 @@ bitflags::bitflags! {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub struct Type {
@@ -85,31 +145,158 @@ pub struct Type {
     pub inner: TypeInner,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub enum TypeInner {
     Scalar { kind: ScalarKind, width: Bytes },
     Vector { size: VectorSize, kind: ScalarKind, width: Bytes },
     Matrix { columns: VectorSize, rows: VectorSize, kind: ScalarKind, width: Bytes },
-    Pointer { base: Handle<Type>, class: spirv::StorageClass },
+    Pointer { base: Handle<Type>, class: StorageClass },
     Array { base: Handle<Type>, size: ArraySize },
     Struct { members: Vec<StructMember> },
     Image { base: Handle<Type>, dim: spirv::Dim, flags: ImageFlags },
-    Sampler,
+    Sampler { comparison: bool },
 }
 
-#[derive(Debug, PartialEq)]
+impl TypeInner {
+    /// Compute the size of this type in bytes, assuming tightly packed members.
+    ///
+    /// Scalar widths are given in bits and rounded up to whole bytes.
+    /// Structures are padded to a multiple of their alignment.
+    /// Returns `None` for opaque types, pointers, dynamically sized arrays,
+    /// and sizes that don't fit in 32 bits.
+    pub fn size(&self, types: &Arena<Type>) -> Option<u32> {
+        match *self {
+            TypeInner::Scalar { width, .. } => Some(scalar_size(width)),
+            TypeInner::Vector { size, width, .. } => (size as u32).checked_mul(scalar_size(width)),
+            TypeInner::Matrix { columns, rows, width, .. } => {
+                (columns as u32 * rows as u32).checked_mul(scalar_size(width))
+            }
+            TypeInner::Array { base, size: ArraySize::Static(length) } => {
+                length.checked_mul(types[base].inner.size(types)?)
+            }
+            TypeInner::Struct { ref members } => {
+                let mut size = 0;
+                for member in members {
+                    let end = member.offset.checked_add(types[member.ty].inner.size(types)?)?;
+                    size = size.max(end);
+                }
+                let alignment = self.alignment(types)?;
+                let padded = size.checked_add(alignment - 1)?;
+                Some(padded - padded % alignment)
+            }
+            TypeInner::Array { size: ArraySize::Dynamic, .. } |
+            TypeInner::Pointer { .. } |
+            TypeInner::Image { .. } |
+            TypeInner::Sampler { .. } => None,
+        }
+    }
+
+    /// Compute the alignment of this type in bytes.
+    ///
+    /// Two-component vectors are aligned to twice their scalar size,
+    /// and three- and four-component vectors to four times it.
+    /// Matrices are aligned like their columns, arrays like their elements,
+    /// and structures like their most aligned member.
+    /// Returns `None` for opaque types and pointers.
+    pub fn alignment(&self, types: &Arena<Type>) -> Option<u32> {
+        let vector_alignment = |size: VectorSize, width: Bytes| match size {
+            VectorSize::Bi => 2 * scalar_size(width),
+            VectorSize::Tri |
+            VectorSize::Quad => 4 * scalar_size(width),
+        };
+        match *self {
+            TypeInner::Scalar { width, .. } => Some(scalar_size(width)),
+            TypeInner::Vector { size, width, .. } => Some(vector_alignment(size, width)),
+            TypeInner::Matrix { rows, width, .. } => Some(vector_alignment(rows, width)),
+            TypeInner::Array { base, .. } => types[base].inner.alignment(types),
+            TypeInner::Struct { ref members } => {
+                let mut alignment = 1;
+                for member in members {
+                    alignment = alignment.max(types[member.ty].inner.alignment(types)?);
+                }
+                Some(alignment)
+            }
+            TypeInner::Pointer { .. } |
+            TypeInner::Image { .. } |
+            TypeInner::Sampler { .. } => None,
+        }
+    }
+}
+
+/// Number of bytes taken by a scalar of the given width in bits.
+fn scalar_size(width: Bytes) -> u32 {
+    (u32::from(width) + 7) / 8
+}
+
+/// Write a scalar type the way shading languages spell it, like `f32`.
+fn fmt_scalar(kind: ScalarKind, width: Bytes, formatter: &mut fmt::Formatter) -> fmt::Result {
+    match kind {
+        ScalarKind::Sint => write!(formatter, "i{}", width),
+        ScalarKind::Uint => write!(formatter, "u{}", width),
+        ScalarKind::Float => write!(formatter, "f{}", width),
+        ScalarKind::Bool => write!(formatter, "bool"),
+    }
+}
+
+/// Renders the type in a WGSL-like notation, like `vec3<f32>`.
+///
+/// Types referenced by handle are printed as `type#<index>`, and
+/// unnamed structures as `struct`.
+impl fmt::Display for Type {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.inner {
+            TypeInner::Scalar { kind, width } => fmt_scalar(kind, width, formatter),
+            TypeInner::Vector { size, kind, width } => {
+                write!(formatter, "vec{}<", size as u8)?;
+                fmt_scalar(kind, width, formatter)?;
+                write!(formatter, ">")
+            }
+            TypeInner::Matrix { columns, rows, kind, width } => {
+                write!(formatter, "mat{}x{}<", columns as u8, rows as u8)?;
+                fmt_scalar(kind, width, formatter)?;
+                write!(formatter, ">")
+            }
+            TypeInner::Pointer { base, class } => {
+                write!(formatter, "ptr<{:?}, type#{}>", class, base.index())
+            }
+            TypeInner::Array { base, size: ArraySize::Static(length) } => {
+                write!(formatter, "array<type#{}, {}>", base.index(), length)
+            }
+            TypeInner::Array { base, size: ArraySize::Dynamic } => {
+                write!(formatter, "array<type#{}>", base.index())
+            }
+            TypeInner::Struct { .. } => match self.name {
+                Some(ref name) => write!(formatter, "{}", name),
+                None => write!(formatter, "struct"),
+            },
+            TypeInner::Image { base, dim, .. } => {
+                write!(formatter, "image{:?}<type#{}>", dim, base.index())
+            }
+            TypeInner::Sampler { comparison: false } => write!(formatter, "sampler"),
+            TypeInner::Sampler { comparison: true } => write!(formatter, "sampler_comparison"),
+        }
+    }
+}
+
+#[derive(Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub struct Constant {
     pub name: Option<String>,
+    /// The `SpecId` of a specialization constant, which pipeline
+    /// creation can override.
     pub specialization: Option<spirv::Word>,
     pub inner: ConstantInner,
     pub ty: Handle<Type>,
 }
 
-#[derive(Debug, PartialEq)]
+/// Value of a constant.
+///
+/// Floats are compared and hashed by their bit patterns, so that `NaN`
+/// is equal to itself, and `-0.0` is distinct from `0.0`.
+#[derive(Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub enum ConstantInner {
@@ -120,13 +307,41 @@ pub enum ConstantInner {
     Composite(Vec<Handle<Constant>>),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl PartialEq for ConstantInner {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ConstantInner::Sint(a), ConstantInner::Sint(b)) => a == b,
+            (ConstantInner::Uint(a), ConstantInner::Uint(b)) => a == b,
+            (ConstantInner::Float(a), ConstantInner::Float(b)) => a.to_bits() == b.to_bits(),
+            (ConstantInner::Bool(a), ConstantInner::Bool(b)) => a == b,
+            (ConstantInner::Composite(a), ConstantInner::Composite(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ConstantInner {}
+
+impl hash::Hash for ConstantInner {
+    fn hash<H: hash::Hasher>(&self, hasher: &mut H) {
+        mem::discriminant(self).hash(hasher);
+        match *self {
+            ConstantInner::Sint(value) => value.hash(hasher),
+            ConstantInner::Uint(value) => value.hash(hasher),
+            ConstantInner::Float(value) => value.to_bits().hash(hasher),
+            ConstantInner::Bool(value) => value.hash(hasher),
+            ConstantInner::Composite(ref constituents) => constituents.hash(hasher),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub enum Binding {
     BuiltIn(spirv::BuiltIn),
     Location(spirv::Word),
-    Descriptor { set: spirv::Word, binding: spirv::Word },
+    Resource { group: spirv::Word, binding: spirv::Word },
 }
 
 bitflags::bitflags! {
@@ -138,14 +353,40 @@ bitflags::bitflags! {
     }
 }
 
+/// How a pipeline input is interpolated across a primitive.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum Interpolation {
+    /// Not interpolated, taken from the provoking vertex.
+    Flat,
+    /// Interpolated linearly in screen space, without perspective correction.
+    Linear,
+}
+
+/// Where within a pixel a pipeline input is interpolated.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum Sampling {
+    /// Interpolated at the centroid of the covered samples.
+    Centroid,
+    /// Interpolated at each sample position.
+    Sample,
+}
+
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub struct GlobalVariable {
     pub name: Option<String>,
-    pub class: spirv::StorageClass,
+    pub class: StorageClass,
     pub binding: Option<Binding>,
+    pub interpolation: Option<Interpolation>,
+    pub sampling: Option<Sampling>,
     pub ty: Handle<Type>,
+    /// Initial value, for variables that have one.
+    pub init: Option<Handle<Constant>>,
 }
 
 #[derive(Clone, Debug)]
@@ -202,6 +443,62 @@ pub enum IntrinsicFunction {
     IsNormal,
 }
 
+/// Built-in mathematical function, like the ones of GLSL.std.450.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum MathFunction {
+    // comparison
+    Abs,
+    Min,
+    Max,
+    Clamp,
+    // trigonometry
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    // decomposition
+    Floor,
+    Ceil,
+    Round,
+    Fract,
+    Trunc,
+    // exponent
+    Exp,
+    Exp2,
+    Log,
+    Log2,
+    Pow,
+    // geometry
+    Distance,
+    Length,
+    Normalize,
+    Reflect,
+    // computational
+    Sign,
+    Sqrt,
+    InverseSqrt,
+    Mix,
+    Step,
+    SmoothStep,
+}
+
+/// Component of a vector picked by a swizzle.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum SwizzleComponent {
+    X = 0,
+    Y = 1,
+    Z = 2,
+    W = 3,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
@@ -211,6 +508,17 @@ pub enum DerivativeAxis {
     Width,
 }
 
+/// The function invoked by a call expression.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum FunctionOrigin {
+    /// A function of this module.
+    Local(Handle<Function>),
+    /// A function provided by the target, like a standard library one.
+    External(String),
+}
+
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
@@ -224,6 +532,16 @@ pub enum Expression {
         index: u32,
     },
     Constant(Handle<Constant>),
+    /// Components of a vector, rearranged into a new vector.
+    ///
+    /// Only the first `size` entries of the `pattern` are meaningful.
+    Swizzle {
+        size: VectorSize,
+        vector: Handle<Expression>,
+        pattern: [SwizzleComponent; 4],
+    },
+    /// An undefined value of the given type.
+    Undef(Handle<Type>),
     Compose {
         ty: Handle<Type>,
         components: Vec<Handle<Expression>>,
@@ -248,6 +566,12 @@ pub enum Expression {
         left: Handle<Expression>,
         right: Handle<Expression>,
     },
+    /// Choose between two values without control flow.
+    Select {
+        condition: Handle<Expression>,
+        accept: Handle<Expression>,
+        reject: Handle<Expression>,
+    },
     Intrinsic {
         fun: IntrinsicFunction,
         argument: Handle<Expression>,
@@ -260,18 +584,42 @@ pub enum Expression {
         expr: Handle<Expression>,
     },
     Call {
-        name: String,
+        origin: FunctionOrigin,
+        arguments: Vec<Handle<Expression>>,
+    },
+    Math {
+        fun: MathFunction,
         arguments: Vec<Handle<Expression>>,
     },
+    /// Cast a scalar or vector to another scalar kind.
+    ///
+    /// With `convert` set, the value is converted, otherwise its bits
+    /// are reinterpreted.
+    As {
+        expr: Handle<Expression>,
+        kind: ScalarKind,
+        convert: bool,
+    },
+}
+
+/// A location in the shader source.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub struct Span {
+    /// Name of the source file, if known.
+    pub file: Option<String>,
+    pub line: u32,
+    pub column: u32,
 }
 
 pub type Block = Vec<Statement>;
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub struct FallThrough;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub enum Statement {
@@ -302,6 +650,14 @@ pub enum Statement {
         pointer: Handle<Expression>,
         value: Handle<Expression>,
     },
+    /// A call to a function whose result, if any, is unused.
+    Call {
+        function: Handle<Function>,
+        arguments: Vec<Handle<Expression>>,
+    },
+    /// Set the source location of the following statements in the block,
+    /// or clear it with `None`.
+    Line(Option<Span>),
 }
 
 #[derive(Debug)]
@@ -315,16 +671,29 @@ pub struct Function {
     pub global_usage: Vec<GlobalUse>,
     pub local_variables: Arena<LocalVariable>,
     pub expressions: Arena<Expression>,
+    /// Source locations of the expressions, where known.
+    pub expression_spans: FastHashMap<Handle<Expression>, Span>,
     pub body: Block,
 }
 
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[cfg_attr(feature = "deserialize", derive(serde::Deserialize))]
 pub struct EntryPoint {
-    pub exec_model: spirv::ExecutionModel,
+    pub stage: ShaderStage,
     pub name: String,
     pub function: Handle<Function>,
+    /// Local workgroup size of a compute entry point, or `[0, 0, 0]` otherwise.
+    pub workgroup_size: [u32; 3],
 }
 
 #[derive(Debug)]
@@ -338,3 +707,260 @@ pub struct Module {
     pub functions: Arena<Function>,
     pub entry_points: Vec<EntryPoint>,
 }
+
+impl Module {
+    /// Create a module with the given header and nothing else in it.
+    pub fn new(header: Header) -> Self {
+        Module {
+            header,
+            types: Arena::new(),
+            constants: Arena::new(),
+            global_variables: Arena::new(),
+            functions: Arena::new(),
+            entry_points: Vec::new(),
+        }
+    }
+
+    /// Find the first entry point with the given name and stage.
+    ///
+    /// Both are needed, since entry points of different stages may share a name.
+    pub fn entry_point(&self, name: &str, stage: ShaderStage) -> Option<&EntryPoint> {
+        self.entry_points
+            .iter()
+            .find(|ep| ep.name == name && ep.stage == stage)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn constant_deduplication() {
+        let mut types = crate::Arena::new();
+        let ty = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let float = |value| crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Float(value),
+            ty,
+        };
+        let mut constants = crate::Arena::new();
+        let zero = constants.fetch_or_append(float(0.0));
+        assert_eq!(constants.fetch_or_append(float(0.0)), zero);
+        assert_ne!(constants.fetch_or_append(float(-0.0)), zero);
+        let nan = constants.fetch_or_append(float(std::f64::NAN));
+        assert_eq!(constants.fetch_or_append(float(std::f64::NAN)), nan);
+        assert_eq!(constants.len(), 3);
+    }
+
+    #[test]
+    fn header_version() {
+        let header = crate::Header::default();
+        assert_eq!(header.version, (1, 0, 0));
+        assert_eq!(header.generator, 0);
+        assert!(header.version_at_least(1, 0));
+        assert!(!header.version_at_least(1, 1));
+
+        let header = crate::Header { version: (1, 3, 2), generator: 0 };
+        assert!(header.version_at_least(1, 2));
+        assert!(header.version_at_least(1, 3));
+        assert!(!header.version_at_least(1, 4));
+        assert!(!header.version_at_least(2, 0));
+    }
+
+    #[test]
+    fn vector_size_by_value() {
+        let size = crate::VectorSize::Tri;
+        let copy = size;
+        let components = match copy {
+            crate::VectorSize::Bi => 2,
+            crate::VectorSize::Tri => 3,
+            crate::VectorSize::Quad => 4,
+        };
+        assert_eq!(components, size as u8);
+
+        let mut kinds = crate::FastHashSet::default();
+        kinds.insert((crate::ScalarKind::Float, size));
+        kinds.insert((crate::ScalarKind::Float, copy));
+        assert_eq!(kinds.len(), 1);
+    }
+
+    #[test]
+    fn fetch_or_append_type() {
+        let mut types = crate::Arena::new();
+        let float = crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 4 },
+        };
+        let t1 = types.fetch_or_append(float.clone());
+        let t2 = types.fetch_or_append(float);
+        assert_eq!(t1, t2);
+        assert_eq!(types.len(), 1);
+    }
+
+    #[test]
+    fn type_size() {
+        let mut types = crate::Arena::new();
+        let vec3 = crate::TypeInner::Vector {
+            size: crate::VectorSize::Tri,
+            kind: crate::ScalarKind::Float,
+            width: 32,
+        };
+        assert_eq!(vec3.size(&types), Some(12));
+        assert_eq!(vec3.alignment(&types), Some(16));
+
+        let mat4 = crate::TypeInner::Matrix {
+            columns: crate::VectorSize::Quad,
+            rows: crate::VectorSize::Quad,
+            kind: crate::ScalarKind::Float,
+            width: 32,
+        };
+        assert_eq!(mat4.size(&types), Some(64));
+        assert_eq!(mat4.alignment(&types), Some(16));
+
+        let base = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Bi,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        });
+        let array = crate::TypeInner::Array { base, size: crate::ArraySize::Static(5) };
+        assert_eq!(array.size(&types), Some(40));
+        assert_eq!(array.alignment(&types), Some(8));
+
+        let array_ty = types.append(crate::Type { name: None, inner: array });
+        let structure = crate::TypeInner::Struct {
+            members: vec![crate::StructMember {
+                name: None,
+                binding: None,
+                ty: base,
+                offset: 0,
+            }, crate::StructMember {
+                name: None,
+                binding: None,
+                ty: array_ty,
+                offset: 16,
+            }],
+        };
+        assert_eq!(structure.size(&types), Some(56));
+        assert_eq!(structure.alignment(&types), Some(8));
+
+        // members don't have to be in order, and the tail is padded
+        let vec3_ty = types.append(crate::Type { name: None, inner: vec3 });
+        let padded = crate::TypeInner::Struct {
+            members: vec![crate::StructMember {
+                name: None,
+                binding: None,
+                ty: vec3_ty,
+                offset: 16,
+            }, crate::StructMember {
+                name: None,
+                binding: None,
+                ty: base,
+                offset: 0,
+            }],
+        };
+        assert_eq!(padded.size(&types), Some(32));
+
+        let huge = crate::TypeInner::Array { base, size: crate::ArraySize::Static(std::u32::MAX) };
+        assert_eq!(huge.size(&types), None);
+        let dynamic = crate::TypeInner::Array { base, size: crate::ArraySize::Dynamic };
+        assert_eq!(dynamic.size(&types), None);
+        assert_eq!(dynamic.alignment(&types), Some(8));
+    }
+
+    #[test]
+    fn entry_point_lookup() {
+        let mut module = crate::Module::new(crate::Header::default());
+        let vertex = module.functions.append(crate::Function {
+            name: None,
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: crate::Arena::new(),
+            expressions: crate::Arena::new(),
+            expression_spans: crate::FastHashMap::default(),
+            body: Vec::new(),
+        });
+        let fragment = module.functions.append(crate::Function {
+            name: None,
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: crate::Arena::new(),
+            expressions: crate::Arena::new(),
+            expression_spans: crate::FastHashMap::default(),
+            body: Vec::new(),
+        });
+        for &(stage, function) in &[
+            (crate::ShaderStage::Vertex, vertex),
+            (crate::ShaderStage::Fragment, fragment),
+        ] {
+            module.entry_points.push(crate::EntryPoint {
+                stage,
+                name: "main".to_owned(),
+                function,
+                workgroup_size: [0; 3],
+            });
+        }
+        let ep = module.entry_point("main", crate::ShaderStage::Vertex).unwrap();
+        assert_eq!(ep.function, vertex);
+        let ep = module.entry_point("main", crate::ShaderStage::Fragment).unwrap();
+        assert_eq!(ep.function, fragment);
+        assert!(module.entry_point("main", crate::ShaderStage::Compute).is_none());
+        assert!(module.entry_point("other", crate::ShaderStage::Vertex).is_none());
+    }
+
+    #[test]
+    fn type_display() {
+        let float = crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        };
+        assert_eq!(float.to_string(), "f32");
+
+        let vec3 = crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Tri,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        };
+        assert_eq!(vec3.to_string(), "vec3<f32>");
+
+        let named = crate::Type {
+            name: Some("Light".to_owned()),
+            inner: crate::TypeInner::Struct { members: Vec::new() },
+        };
+        assert_eq!(named.to_string(), "Light");
+    }
+
+    #[test]
+    fn module_new() {
+        let mut module = crate::Module::new(crate::Header::default());
+        assert_eq!(module.header.version, (1, 0, 0));
+        assert_eq!(module.types.len(), 0);
+        assert!(module.entry_points.is_empty());
+
+        let handle = module.functions.append(crate::Function {
+            name: Some("main".to_owned()),
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: crate::Arena::new(),
+            expressions: crate::Arena::new(),
+            expression_spans: crate::FastHashMap::default(),
+            body: Vec::new(),
+        });
+        assert_eq!(module.functions.len(), 1);
+        assert_eq!(module.functions[handle].name.as_deref(), Some("main"));
+    }
+}
@@ -18,7 +18,11 @@ impl<'a> Interface<'a> {
             E::AccessIndex { base, .. } => {
                 self.add_inputs(base);
             }
-            E::Constant(_) => {}
+            E::Swizzle { vector, .. } => {
+                self.add_inputs(vector);
+            }
+            E::Constant(_) |
+            E::Undef(_) => {}
             E::Compose { ref components, .. } => {
                 for &comp in components {
                     self.add_inputs(comp);
@@ -44,6 +48,11 @@ impl<'a> Interface<'a> {
                 self.add_inputs(left);
                 self.add_inputs(right);
             }
+            E::Select { condition, accept, reject } => {
+                self.add_inputs(condition);
+                self.add_inputs(accept);
+                self.add_inputs(reject);
+            }
             E::Intrinsic { argument, .. } => {
                 self.add_inputs(argument);
             }
@@ -55,10 +64,12 @@ impl<'a> Interface<'a> {
                 self.add_inputs(left);
                 self.add_inputs(right);
             }
-            E::Derivative { expr, .. } => {
+            E::Derivative { expr, .. } |
+            E::As { expr, .. } => {
                 self.add_inputs(expr);
             }
-            E::Call { ref arguments, .. } => {
+            E::Call { ref arguments, .. } |
+            E::Math { ref arguments, .. } => {
                 for &argument in arguments {
                     self.add_inputs(argument);
                 }
@@ -71,6 +82,7 @@ impl<'a> Interface<'a> {
             use crate::Statement as S;
             match *statement {
                 S::Empty |
+                S::Line(_) |
                 S::Break |
                 S::Continue |
                 S::Kill => (),
@@ -98,6 +110,11 @@ impl<'a> Interface<'a> {
                         self.add_inputs(expr);
                     }
                 }
+                S::Call { ref arguments, .. } => {
+                    for &argument in arguments {
+                        self.add_inputs(argument);
+                    }
+                }
                 S::Store { pointer, value } => {
                     let mut left = pointer;
                     loop {
@@ -137,3 +154,60 @@ impl crate::GlobalUse {
         io.uses
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        arena::Arena,
+        Expression as E,
+        Statement as S,
+    };
+
+    #[test]
+    fn scan_switch_and_loop() {
+        let mut types = Arena::new();
+        let ty = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Sint, width: 4 },
+        });
+        let mut globals = Arena::new();
+        let mut expressions = Arena::new();
+        let mut pointers = Vec::new();
+        for _ in 0 .. 3 {
+            let var = globals.append(crate::GlobalVariable {
+                name: None,
+                class: crate::StorageClass::Private,
+                binding: None,
+                interpolation: None,
+                sampling: None,
+                ty,
+                init: None,
+            });
+            pointers.push(expressions.append(E::GlobalVariable(var)));
+        }
+        let selector = expressions.append(E::FunctionParameter(0));
+
+        let mut cases = crate::FastHashMap::default();
+        cases.insert(0, (vec![
+            S::Store { pointer: pointers[0], value: selector },
+            S::Break,
+        ], None));
+        let body = vec![
+            S::Switch { selector, cases, default: Vec::new() },
+            S::Loop {
+                body: vec![
+                    S::Store { pointer: pointers[1], value: selector },
+                    S::Continue,
+                ],
+                continuing: Vec::new(),
+            },
+        ];
+
+        let uses = crate::GlobalUse::scan(&expressions, &body, &globals);
+        assert_eq!(uses, vec![
+            crate::GlobalUse::STORE,
+            crate::GlobalUse::STORE,
+            crate::GlobalUse::empty(),
+        ]);
+    }
+}
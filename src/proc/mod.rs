@@ -1,4 +1,6 @@
 mod interface;
 mod typifier;
+mod validate;
 
-pub use typifier::{ResolveError, Typifier};
+pub use typifier::{resolve_type, ResolveError, TypeResolution, Typifier};
+pub use validate::{ValidationError, Validator};
@@ -7,6 +7,16 @@ pub struct Typifier {
 #[derive(Debug)]
 pub enum ResolveError {
     InvalidAccessIndex,
+    InvalidFunctionParameter,
+    FunctionReturnsVoid,
+    /// The expression refers to itself, or to an expression after it.
+    ForwardReference(Handle<crate::Expression>),
+    /// An operand of the expression has a type that it can't take.
+    InvalidOperand(Handle<crate::Expression>),
+    /// The operands of the binary expression have mismatching types.
+    IncompatibleOperands(Handle<crate::Expression>),
+    InvalidFunction(Handle<crate::Function>),
+    UnknownExternalFunction(String),
 }
 
 impl Typifier {
@@ -16,6 +26,7 @@ impl Typifier {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn resolve(
         &mut self,
         expr_handle: Handle<crate::Expression>,
@@ -24,18 +35,27 @@ impl Typifier {
         constants: &Arena<crate::Constant>,
         global_vars: &Arena<crate::GlobalVariable>,
         local_vars: &Arena<crate::LocalVariable>,
+        functions: &Arena<crate::Function>,
+        parameter_types: &[Handle<crate::Type>],
     ) -> Result<Handle<crate::Type>, ResolveError> {
         if self.types.len() <= expr_handle.index() {
             for (eh, expr) in expressions.iter().skip(self.types.len()) {
+                let resolved = |handle: Handle<crate::Expression>| {
+                    self.types
+                        .get(handle.index())
+                        .cloned()
+                        .ok_or(ResolveError::ForwardReference(eh))
+                };
+                let invalid_operand = ResolveError::InvalidOperand(eh);
                 let ty = match *expr {
                     crate::Expression::Access { base, .. } => {
-                        match types[self.types[base.index()]].inner {
+                        match types[resolved(base)?].inner {
                             crate::TypeInner::Array { base, .. } => base,
-                            ref other => panic!("Can't access into {:?}", other),
+                            _ => return Err(invalid_operand),
                         }
                     }
                     crate::Expression::AccessIndex { base, index } => {
-                        match types[self.types[base.index()]].inner {
+                        match types[resolved(base)?].inner {
                             crate::TypeInner::Vector { size, kind, width } => {
                                 if index >= size as u32 {
                                     return Err(ResolveError::InvalidAccessIndex)
@@ -56,28 +76,59 @@ impl Typifier {
                                     .ok_or(ResolveError::InvalidAccessIndex)?
                                     .ty
                             }
-                            ref other => panic!("Can't access into {:?}", other),
+                            _ => return Err(invalid_operand),
+                        }
+                    }
+                    crate::Expression::Swizzle { size, vector, .. } => {
+                        match types[resolved(vector)?].inner {
+                            crate::TypeInner::Vector { kind, width, .. } => {
+                                let inner = crate::TypeInner::Vector { size, kind, width };
+                                Self::deduce_type_handle(inner, types)
+                            }
+                            _ => return Err(invalid_operand),
                         }
                     }
                     crate::Expression::Constant(h) => constants[h].ty,
+                    crate::Expression::Undef(ty) => ty,
                     crate::Expression::Compose { ty, .. } => ty,
-                    crate::Expression::FunctionParameter(_) => unimplemented!(),
+                    crate::Expression::FunctionParameter(index) => {
+                        *parameter_types.get(index as usize)
+                            .ok_or(ResolveError::InvalidFunctionParameter)?
+                    }
                     crate::Expression::GlobalVariable(h) => global_vars[h].ty,
                     crate::Expression::LocalVariable(h) => local_vars[h].ty,
-                    crate::Expression::Load { .. } => unimplemented!(),
-                    crate::Expression::ImageSample { .. } => unimplemented!(),
-                    crate::Expression::Unary { expr, .. } => self.types[expr.index()],
+                    crate::Expression::Load { pointer } => {
+                        let ty = resolved(pointer)?;
+                        match types[ty].inner {
+                            crate::TypeInner::Pointer { base, .. } => base,
+                            _ => ty,
+                        }
+                    }
+                    crate::Expression::ImageSample { image, .. } => {
+                        let base = match types[resolved(image)?].inner {
+                            crate::TypeInner::Image { base, .. } => base,
+                            _ => return Err(invalid_operand),
+                        };
+                        match types[base].inner {
+                            crate::TypeInner::Scalar { kind, width } => {
+                                let inner = crate::TypeInner::Vector { size: crate::VectorSize::Quad, kind, width };
+                                Self::deduce_type_handle(inner, types)
+                            }
+                            _ => base,
+                        }
+                    }
+                    crate::Expression::Unary { expr, .. } => resolved(expr)?,
                     crate::Expression::Binary { op, left, right } => {
                         match op {
                             crate::BinaryOperator::Add |
                             crate::BinaryOperator::Subtract |
                             crate::BinaryOperator::Divide |
                             crate::BinaryOperator::Modulo => {
-                                self.types[left.index()]
+                                resolved(left)?
                             }
                             crate::BinaryOperator::Multiply => {
-                                let ty_left = self.types[left.index()];
-                                let ty_right = self.types[right.index()];
+                                let ty_left = resolved(left)?;
+                                let ty_right = resolved(right)?;
                                 if ty_left == ty_right {
                                     ty_left
                                 } else if let crate::TypeInner::Scalar { .. } = types[ty_right].inner {
@@ -86,7 +137,7 @@ impl Typifier {
                                     let inner = crate::TypeInner::Vector { size: columns, kind, width};
                                     Self::deduce_type_handle(inner, types)
                                 } else {
-                                    panic!("Incompatible arguments {:?} x {:?}", types[ty_left], types[ty_right]);
+                                    return Err(ResolveError::IncompatibleOperands(eh));
                                 }
                             }
                             crate::BinaryOperator::Equal |
@@ -94,10 +145,19 @@ impl Typifier {
                             crate::BinaryOperator::Less |
                             crate::BinaryOperator::LessEqual |
                             crate::BinaryOperator::Greater |
-                            crate::BinaryOperator::GreaterEqual |
+                            crate::BinaryOperator::GreaterEqual => {
+                                let kind = crate::ScalarKind::Bool;
+                                let width = 1;
+                                let inner = match types[resolved(left)?].inner {
+                                    crate::TypeInner::Scalar { .. } => crate::TypeInner::Scalar { kind, width },
+                                    crate::TypeInner::Vector { size, .. } => crate::TypeInner::Vector { size, kind, width },
+                                    _ => return Err(invalid_operand),
+                                };
+                                Self::deduce_type_handle(inner, types)
+                            }
                             crate::BinaryOperator::LogicalAnd |
                             crate::BinaryOperator::LogicalOr => {
-                                self.types[left.index()]
+                                resolved(left)?
                             }
                             crate::BinaryOperator::And |
                             crate::BinaryOperator::ExclusiveOr |
@@ -105,30 +165,89 @@ impl Typifier {
                             crate::BinaryOperator::ShiftLeftLogical |
                             crate::BinaryOperator::ShiftRightLogical |
                             crate::BinaryOperator::ShiftRightArithmetic => {
-                                self.types[left.index()]
+                                resolved(left)?
                             }
                         }
                     }
-                    crate::Expression::Intrinsic { .. } => unimplemented!(),
-                    crate::Expression::DotProduct(_, _) => unimplemented!(),
-                    crate::Expression::CrossProduct(_, _) => unimplemented!(),
-                    crate::Expression::Derivative { .. } => unimplemented!(),
-                    crate::Expression::Call { ref name, ref arguments } => {
+                    crate::Expression::Select { accept, .. } => resolved(accept)?,
+                    crate::Expression::Intrinsic { fun, argument } => {
+                        let kind = crate::ScalarKind::Bool;
+                        let width = 1;
+                        let inner = match (fun, &types[resolved(argument)?].inner) {
+                            (crate::IntrinsicFunction::Any, _) |
+                            (crate::IntrinsicFunction::All, _) |
+                            (_, &crate::TypeInner::Scalar { .. }) => crate::TypeInner::Scalar { kind, width },
+                            (_, &crate::TypeInner::Vector { size, .. }) => crate::TypeInner::Vector { size, kind, width },
+                            (_, _) => return Err(invalid_operand),
+                        };
+                        Self::deduce_type_handle(inner, types)
+                    }
+                    crate::Expression::DotProduct(left, _) => {
+                        let inner = match types[resolved(left)?].inner {
+                            crate::TypeInner::Vector { kind, width, .. } => crate::TypeInner::Scalar { kind, width },
+                            _ => return Err(invalid_operand),
+                        };
+                        Self::deduce_type_handle(inner, types)
+                    }
+                    crate::Expression::CrossProduct(left, _) => resolved(left)?,
+                    crate::Expression::Derivative { expr, .. } => resolved(expr)?,
+                    crate::Expression::Call { origin: crate::FunctionOrigin::Local(function), .. } => {
+                        functions
+                            .try_get(function)
+                            .ok_or(ResolveError::InvalidFunction(function))?
+                            .return_type
+                            .ok_or(ResolveError::FunctionReturnsVoid)?
+                    }
+                    crate::Expression::Call { origin: crate::FunctionOrigin::External(ref name), ref arguments } => {
+                        let first = *arguments.first().ok_or(ResolveError::InvalidOperand(eh))?;
                         match name.as_str() {
                             "distance" | "length" => {
-                                let ty_handle = self.types[arguments[0].index()];
-                                let inner = match types[ty_handle].inner {
+                                let argument = resolved(first)?;
+                                match types[argument].inner {
+                                    crate::TypeInner::Scalar { .. } => argument,
                                     crate::TypeInner::Vector { kind, width, .. } => {
-                                        crate::TypeInner::Scalar { kind, width }
+                                        let inner = crate::TypeInner::Scalar { kind, width };
+                                        Self::deduce_type_handle(inner, types)
                                     }
-                                    ref other => panic!("Unexpected argument {:?}", other),
-                                };
-                                Self::deduce_type_handle(inner, types)
+                                    _ => return Err(invalid_operand),
+                                }
                             }
-                            "normalize" | "fclamp" => self.types[arguments[0].index()],
-                            _ => panic!("Unknown '{}' call", name),
+                            "normalize" | "fclamp" => resolved(first)?,
+                            _ => return Err(ResolveError::UnknownExternalFunction(name.clone())),
                         }
                     }
+                    crate::Expression::Math { fun, ref arguments } => {
+                        let (&first, &last) = match (arguments.first(), arguments.last()) {
+                            (Some(first), Some(last)) => (first, last),
+                            _ => return Err(invalid_operand),
+                        };
+                        match fun {
+                            crate::MathFunction::Distance |
+                            crate::MathFunction::Length => {
+                                let argument = resolved(first)?;
+                                match types[argument].inner {
+                                    crate::TypeInner::Scalar { .. } => argument,
+                                    crate::TypeInner::Vector { kind, width, .. } => {
+                                        let inner = crate::TypeInner::Scalar { kind, width };
+                                        Self::deduce_type_handle(inner, types)
+                                    }
+                                    _ => return Err(invalid_operand),
+                                }
+                            }
+                            // step and smoothstep take the value last
+                            crate::MathFunction::Step |
+                            crate::MathFunction::SmoothStep => resolved(last)?,
+                            _ => resolved(first)?,
+                        }
+                    }
+                    crate::Expression::As { expr, kind, convert: _ } => {
+                        let inner = match types[resolved(expr)?].inner {
+                            crate::TypeInner::Scalar { kind: _, width } => crate::TypeInner::Scalar { kind, width },
+                            crate::TypeInner::Vector { size, kind: _, width } => crate::TypeInner::Vector { size, kind, width },
+                            _ => return Err(invalid_operand),
+                        };
+                        Self::deduce_type_handle(inner, types)
+                    }
                 };
                 log::debug!("Resolving {:?} = {:?} : {:?}", eh, expr, ty);
                 self.types.push(ty);
@@ -153,3 +272,151 @@ impl Typifier {
         })
     }
 }
+
+/// Type of an expression, as resolved by `resolve_type`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeResolution {
+    /// A type of the module.
+    Handle(Handle<crate::Type>),
+    /// A type that the module doesn't have, like the boolean result
+    /// of a comparison.
+    Value(crate::TypeInner),
+}
+
+impl TypeResolution {
+    pub fn inner<'a>(&'a self, types: &'a Arena<crate::Type>) -> &'a crate::TypeInner {
+        match *self {
+            TypeResolution::Handle(handle) => &types[handle].inner,
+            TypeResolution::Value(ref inner) => inner,
+        }
+    }
+}
+
+/// Resolve the type of a single expression of `function`.
+///
+/// The module is left untouched: types that it doesn't have are
+/// returned by value.
+pub fn resolve_type(
+    expr: Handle<crate::Expression>,
+    function: &crate::Function,
+    module: &crate::Module,
+) -> Result<TypeResolution, ResolveError> {
+    let mut types = module.types.clone();
+    let handle = Typifier::new().resolve(
+        expr,
+        &function.expressions,
+        &mut types,
+        &module.constants,
+        &module.global_variables,
+        &function.local_variables,
+        &module.functions,
+        &function.parameter_types,
+    )?;
+    Ok(if handle.index() < module.types.len() {
+        TypeResolution::Handle(handle)
+    } else {
+        TypeResolution::Value(types[handle].inner.clone())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{resolve_type, TypeResolution};
+    use crate::Expression as E;
+
+    fn function(parameter_types: Vec<crate::Handle<crate::Type>>) -> crate::Function {
+        crate::Function {
+            name: None,
+            control: spirv::FunctionControl::empty(),
+            parameter_types,
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: crate::Arena::new(),
+            expressions: crate::Arena::new(),
+            expression_spans: crate::FastHashMap::default(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn binary() {
+        let mut module = crate::Module::new(crate::Header::default());
+        let float = crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 };
+        let float_ty = module.types.append(crate::Type { name: None, inner: float });
+        let int_vec_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Tri,
+                kind: crate::ScalarKind::Sint,
+                width: 32,
+            },
+        });
+        let mut fun = function(vec![float_ty, int_vec_ty]);
+        let x = fun.expressions.append(E::FunctionParameter(0));
+        let v = fun.expressions.append(E::FunctionParameter(1));
+        let sum = fun.expressions.append(E::Binary { op: crate::BinaryOperator::Add, left: x, right: x });
+        let less = fun.expressions.append(E::Binary { op: crate::BinaryOperator::Less, left: v, right: v });
+
+        assert_eq!(resolve_type(sum, &fun, &module).unwrap(), TypeResolution::Handle(float_ty));
+        let less_ty = resolve_type(less, &fun, &module).unwrap();
+        assert_eq!(less_ty, TypeResolution::Value(crate::TypeInner::Vector {
+            size: crate::VectorSize::Tri,
+            kind: crate::ScalarKind::Bool,
+            width: 1,
+        }));
+        assert_eq!(less_ty.inner(&module.types), &crate::TypeInner::Vector {
+            size: crate::VectorSize::Tri,
+            kind: crate::ScalarKind::Bool,
+            width: 1,
+        });
+        assert_eq!(module.types.len(), 2);
+    }
+
+    #[test]
+    fn compose() {
+        let mut module = crate::Module::new(crate::Header::default());
+        let float_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let vec2_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Bi,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        });
+        let mut fun = function(vec![float_ty]);
+        let x = fun.expressions.append(E::FunctionParameter(0));
+        let composite = fun.expressions.append(E::Compose { ty: vec2_ty, components: vec![x, x] });
+
+        assert_eq!(resolve_type(composite, &fun, &module).unwrap(), TypeResolution::Handle(vec2_ty));
+        assert_eq!(resolve_type(x, &fun, &module).unwrap().inner(&module.types), &module.types[float_ty].inner);
+    }
+
+    #[test]
+    fn length() {
+        let mut module = crate::Module::new(crate::Header::default());
+        let float = crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 };
+        let float_ty = module.types.append(crate::Type { name: None, inner: float });
+        let vec3_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Tri,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        });
+        let mut fun = function(vec![float_ty, vec3_ty]);
+        let x = fun.expressions.append(E::FunctionParameter(0));
+        let v = fun.expressions.append(E::FunctionParameter(1));
+        let scalar_length = fun.expressions.append(E::Math { fun: crate::MathFunction::Length, arguments: vec![x] });
+        let vector_length = fun.expressions.append(E::Math { fun: crate::MathFunction::Length, arguments: vec![v] });
+        let distance = fun.expressions.append(E::Math { fun: crate::MathFunction::Distance, arguments: vec![x, x] });
+
+        assert_eq!(resolve_type(scalar_length, &fun, &module).unwrap(), TypeResolution::Handle(float_ty));
+        assert_eq!(resolve_type(vector_length, &fun, &module).unwrap().inner(&module.types), &module.types[float_ty].inner);
+        assert_eq!(resolve_type(distance, &fun, &module).unwrap(), TypeResolution::Handle(float_ty));
+    }
+}
@@ -0,0 +1,711 @@
+use crate::arena::{Arena, Handle};
+use super::{ResolveError, Typifier};
+
+pub struct Validator {}
+
+#[derive(Debug)]
+pub enum ValidationError {
+    InvalidTypeHandle(Handle<crate::Type>),
+    InvalidConstantHandle(Handle<crate::Constant>),
+    InvalidGlobalVariableHandle(Handle<crate::GlobalVariable>),
+    InvalidFunctionHandle(Handle<crate::Function>),
+    /// A scalar constant has a width that doesn't fit its value kind.
+    InvalidScalarWidth {
+        constant: Handle<crate::Constant>,
+        width: crate::Bytes,
+    },
+    /// The module has entry points, but no functions for them to refer to.
+    MissingFunctions,
+    /// A built-in global used by an entry point isn't available
+    /// for its stage and storage class.
+    InvalidBuiltIn {
+        stage: crate::ShaderStage,
+        variable: Handle<crate::GlobalVariable>,
+    },
+    InvalidLocalVariableHandle {
+        function: Handle<crate::Function>,
+        handle: Handle<crate::LocalVariable>,
+    },
+    InvalidExpressionHandle {
+        function: Handle<crate::Function>,
+        handle: Handle<crate::Expression>,
+    },
+    InvalidResolve {
+        function: Handle<crate::Function>,
+        error: ResolveError,
+    },
+    /// A call used as a value is made to a function that doesn't return one.
+    VoidFunctionCall {
+        function: Handle<crate::Function>,
+        callee: Handle<crate::Function>,
+    },
+    ReturnTypeMismatch {
+        function: Handle<crate::Function>,
+        expected: Option<Handle<crate::Type>>,
+        value: Option<Handle<crate::Expression>>,
+    },
+}
+
+struct FunctionValidator<'a> {
+    module: &'a crate::Module,
+    handle: Handle<crate::Function>,
+    fun: &'a crate::Function,
+    typifier: Typifier,
+    /// Scratch copy of the module types, which the typifier may extend.
+    types: &'a mut Arena<crate::Type>,
+}
+
+impl FunctionValidator<'_> {
+    fn check_type(&self, handle: Handle<crate::Type>) -> Result<(), ValidationError> {
+        check(&self.module.types, handle, ValidationError::InvalidTypeHandle)
+    }
+
+    fn check_expression(&self, handle: Handle<crate::Expression>) -> Result<(), ValidationError> {
+        check(&self.fun.expressions, handle, |handle| ValidationError::InvalidExpressionHandle {
+            function: self.handle,
+            handle,
+        })
+    }
+
+    fn check_local_variable(&self, handle: Handle<crate::LocalVariable>) -> Result<(), ValidationError> {
+        check(&self.fun.local_variables, handle, |handle| ValidationError::InvalidLocalVariableHandle {
+            function: self.handle,
+            handle,
+        })
+    }
+
+    fn validate_expression(&self, expression: &crate::Expression) -> Result<(), ValidationError> {
+        use crate::Expression as E;
+        match *expression {
+            E::Access { base, index } => {
+                self.check_expression(base)?;
+                self.check_expression(index)
+            }
+            E::AccessIndex { base, .. } => self.check_expression(base),
+            E::Swizzle { vector, .. } => self.check_expression(vector),
+            E::Constant(handle) => {
+                check(&self.module.constants, handle, ValidationError::InvalidConstantHandle)
+            }
+            E::Undef(ty) => self.check_type(ty),
+            E::Compose { ty, ref components } => {
+                self.check_type(ty)?;
+                components.iter().try_for_each(|&comp| self.check_expression(comp))
+            }
+            E::FunctionParameter(_) => Ok(()),
+            E::GlobalVariable(handle) => {
+                check(&self.module.global_variables, handle, ValidationError::InvalidGlobalVariableHandle)
+            }
+            E::LocalVariable(handle) => self.check_local_variable(handle),
+            E::Load { pointer } => self.check_expression(pointer),
+            E::ImageSample { image, sampler, coordinate } => {
+                self.check_expression(image)?;
+                self.check_expression(sampler)?;
+                self.check_expression(coordinate)
+            }
+            E::Unary { expr, .. } => self.check_expression(expr),
+            E::Binary { left, right, .. } => {
+                self.check_expression(left)?;
+                self.check_expression(right)
+            }
+            E::Select { condition, accept, reject } => {
+                self.check_expression(condition)?;
+                self.check_expression(accept)?;
+                self.check_expression(reject)
+            }
+            E::Intrinsic { argument, .. } => self.check_expression(argument),
+            E::DotProduct(left, right) |
+            E::CrossProduct(left, right) => {
+                self.check_expression(left)?;
+                self.check_expression(right)
+            }
+            E::Derivative { expr, .. } |
+            E::As { expr, .. } => self.check_expression(expr),
+            E::Call { origin: crate::FunctionOrigin::Local(callee), ref arguments } => {
+                check(&self.module.functions, callee, ValidationError::InvalidFunctionHandle)?;
+                if self.module.functions[callee].return_type.is_none() {
+                    return Err(ValidationError::VoidFunctionCall {
+                        function: self.handle,
+                        callee,
+                    });
+                }
+                arguments.iter().try_for_each(|&argument| self.check_expression(argument))
+            }
+            E::Call { origin: crate::FunctionOrigin::External(_), ref arguments } |
+            E::Math { ref arguments, .. } => {
+                arguments.iter().try_for_each(|&argument| self.check_expression(argument))
+            }
+        }
+    }
+
+    fn validate_return(&mut self, value: Option<Handle<crate::Expression>>) -> Result<(), ValidationError> {
+        let resolved = match value {
+            Some(expr) => Some(self.typifier
+                .resolve(
+                    expr,
+                    &self.fun.expressions,
+                    self.types,
+                    &self.module.constants,
+                    &self.module.global_variables,
+                    &self.fun.local_variables,
+                    &self.module.functions,
+                    &self.fun.parameter_types,
+                )
+                .map_err(|error| ValidationError::InvalidResolve { function: self.handle, error })?
+            ),
+            None => None,
+        };
+        let matches = match (resolved, self.fun.return_type) {
+            (Some(resolved), Some(expected)) => self.types[resolved].inner == self.types[expected].inner,
+            (None, None) => true,
+            _ => false,
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(ValidationError::ReturnTypeMismatch {
+                function: self.handle,
+                expected: self.fun.return_type,
+                value,
+            })
+        }
+    }
+
+    fn validate_block(&mut self, block: &[crate::Statement]) -> Result<(), ValidationError> {
+        for statement in block {
+            use crate::Statement as S;
+            match *statement {
+                S::Empty |
+                S::Line(_) |
+                S::Break |
+                S::Continue |
+                S::Kill => (),
+                S::Block(ref b) => {
+                    self.validate_block(b)?;
+                }
+                S::If { condition, ref accept, ref reject } => {
+                    self.check_expression(condition)?;
+                    self.validate_block(accept)?;
+                    self.validate_block(reject)?;
+                }
+                S::Switch { selector, ref cases, ref default } => {
+                    self.check_expression(selector)?;
+                    for (case, _) in cases.values() {
+                        self.validate_block(case)?;
+                    }
+                    self.validate_block(default)?;
+                }
+                S::Loop { ref body, ref continuing } => {
+                    self.validate_block(body)?;
+                    self.validate_block(continuing)?;
+                }
+                S::Return { value } => {
+                    if let Some(expr) = value {
+                        self.check_expression(expr)?;
+                    }
+                    self.validate_return(value)?;
+                }
+                S::Call { function, ref arguments } => {
+                    check(&self.module.functions, function, ValidationError::InvalidFunctionHandle)?;
+                    for &argument in arguments {
+                        self.check_expression(argument)?;
+                    }
+                }
+                S::Store { pointer, value } => {
+                    self.check_expression(pointer)?;
+                    self.check_expression(value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&mut self) -> Result<(), ValidationError> {
+        for &ty in self.fun.parameter_types.iter().chain(&self.fun.return_type) {
+            self.check_type(ty)?;
+        }
+        for (_, var) in self.fun.local_variables.iter() {
+            self.check_type(var.ty)?;
+            if let Some(init) = var.init {
+                self.check_expression(init)?;
+            }
+        }
+        for (_, expression) in self.fun.expressions.iter() {
+            self.validate_expression(expression)?;
+        }
+        self.validate_block(&self.fun.body)
+    }
+}
+
+/// Built-ins that an entry point of the given stage may use, by storage class.
+const BUILT_INS: &[(crate::ShaderStage, crate::StorageClass, spirv::BuiltIn)] = {
+    use crate::{ShaderStage as Ss, StorageClass as Sc};
+    use spirv::BuiltIn as Bi;
+    &[
+        (Ss::Vertex, Sc::Input, Bi::VertexIndex),
+        (Ss::Vertex, Sc::Input, Bi::InstanceIndex),
+        (Ss::Vertex, Sc::Input, Bi::BaseVertex),
+        (Ss::Vertex, Sc::Input, Bi::BaseInstance),
+        (Ss::Vertex, Sc::Input, Bi::DrawIndex),
+        (Ss::Vertex, Sc::Output, Bi::Position),
+        (Ss::Vertex, Sc::Output, Bi::PointSize),
+        (Ss::Vertex, Sc::Output, Bi::ClipDistance),
+        (Ss::Vertex, Sc::Output, Bi::CullDistance),
+        (Ss::Fragment, Sc::Input, Bi::FragCoord),
+        (Ss::Fragment, Sc::Input, Bi::FrontFacing),
+        (Ss::Fragment, Sc::Input, Bi::PointCoord),
+        (Ss::Fragment, Sc::Input, Bi::PrimitiveId),
+        (Ss::Fragment, Sc::Input, Bi::SampleId),
+        (Ss::Fragment, Sc::Input, Bi::SamplePosition),
+        (Ss::Fragment, Sc::Input, Bi::SampleMask),
+        (Ss::Fragment, Sc::Input, Bi::ClipDistance),
+        (Ss::Fragment, Sc::Input, Bi::CullDistance),
+        (Ss::Fragment, Sc::Output, Bi::FragDepth),
+        (Ss::Fragment, Sc::Output, Bi::SampleMask),
+        (Ss::Compute, Sc::Input, Bi::GlobalInvocationId),
+        (Ss::Compute, Sc::Input, Bi::LocalInvocationId),
+        (Ss::Compute, Sc::Input, Bi::LocalInvocationIndex),
+        (Ss::Compute, Sc::Input, Bi::WorkgroupId),
+        (Ss::Compute, Sc::Input, Bi::NumWorkgroups),
+    ]
+};
+
+/// Check that a scalar width, in bits, is valid for the given kind.
+fn is_valid_scalar_width(kind: crate::ScalarKind, width: crate::Bytes) -> bool {
+    match kind {
+        crate::ScalarKind::Bool => width == 1,
+        crate::ScalarKind::Sint |
+        crate::ScalarKind::Uint => [8, 16, 32, 64].contains(&width),
+        crate::ScalarKind::Float => [16, 32, 64].contains(&width),
+    }
+}
+
+fn check<T>(
+    arena: &Arena<T>,
+    handle: Handle<T>,
+    error: impl FnOnce(Handle<T>) -> ValidationError,
+) -> Result<(), ValidationError> {
+    match arena.try_get(handle) {
+        Some(_) => Ok(()),
+        None => Err(error(handle)),
+    }
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Validator {}
+    }
+
+    pub fn validate(&self, module: &crate::Module) -> Result<(), ValidationError> {
+        let check_type = |handle| check(&module.types, handle, ValidationError::InvalidTypeHandle);
+
+        for (_, ty) in module.types.iter() {
+            match ty.inner {
+                crate::TypeInner::Scalar { .. } |
+                crate::TypeInner::Vector { .. } |
+                crate::TypeInner::Matrix { .. } |
+                crate::TypeInner::Sampler { .. } => (),
+                crate::TypeInner::Pointer { base, .. } |
+                crate::TypeInner::Array { base, .. } |
+                crate::TypeInner::Image { base, .. } => check_type(base)?,
+                crate::TypeInner::Struct { ref members } => {
+                    for member in members {
+                        check_type(member.ty)?;
+                    }
+                }
+            }
+        }
+
+        for (handle, constant) in module.constants.iter() {
+            check_type(constant.ty)?;
+            let kind = match constant.inner {
+                crate::ConstantInner::Sint(_) => crate::ScalarKind::Sint,
+                crate::ConstantInner::Uint(_) => crate::ScalarKind::Uint,
+                crate::ConstantInner::Float(_) => crate::ScalarKind::Float,
+                crate::ConstantInner::Bool(_) => crate::ScalarKind::Bool,
+                crate::ConstantInner::Composite(ref constituents) => {
+                    for &constituent in constituents {
+                        check(&module.constants, constituent, ValidationError::InvalidConstantHandle)?;
+                    }
+                    continue;
+                }
+            };
+            if let crate::TypeInner::Scalar { width, .. } = module.types[constant.ty].inner {
+                if !is_valid_scalar_width(kind, width) {
+                    return Err(ValidationError::InvalidScalarWidth { constant: handle, width });
+                }
+            }
+        }
+
+        for (_, var) in module.global_variables.iter() {
+            check_type(var.ty)?;
+            if let Some(init) = var.init {
+                check(&module.constants, init, ValidationError::InvalidConstantHandle)?;
+            }
+        }
+
+        let mut types = module.types.clone();
+        for (handle, fun) in module.functions.iter() {
+            FunctionValidator {
+                module,
+                handle,
+                fun,
+                typifier: Typifier::new(),
+                types: &mut types,
+            }.validate()?;
+        }
+
+        if module.functions.is_empty() && !module.entry_points.is_empty() {
+            return Err(ValidationError::MissingFunctions);
+        }
+        for entry_point in module.entry_points.iter() {
+            check(&module.functions, entry_point.function, ValidationError::InvalidFunctionHandle)?;
+            let fun = &module.functions[entry_point.function];
+            for ((handle, var), usage) in module.global_variables.iter().zip(&fun.global_usage) {
+                let built_in = match var.binding {
+                    Some(crate::Binding::BuiltIn(built_in)) if !usage.is_empty() => built_in,
+                    _ => continue,
+                };
+                if !BUILT_INS.contains(&(entry_point.stage, var.class, built_in)) {
+                    return Err(ValidationError::InvalidBuiltIn {
+                        stage: entry_point.stage,
+                        variable: handle,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ValidationError, Validator};
+    use crate::arena::Arena;
+
+    fn empty_function() -> crate::Function {
+        crate::Function {
+            name: None,
+            control: spirv::FunctionControl::empty(),
+            parameter_types: Vec::new(),
+            return_type: None,
+            global_usage: Vec::new(),
+            local_variables: Arena::new(),
+            expressions: Arena::new(),
+            expression_spans: crate::FastHashMap::default(),
+            body: vec![crate::Statement::Return { value: None }],
+        }
+    }
+
+    fn empty_module() -> crate::Module {
+        crate::Module::new(crate::Header::default())
+    }
+
+    fn module_with_entry_point(function: crate::Handle<crate::Function>) -> crate::Module {
+        let mut module = empty_module();
+        module.functions.append(empty_function());
+        module.entry_points.push(crate::EntryPoint {
+            stage: crate::ShaderStage::Vertex,
+            name: "main".to_owned(),
+            function,
+            workgroup_size: [0; 3],
+        });
+        module
+    }
+
+    /// Builds a module with a function returning `return_type`,
+    /// optionally returning a `f32` constant.
+    fn module_with_return(return_type: Option<crate::TypeInner>, return_value: bool) -> crate::Module {
+        let mut module = empty_module();
+        let float_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let constant = module.constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Float(1.0),
+            ty: float_ty,
+        });
+        let mut fun = empty_function();
+        fun.return_type = return_type.map(|inner| module.types.append(crate::Type {
+            name: None,
+            inner,
+        }));
+        let value = if return_value {
+            Some(fun.expressions.append(crate::Expression::Constant(constant)))
+        } else {
+            None
+        };
+        fun.body = vec![crate::Statement::Return { value }];
+        module.functions.append(fun);
+        module
+    }
+
+    #[test]
+    fn valid_entry_point() {
+        let mut functions = Arena::new();
+        let function = functions.append(empty_function());
+        let module = module_with_entry_point(function);
+        assert!(Validator::new().validate(&module).is_ok());
+    }
+
+    #[test]
+    fn dangling_entry_point() {
+        let mut functions = Arena::new();
+        functions.append(empty_function());
+        let dangling = functions.append(empty_function());
+        let module = module_with_entry_point(dangling);
+        match Validator::new().validate(&module) {
+            Err(ValidationError::InvalidFunctionHandle(handle)) => assert_eq!(handle, dangling),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn entry_point_without_functions() {
+        let mut module = module_with_entry_point(crate::Handle::from_usize(0).unwrap());
+        module.functions.clear();
+        match Validator::new().validate(&module) {
+            Err(ValidationError::MissingFunctions) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn uniform_global() {
+        let mut module = empty_module();
+        let float_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let handle = module.global_variables.append(crate::GlobalVariable {
+            name: Some("scale".to_owned()),
+            class: crate::StorageClass::Uniform,
+            binding: Some(crate::Binding::Resource { group: 0, binding: 1 }),
+            interpolation: None,
+            sampling: None,
+            ty: float_ty,
+            init: None,
+        });
+        assert!(Validator::new().validate(&module).is_ok());
+
+        let mut types = module.types.clone();
+        let dangling = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Sampler { comparison: false },
+        });
+        module.global_variables[handle].ty = dangling;
+        match Validator::new().validate(&module) {
+            Err(ValidationError::InvalidTypeHandle(ty)) => assert_eq!(ty, dangling),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn return_type_match() {
+        let validator = Validator::new();
+        let float = crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 };
+        assert!(validator.validate(&module_with_return(Some(float), true)).is_ok());
+        assert!(validator.validate(&module_with_return(None, false)).is_ok());
+    }
+
+    #[test]
+    fn return_type_mismatch() {
+        let validator = Validator::new();
+        let float = crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 };
+        let vector = crate::TypeInner::Vector {
+            size: crate::VectorSize::Bi,
+            kind: crate::ScalarKind::Float,
+            width: 32,
+        };
+        for &(ref return_type, return_value) in &[
+            (Some(vector), true),
+            (None, true),
+            (Some(float), false),
+        ] {
+            let module = module_with_return(return_type.clone(), return_value);
+            match validator.validate(&module) {
+                Err(ValidationError::ReturnTypeMismatch { .. }) => (),
+                other => panic!("Unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn scalar_constant_width() {
+        let mut module = empty_module();
+        let float_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let constant = module.constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Float(1.0),
+            ty: float_ty,
+        });
+        assert!(Validator::new().validate(&module).is_ok());
+
+        module.types[float_ty].inner = crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 24 };
+        match Validator::new().validate(&module) {
+            Err(ValidationError::InvalidScalarWidth { constant: handle, width: 24 }) => {
+                assert_eq!(handle, constant)
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn built_in_stage() {
+        let mut module = module_with_entry_point(crate::Handle::from_usize(0).unwrap());
+        let vec4_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Vector {
+                size: crate::VectorSize::Quad,
+                kind: crate::ScalarKind::Float,
+                width: 32,
+            },
+        });
+        let position = module.global_variables.append(crate::GlobalVariable {
+            name: None,
+            class: crate::StorageClass::Output,
+            binding: Some(crate::Binding::BuiltIn(spirv::BuiltIn::Position)),
+            interpolation: None,
+            sampling: None,
+            ty: vec4_ty,
+            init: None,
+        });
+        let main = module.entry_points[0].function;
+        module.functions[main].global_usage = vec![crate::GlobalUse::STORE];
+        assert!(Validator::new().validate(&module).is_ok());
+
+        module.entry_points[0].stage = crate::ShaderStage::Fragment;
+        module.global_variables[position].class = crate::StorageClass::Input;
+        module.global_variables[position].binding = Some(crate::Binding::BuiltIn(spirv::BuiltIn::VertexIndex));
+        module.functions[main].global_usage = vec![crate::GlobalUse::LOAD];
+        match Validator::new().validate(&module) {
+            Err(ValidationError::InvalidBuiltIn { stage: crate::ShaderStage::Fragment, variable }) => {
+                assert_eq!(variable, position)
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn function_type_handles() {
+        let mut module = empty_module();
+        let float_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let mut fun = empty_function();
+        let parameter = fun.expressions.append(crate::Expression::FunctionParameter(0));
+        fun.parameter_types = vec![float_ty];
+        fun.return_type = Some(float_ty);
+        fun.body = vec![crate::Statement::Return { value: Some(parameter) }];
+        let handle = module.functions.append(fun);
+        assert!(Validator::new().validate(&module).is_ok());
+
+        let mut types = module.types.clone();
+        let dangling = types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Sampler { comparison: false },
+        });
+        module.functions[handle].parameter_types[0] = dangling;
+        match Validator::new().validate(&module) {
+            Err(ValidationError::InvalidTypeHandle(ty)) => assert_eq!(ty, dangling),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn return_unresolved() {
+        let mut module = empty_module();
+        let float_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let mut fun = empty_function();
+        let parameter = fun.expressions.append(crate::Expression::FunctionParameter(0));
+        let value = fun.expressions.append(crate::Expression::Call {
+            origin: crate::FunctionOrigin::External("sin".to_owned()),
+            arguments: vec![parameter],
+        });
+        fun.parameter_types = vec![float_ty];
+        fun.return_type = Some(float_ty);
+        fun.body = vec![crate::Statement::Return { value: Some(value) }];
+        let handle = module.functions.append(fun);
+        match Validator::new().validate(&module) {
+            Err(ValidationError::InvalidResolve {
+                error: crate::proc::ResolveError::UnknownExternalFunction(ref name),
+                ..
+            }) => assert_eq!(name, "sin"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        // an operand after the expression that uses it
+        let fun = &mut module.functions[handle];
+        let negate = fun.expressions.append(crate::Expression::Unary {
+            op: crate::UnaryOperator::Negate,
+            expr: value,
+        });
+        fun.expressions[value] = crate::Expression::Unary {
+            op: crate::UnaryOperator::Negate,
+            expr: negate,
+        };
+        match Validator::new().validate(&module) {
+            Err(ValidationError::InvalidResolve {
+                error: crate::proc::ResolveError::ForwardReference(expr),
+                ..
+            }) => assert_eq!(expr, value),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn call_expression() {
+        let mut module = empty_module();
+        let float_ty = module.types.append(crate::Type {
+            name: None,
+            inner: crate::TypeInner::Scalar { kind: crate::ScalarKind::Float, width: 32 },
+        });
+        let callee = module.functions.append(empty_function());
+        let mut fun = empty_function();
+        fun.expressions.append(crate::Expression::Call {
+            origin: crate::FunctionOrigin::Local(callee),
+            arguments: Vec::new(),
+        });
+        let caller = module.functions.append(fun);
+        match Validator::new().validate(&module) {
+            Err(ValidationError::VoidFunctionCall { function, callee: handle }) => {
+                assert_eq!(function, caller);
+                assert_eq!(handle, callee);
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let one = module.constants.append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Float(1.0),
+            ty: float_ty,
+        });
+        let callee_fun = &mut module.functions[callee];
+        let value = callee_fun.expressions.append(crate::Expression::Constant(one));
+        callee_fun.return_type = Some(float_ty);
+        callee_fun.body = vec![crate::Statement::Return { value: Some(value) }];
+        assert!(Validator::new().validate(&module).is_ok());
+
+        let dangling = crate::Handle::from_usize(41).unwrap();
+        module.functions[caller].expressions = Arena::new();
+        module.functions[caller].expressions.append(crate::Expression::Call {
+            origin: crate::FunctionOrigin::Local(dangling),
+            arguments: Vec::new(),
+        });
+        match Validator::new().validate(&module) {
+            Err(ValidationError::InvalidFunctionHandle(handle)) => assert_eq!(handle, dangling),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -1,7 +1,23 @@
 fn load_wgsl(name: &str) -> naga::Module {
     let path = format!("{}/test-data/{}.wgsl", env!("CARGO_MANIFEST_DIR"), name);
     let input = std::fs::read_to_string(path).unwrap();
-    naga::front::wgsl::parse_str(&input).unwrap()
+    let module = naga::front::wgsl::parse_str(&input).unwrap();
+    naga::proc::Validator::new().validate(&module).unwrap();
+    module
+}
+
+#[test]
+fn entry_point_handles() {
+    let module = load_wgsl("quad");
+    let names = module
+        .entry_points
+        .iter()
+        .map(|ep| {
+            let function: naga::Handle<naga::Function> = ep.function;
+            module.functions[function].name.as_deref()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(names, [Some("main_vert"), Some("main_frag")]);
 }
 
 #[test]
@@ -59,3 +75,33 @@ fn boids_serde_round_trip() {
     let module_ser_ser = ron::ser::to_string(&module_de).unwrap();
     assert_eq!(module_ser, module_ser_ser);
 }
+
+#[test]
+#[cfg(all(feature = "serialize", feature = "deserialize"))]
+fn spirv_serde_round_trip() {
+    let mut module = naga::Module::new(naga::Header::default());
+    let function = module.functions.append(naga::Function {
+        name: Some("main".to_owned()),
+        control: spirv::FunctionControl::empty(),
+        parameter_types: Vec::new(),
+        return_type: None,
+        global_usage: Vec::new(),
+        local_variables: naga::Arena::new(),
+        expressions: naga::Arena::new(),
+        expression_spans: naga::FastHashMap::default(),
+        body: vec![naga::Statement::Return { value: None }],
+    });
+    module.entry_points.push(naga::EntryPoint {
+        stage: naga::ShaderStage::Compute,
+        name: "main".to_owned(),
+        function,
+        workgroup_size: [8, 8, 1],
+    });
+
+    let words = naga::back::spv::Writer::new().write(&module).unwrap();
+    let module = naga::front::spirv::Parser::new(words.into_iter()).parse().unwrap();
+    let module_ser = ron::ser::to_string(&module).unwrap();
+    let module_de = ron::de::from_str::<naga::Module>(&module_ser).unwrap();
+    let module_ser_ser = ron::ser::to_string(&module_de).unwrap();
+    assert_eq!(module_ser, module_ser_ser);
+}